@@ -6,6 +6,7 @@ import './App.css';
 
 interface Service {
   service_id: string;
+  namespace: string;
   service_name: string;
   service_version: string;
   service_address: string;
@@ -14,7 +15,8 @@ interface Service {
   metadata: Record<string, string>;
   registered_at: string;
   last_heartbeat: string;
-  status: string; // "online", "offline", or "busy"
+  status: string; // "online", "offline", "busy", or "recovering"
+  circuit_state: string; // "closed", "open", or "half_open"
 }
 
 interface MethodSchema {
@@ -122,10 +124,20 @@ function App() {
         return prevServices;
       });
     },
+    onCircuitChange: (data) => {
+      setServices(prevServices => prevServices.map(service =>
+        service.service_id === data.service_id
+          ? { ...service, circuit_state: data.circuit_state }
+          : service
+      ));
+    },
     onServiceRegistered: () => {
       fetchServices();
       fetchSchemas();
     },
+    onServiceRemoved: (data) => {
+      setServices(prevServices => prevServices.filter(service => service.service_id !== data.service_id));
+    },
     onConnection: (data) => {
       console.log('SSE connected:', data.message);
     }
@@ -194,13 +206,20 @@ function App() {
                     onClick={() => setSelectedService(service.service_id)}
                   >
                     <div className="service-item-header">
-                      <span className="service-item-name">{service.service_name}</span>
+                      <span className="service-item-name">
+                        {service.namespace !== 'default' && <span className="service-item-namespace">{service.namespace}/</span>}
+                        {service.service_name}
+                      </span>
                       <span className="service-item-version">v{service.service_version}</span>
                       <span className={`service-status ${service.status}`}>
                         {service.status === 'online' && '🟢'}
                         {service.status === 'offline' && '🔴'}
                         {service.status === 'busy' && '🟠'}
+                        {service.status === 'recovering' && '🟡'}
                       </span>
+                      {service.circuit_state && service.circuit_state !== 'closed' && (
+                        <span className={`service-item-circuit ${service.circuit_state}`} title={`Circuit ${service.circuit_state.replace('_', '-')}`}>⚡</span>
+                      )}
                     </div>
                     <div className="service-item-address">{service.service_address}:{service.service_port}</div>
                     <div className="service-item-methods">{service.methods.length} method{service.methods.length !== 1 ? 's' : ''}</div>
@@ -351,7 +370,8 @@ function ServiceCard({
         console.log('Service unregistered:', service.service_name);
         onUnregister();
       } else {
-        alert('Failed to unregister service: ' + result.message);
+        // Only operators may unregister an instance without its secret
+        alert('Failed to unregister service: ' + (result.message || result.error));
       }
     } catch (error) {
       alert('Error unregistering service: ' + (error instanceof Error ? error.message : 'Unknown error'));
@@ -373,6 +393,7 @@ function ServiceCard({
           {service.status === 'online' && <span className="status-online">🟢 Online</span>}
           {service.status === 'offline' && <span className="status-offline">🔴 Offline</span>}
           {service.status === 'busy' && <span className="status-busy">🟠 Busy</span>}
+          {service.status === 'recovering' && <span className="status-recovering">🟡 Recovering</span>}
         </div>
         <button 
           className="unregister-button" 
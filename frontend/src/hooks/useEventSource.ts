@@ -2,7 +2,9 @@ import { useState, useEffect, useRef, useCallback } from 'react';
 
 interface SSEEventHandlers {
     onStatusChange?: (data: any) => void;
+    onCircuitChange?: (data: any) => void;
     onServiceRegistered?: (data: any) => void;
+    onServiceRemoved?: (data: any) => void;
     onConnection?: (data: any) => void;
 }
 
@@ -126,6 +128,32 @@ export function useEventSource(
             }
         });
 
+        // Handle circuit_breaker events
+        eventSource.addEventListener('circuit_breaker', (event: MessageEvent) => {
+            connectionEstablishedRef.current = true;
+            lastKeepAliveTimeRef.current = Date.now();
+            setIsConnected(true);
+            const data = JSON.parse(event.data);
+            console.log('Circuit breaker event:', data);
+            if (handlersRef.current.onCircuitChange) {
+                handlersRef.current.onCircuitChange(data);
+            }
+        });
+
+        // Handle service_unregistered and service_evicted events
+        for (const eventType of ['service_unregistered', 'service_evicted']) {
+            eventSource.addEventListener(eventType, (event: MessageEvent) => {
+                connectionEstablishedRef.current = true;
+                lastKeepAliveTimeRef.current = Date.now();
+                setIsConnected(true);
+                const data = JSON.parse(event.data);
+                console.log('Service removed event:', eventType, data);
+                if (handlersRef.current.onServiceRemoved) {
+                    handlersRef.current.onServiceRemoved(data);
+                }
+            });
+        }
+
         // Handle connection events
         eventSource.addEventListener('connection', (event: MessageEvent) => {
             connectionEstablishedRef.current = true;
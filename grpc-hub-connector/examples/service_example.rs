@@ -6,6 +6,7 @@ use serde_json::Value;
 struct MyService {
     connector: GrpcHubConnector,
     service_id: String,
+    #[allow(dead_code)]
     data: HashMap<String, Value>,
 }
 
@@ -18,7 +19,7 @@ impl MyService {
         }
     }
     
-    async fn register_with_hub(&self, service_name: &str, service_port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    async fn register_with_hub(&self, service_name: &str, _service_port: u16) -> Result<(), Box<dyn std::error::Error>> {
         println!("📝 Registering service '{}' with hub...", service_name);
         
         // In a real implementation, you would register with the hub here
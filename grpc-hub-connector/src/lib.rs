@@ -1,24 +1,34 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use anyhow::Result;
 
-mod grpc_hub {
+/// The hub's protobuf messages, e.g. the `RegisterServiceRequest` for `GrpcHubConnector::register`
+pub mod grpc_hub {
     tonic::include_proto!("grpc_hub");
 }
 
 use grpc_hub::grpc_hub_client::GrpcHubClient;
-use grpc_hub::{ListServicesRequest, UpdateServiceStatusRequest};
+use tonic::transport::{Channel, Endpoint};
+use grpc_hub::{ListServicesRequest, RegisterServiceRequest, RegisterServiceResponse, SubscribeRequest, UpdateServiceStatusRequest};
+
+/// TLS settings for `GrpcHubConnector::with_tls`
+pub use tonic::transport::{Certificate, ClientTlsConfig, Identity};
 
 /// A reusable connector for discovering and connecting to services through the gRPC hub
 #[derive(Debug, Clone)]
 pub struct GrpcHubConnector {
-    hub_host: String,
-    hub_port: u16,
+    hub_endpoints: Vec<(String, u16)>, // Every hub in the cluster; the first one that answers is used
+    active_endpoint: Arc<AtomicUsize>, // Index of the hub that answered last
     service_cache: Arc<RwLock<Option<(String, u16)>>>,
     cache_timestamp: Arc<AtomicU64>,
     cache_duration_seconds: u64,
+    namespace: Option<String>, // Hub namespace to discover in and subscribe to; None means all of them
+    credentials: Option<String>, // API key or bearer token sent with every request to the hub
+    tls: Option<ClientTlsConfig>, // CA, client certificate and name the hub is reached over TLS with; None means plaintext
+    instance_secrets: Arc<RwLock<HashMap<String, String>>>, // Secret the hub issued to each instance at registration, by service ID
 }
 
 impl GrpcHubConnector {
@@ -30,52 +40,155 @@ impl GrpcHubConnector {
     /// Create a new connector with custom hub host and port
     pub fn with_hub_connection(hub_host: String, hub_port: u16) -> Self {
         Self {
-            hub_host,
-            hub_port,
+            hub_endpoints: vec![(hub_host, hub_port)],
+            active_endpoint: Arc::new(AtomicUsize::new(0)),
             service_cache: Arc::new(RwLock::new(None)),
             cache_timestamp: Arc::new(AtomicU64::new(0)),
             cache_duration_seconds: 30, // Default 30 seconds cache
+            namespace: None,
+            credentials: None,
+            tls: None,
+            instance_secrets: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
     /// Create a new connector with a custom hub endpoint (for backward compatibility)
     pub fn with_hub_endpoint(hub_endpoint: String) -> Self {
-        // Parse the endpoint to extract host and port
-        let (host, port) = if hub_endpoint.starts_with("http://") {
-            let without_protocol = &hub_endpoint[7..];
-            if let Some(colon_pos) = without_protocol.find(':') {
-                let host = without_protocol[..colon_pos].to_string();
-                let port = without_protocol[colon_pos + 1..].parse().unwrap_or(50099);
-                (host, port)
-            } else {
-                (without_protocol.to_string(), 50099)
-            }
-        } else {
-            (hub_endpoint, 50099)
-        };
-        
+        let (host, port) = parse_hub_endpoint(&hub_endpoint);
         Self::with_hub_connection(host, port)
     }
 
+    /// Create a new connector for a cluster of hubs (e.g. "http://10.0.0.1:50099").
+    /// Calls go to the hub that answered last and fail over to the others in order.
+    pub fn with_hub_endpoints(hub_endpoints: Vec<String>) -> Self {
+        let mut connector = Self::new();
+        if !hub_endpoints.is_empty() {
+            connector.hub_endpoints = hub_endpoints.iter().map(|e| parse_hub_endpoint(e)).collect();
+        }
+        connector
+    }
+
     /// Set custom cache duration in seconds
     pub fn with_cache_duration(mut self, duration_seconds: u64) -> Self {
         self.cache_duration_seconds = duration_seconds;
         self
     }
 
-    /// Get the hub endpoint
+    /// Discover services in, and subscribe to events from, one hub namespace only
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Authenticate to the hub with an API key or a bearer token (JWT)
+    pub fn with_credentials(mut self, token: &str) -> Self {
+        self.credentials = Some(token.to_string());
+        self
+    }
+
+    /// Connect to the hub over TLS, e.g. `ClientTlsConfig::new().ca_certificate(ca)` plus
+    /// `.identity(Identity::from_pem(cert, key))` for a hub that requires client certificates
+    pub fn with_tls(mut self, tls: ClientTlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Register an instance with the hub and remember the `instance_secret` it is issued, so
+    /// `set_service_busy` and `set_service_online` carry it from then on
+    pub async fn register(&self, registration: RegisterServiceRequest) -> Result<RegisterServiceResponse> {
+        let mut client = self.connect_hub().await?;
+        let response = client
+            .register_service(self.request(registration))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to register service: {}", e))?
+            .into_inner();
+        if !response.success {
+            return Err(anyhow::anyhow!("Failed to register service: {}", response.message));
+        }
+        self.remember_registration(&response).await;
+        Ok(response)
+    }
+
+    async fn remember_registration(&self, response: &RegisterServiceResponse) {
+        self.set_instance_secret(&response.service_id, &response.instance_secret).await;
+    }
+
+    /// Remember the `instance_secret` the hub issued when `service_id` registered through
+    /// another client; status updates for that instance carry it from then on
+    pub async fn set_instance_secret(&self, service_id: &str, instance_secret: &str) {
+        self.instance_secrets.write().await.insert(service_id.to_string(), instance_secret.to_string());
+    }
+
+    async fn instance_secret(&self, service_id: &str) -> String {
+        self.instance_secrets.read().await.get(service_id).cloned().unwrap_or_default()
+    }
+
+    fn scheme(&self) -> &'static str {
+        if self.tls.is_some() { "https" } else { "http" }
+    }
+
+    /// A request to the hub carrying this connector's credentials
+    fn request<T>(&self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        if let Some(value) = self.credentials.as_ref().and_then(|token| format!("Bearer {}", token).parse().ok()) {
+            request.metadata_mut().insert("authorization", value);
+        }
+        request
+    }
+
+    fn active_hub(&self) -> &(String, u16) {
+        let index = self.active_endpoint.load(Ordering::Relaxed) % self.hub_endpoints.len();
+        &self.hub_endpoints[index]
+    }
+
+    /// Get the hub endpoint (the active one when several hubs are configured)
     pub fn get_hub_endpoint(&self) -> String {
-        format!("http://{}:{}", self.hub_host, self.hub_port)
+        let (host, port) = self.active_hub();
+        format!("{}://{}:{}", self.scheme(), host, port)
+    }
+
+    /// Get every configured hub endpoint
+    pub fn get_hub_endpoints(&self) -> Vec<String> {
+        self.hub_endpoints.iter().map(|(host, port)| format!("{}://{}:{}", self.scheme(), host, port)).collect()
     }
 
     /// Get the hub host
     pub fn get_hub_host(&self) -> String {
-        self.hub_host.clone()
+        self.active_hub().0.clone()
     }
 
     /// Get the hub port
     pub fn get_hub_port(&self) -> u16 {
-        self.hub_port
+        self.active_hub().1
+    }
+
+    /// Connect to the active hub, failing over to the next configured hub if it is unreachable
+    async fn connect_hub(&self) -> Result<GrpcHubClient<Channel>> {
+        let start = self.active_endpoint.load(Ordering::Relaxed);
+        let mut last_error = None;
+        for offset in 0..self.hub_endpoints.len() {
+            let index = (start + offset) % self.hub_endpoints.len();
+            let (host, port) = &self.hub_endpoints[index];
+            let endpoint = format!("{}://{}:{}", self.scheme(), host, port);
+            let mut builder = Endpoint::from_shared(endpoint.clone())?;
+            if let Some(tls) = &self.tls {
+                builder = builder.tls_config(tls.clone())?;
+            }
+            match builder.connect().await.map(GrpcHubClient::new) {
+                Ok(client) => {
+                    if index != start % self.hub_endpoints.len() {
+                        println!("🔍 [DEBUG] GrpcHubConnector: Failed over to hub at {}", endpoint);
+                    }
+                    self.active_endpoint.store(index, Ordering::Relaxed);
+                    return Ok(client);
+                }
+                Err(e) => {
+                    println!("❌ [DEBUG] GrpcHubConnector: Hub at {} unreachable: {}", endpoint, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(anyhow::anyhow!("No hub reachable: {}", last_error.map(|e| e.to_string()).unwrap_or_default()))
     }
 
     /// Get the address and port of a service, using cache if available
@@ -97,18 +210,30 @@ impl GrpcHubConnector {
 
     /// Discover a service from the hub (bypasses cache)
     pub async fn discover_service(&self, service_name: &str) -> Result<(String, u16)> {
-        println!("🔍 [DEBUG] GrpcHubConnector: Starting service discovery for: {}", service_name);
+        self.discover_service_matching(service_name, "").await
+    }
+
+    /// Discover an instance of a service whose metadata matches a label selector, e.g.
+    /// `environment=production,team in (finance,ops),!deprecated` (bypasses cache).
+    /// `namespace/service` looks in another namespace than the connector's.
+    pub async fn discover_service_matching(&self, service_name: &str, label_selector: &str) -> Result<(String, u16)> {
+        let (namespace, service_name) = match service_name.split_once('/') {
+            Some((namespace, service_name)) => (Some(namespace.to_string()), service_name),
+            None => (self.namespace.clone(), service_name),
+        };
+        println!("🔍 [DEBUG] GrpcHubConnector: Starting service discovery for: {} (selector: '{}')", service_name, label_selector);
         
         // Connect to the hub's gRPC API
-        let hub_endpoint = self.get_hub_endpoint();
-        println!("🔍 [DEBUG] GrpcHubConnector: Connecting to hub at {}", hub_endpoint);
+        println!("🔍 [DEBUG] GrpcHubConnector: Connecting to hub at {}", self.get_hub_endpoint());
         
-        let mut hub_client = GrpcHubClient::connect(hub_endpoint).await?;
+        let mut hub_client = self.connect_hub().await?;
         println!("🔍 [DEBUG] GrpcHubConnector: Successfully connected to hub");
         
-        // Get registered services from the hub
-        let request = tonic::Request::new(ListServicesRequest {
+        // Get registered services from the hub, filtered by the selector there
+        let request = self.request(ListServicesRequest {
             filter: None,
+            label_selector: (!label_selector.is_empty()).then(|| label_selector.to_string()),
+            namespace,
         });
         println!("🔍 [DEBUG] GrpcHubConnector: Requesting service list from hub");
         
@@ -124,6 +249,9 @@ impl GrpcHubConnector {
             .filter(|s| s.service_name == service_name)
             .collect();
         
+        if matching_services.is_empty() && !label_selector.is_empty() {
+            return Err(anyhow::anyhow!("Service '{}' matching '{}' not found in hub", service_name, label_selector));
+        }
         if matching_services.is_empty() {
             return Err(anyhow::anyhow!("Service '{}' not found in hub", service_name));
         }
@@ -156,11 +284,12 @@ impl GrpcHubConnector {
     pub async fn list_all_services(&self) -> Result<Vec<grpc_hub::ServiceInfo>> {
         println!("🔍 [DEBUG] GrpcHubConnector: Listing all services from hub");
         
-        let hub_endpoint = self.get_hub_endpoint();
-        let mut hub_client = GrpcHubClient::connect(hub_endpoint).await?;
+        let mut hub_client = self.connect_hub().await?;
         
-        let request = tonic::Request::new(ListServicesRequest {
+        let request = self.request(ListServicesRequest {
             filter: None,
+            label_selector: None,
+            namespace: self.namespace.clone(),
         });
         
         let response = hub_client.list_services(request).await?;
@@ -182,6 +311,23 @@ impl GrpcHubConnector {
         }
     }
 
+    /// Subscribe to hub events (registrations, unregistrations, status changes and heartbeats).
+    /// An empty `service_name` subscribes to every service, and empty `event_types` to every event.
+    pub async fn subscribe(&self, service_name: &str, event_types: Vec<String>) -> Result<tonic::Streaming<grpc_hub::ServiceEvent>> {
+        println!("🔍 [DEBUG] GrpcHubConnector: Subscribing to events for '{}'", service_name);
+        
+        let mut hub_client = self.connect_hub().await?;
+        
+        let request = self.request(SubscribeRequest {
+            service_name: service_name.to_string(),
+            event_types,
+            namespace: self.namespace.clone().unwrap_or_default(),
+        });
+        
+        let response = hub_client.subscribe_to_service(request).await?;
+        Ok(response.into_inner())
+    }
+
     /// Clear the service cache (force fresh discovery on next call)
     pub async fn clear_cache(&self) {
         println!("🔍 [DEBUG] GrpcHubConnector: Clearing service cache");
@@ -201,12 +347,12 @@ impl GrpcHubConnector {
     pub async fn set_service_busy(&self, service_id: &str) -> Result<()> {
         println!("🔍 [DEBUG] GrpcHubConnector: Setting service {} to busy via gRPC", service_id);
         
-        let hub_endpoint = self.get_hub_endpoint();
-        let mut client = GrpcHubClient::connect(hub_endpoint).await?;
+        let mut client = self.connect_hub().await?;
         
-        let request = tonic::Request::new(UpdateServiceStatusRequest {
+        let request = self.request(UpdateServiceStatusRequest {
             service_id: service_id.to_string(),
             status: "busy".to_string(),
+            instance_secret: self.instance_secret(service_id).await,
         });
         
         match client.update_service_status(request).await {
@@ -226,12 +372,12 @@ impl GrpcHubConnector {
     pub async fn set_service_online(&self, service_id: &str) -> Result<()> {
         println!("🔍 [DEBUG] GrpcHubConnector: Setting service {} to online via gRPC", service_id);
         
-        let hub_endpoint = self.get_hub_endpoint();
-        let mut client = GrpcHubClient::connect(hub_endpoint).await?;
+        let mut client = self.connect_hub().await?;
         
-        let request = tonic::Request::new(UpdateServiceStatusRequest {
+        let request = self.request(UpdateServiceStatusRequest {
             service_id: service_id.to_string(),
             status: "online".to_string(),
+            instance_secret: self.instance_secret(service_id).await,
         });
         
         match client.update_service_status(request).await {
@@ -248,6 +394,22 @@ impl GrpcHubConnector {
     }
 }
 
+/// Parse "http://host:port" or "https://host:port" (or a bare host) into a host and port,
+/// defaulting to port 50099
+fn parse_hub_endpoint(hub_endpoint: &str) -> (String, u16) {
+    if let Some(without_protocol) = hub_endpoint.strip_prefix("http://").or_else(|| hub_endpoint.strip_prefix("https://")) {
+        if let Some(colon_pos) = without_protocol.find(':') {
+            let host = without_protocol[..colon_pos].to_string();
+            let port = without_protocol[colon_pos + 1..].parse().unwrap_or(50099);
+            (host, port)
+        } else {
+            (without_protocol.to_string(), 50099)
+        }
+    } else {
+        (hub_endpoint.to_string(), 50099)
+    }
+}
+
 impl Default for GrpcHubConnector {
     fn default() -> Self {
         Self::new()
@@ -271,6 +433,55 @@ mod tests {
         assert_eq!(connector.get_hub_endpoint(), "http://localhost:9999");
     }
 
+    #[tokio::test]
+    async fn test_connector_with_several_hubs() {
+        let connector = GrpcHubConnector::with_hub_endpoints(vec![
+            "http://127.0.0.1:1".to_string(),
+            "http://127.0.0.1:2".to_string(),
+        ]);
+        assert_eq!(connector.get_hub_endpoint(), "http://127.0.0.1:1");
+        assert_eq!(connector.get_hub_endpoints().len(), 2);
+
+        // Every hub is tried before giving up
+        let error = connector.list_all_services().await.unwrap_err();
+        assert!(error.to_string().contains("No hub reachable"));
+    }
+
+    #[test]
+    fn test_connector_sends_credentials() {
+        let request = GrpcHubConnector::new().with_credentials("k1").request(());
+        assert_eq!(request.metadata().get("authorization").unwrap(), "Bearer k1");
+        assert!(GrpcHubConnector::new().request(()).metadata().get("authorization").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connector_remembers_secrets_of_its_registrations() {
+        let connector = GrpcHubConnector::new();
+        connector.remember_registration(&RegisterServiceResponse {
+            success: true,
+            service_id: "id-1".to_string(),
+            instance_secret: "s3cret".to_string(),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(connector.instance_secret("id-1").await, "s3cret");
+    }
+
+    #[tokio::test]
+    async fn test_connector_remembers_instance_secrets() {
+        let connector = GrpcHubConnector::new();
+        connector.clone().set_instance_secret("id-1", "s3cret").await;
+        assert_eq!(connector.instance_secret("id-1").await, "s3cret");
+        assert_eq!(connector.instance_secret("id-2").await, "");
+    }
+
+    #[test]
+    fn test_connector_with_tls_uses_https() {
+        let connector = GrpcHubConnector::with_hub_endpoint("https://hub.internal:50099".to_string())
+            .with_tls(ClientTlsConfig::new().domain_name("hub.internal"));
+        assert_eq!(connector.get_hub_endpoint(), "https://hub.internal:50099");
+    }
+
     #[tokio::test]
     async fn test_connector_with_custom_cache_duration() {
         let connector = GrpcHubConnector::new().with_cache_duration(60);
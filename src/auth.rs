@@ -0,0 +1,300 @@
+//! Authentication of callers of the hub's gRPC and HTTP APIs.
+//!
+//! With an `auth` section in the hub config file, every `GrpcHub` RPC, proxied call and
+//! `/api/*` request must carry a credential, as `authorization: Bearer <credential>` or in
+//! an `x-api-key` header (or, over HTTP, an `access_token` query parameter, for
+//! `EventSource`). A credential is one of the static API keys, or a JWT signed with HS256
+//! using `jwt.secret`:
+//!
+//! ```json
+//! {
+//!   "auth": {
+//!     "api_keys": { "dividend-service": "5f0c8e...", "ops-dashboard": "9a1e44..." },
+//!     "jwt": { "secret": "change-me", "issuer": "https://auth.example.com", "audience": "grpc-hub" }
+//!   }
+//! }
+//! ```
+//!
+//! The caller of an API key is the key's name; that of a JWT is its `sub` claim. Tokens
+//! are checked for `exp` and `nbf`, give or take `jwt.leeway_secs`. Over mutual TLS, a
+//! caller sending no credential is identified by its client certificate instead.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tonic::service::Interceptor;
+use tonic::{Code, Request, Status};
+
+use crate::grpc_client::GrpcCallError;
+use crate::tls::peer_identity;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// API key per caller name
+    pub api_keys: HashMap<String, String>,
+    /// Accept HS256 bearer tokens signed with this secret
+    pub jwt: Option<JwtConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    pub secret: String,
+    /// Required `iss` claim, if set
+    pub issuer: Option<String>,
+    /// Required `aud` claim (or one of them), if set
+    pub audience: Option<String>,
+    /// Clock skew allowed when checking `exp` and `nbf`
+    pub leeway_secs: i64,
+}
+
+impl AuthConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.api_keys.is_empty() || self.jwt.is_some()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some((name, _)) = self.api_keys.iter().find(|(_, key)| key.is_empty()) {
+            return Err(format!("Empty API key for '{}'", name));
+        }
+        match &self.jwt {
+            Some(jwt) if jwt.secret.is_empty() => Err("jwt.secret must not be empty".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The authenticated caller of a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// API key name or JWT subject
+    pub subject: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: Option<String>,
+    exp: Option<i64>,
+    nbf: Option<i64>,
+    iss: Option<String>,
+    aud: Option<serde_json::Value>,
+}
+
+/// Checks credentials against the configured API keys and JWT secret
+#[derive(Debug, Default)]
+pub struct Authenticator {
+    config: AuthConfig,
+}
+
+impl Authenticator {
+    pub fn new(config: AuthConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
+    }
+
+    /// The caller presenting `credential`, or `None` when authentication is off. Missing or
+    /// invalid credentials are UNAUTHENTICATED.
+    pub fn authenticate(&self, credential: Option<&str>) -> Result<Option<Identity>, GrpcCallError> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let credential = credential
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| GrpcCallError::new(Code::Unauthenticated, "Missing API key or bearer token"))?;
+
+        // Every key is compared, so timing doesn't tell how close a guess was
+        let key_owner = self.config.api_keys.iter()
+            .filter(|(_, key)| constant_time_eq(key.as_bytes(), credential.as_bytes()))
+            .fold(None, |_, (name, _)| Some(name));
+        if let Some(name) = key_owner {
+            return Ok(Some(Identity { subject: name.clone() }));
+        }
+        match &self.config.jwt {
+            Some(jwt) if credential.split('.').count() == 3 => verify_jwt(jwt, credential)
+                .map(Some)
+                .map_err(|e| GrpcCallError::new(Code::Unauthenticated, e)),
+            _ => Err(GrpcCallError::new(Code::Unauthenticated, "Invalid API key or bearer token")),
+        }
+    }
+
+    /// Like `authenticate`, for a caller whose verified client certificate identifies it as
+    /// `certificate`; that identity holds unless the caller also sends a credential
+    pub fn authenticate_peer(&self, credential: Option<&str>, certificate: Option<Identity>) -> Result<Option<Identity>, GrpcCallError> {
+        match certificate {
+            Some(identity) if credential.is_none_or(str::is_empty) => Ok(Some(identity)),
+            _ => self.authenticate(credential),
+        }
+    }
+}
+
+/// Rejects gRPC requests without valid credentials, and adds the caller's `Identity` to the
+/// request extensions of the rest
+#[derive(Debug, Clone)]
+pub struct AuthInterceptor {
+    auth: Arc<Authenticator>,
+}
+
+impl AuthInterceptor {
+    pub fn new(auth: Arc<Authenticator>) -> Self {
+        Self { auth }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let headers = request.metadata().clone().into_headers();
+        match self.auth.authenticate_peer(header_credential(&headers), peer_identity(request.peer_certs())) {
+            Ok(Some(identity)) => {
+                request.extensions_mut().insert(identity);
+                Ok(request)
+            }
+            Ok(None) => Ok(request),
+            Err(e) => {
+                println!("🔒 [AUTH] Rejected gRPC call: {}", e.message);
+                Err(Status::new(e.code, e.message))
+            }
+        }
+    }
+}
+
+/// The credential in an `authorization: Bearer ...` or `x-api-key` header
+pub fn header_credential(headers: &http::HeaderMap) -> Option<&str> {
+    let bearer = headers.get(http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer ").or_else(|| v.strip_prefix("bearer ")));
+    bearer
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn decode_part<T: serde::de::DeserializeOwned>(part: &str) -> Result<T, String> {
+    let bytes = URL_SAFE_NO_PAD.decode(part).map_err(|_| "Malformed token".to_string())?;
+    serde_json::from_slice(&bytes).map_err(|_| "Malformed token".to_string())
+}
+
+fn verify_jwt(config: &JwtConfig, token: &str) -> Result<Identity, String> {
+    let (signed, signature) = token.rsplit_once('.').ok_or("Malformed token")?;
+    let (header, payload) = signed.split_once('.').ok_or("Malformed token")?;
+
+    let header: serde_json::Value = decode_part(header)?;
+    if header.get("alg").and_then(|v| v.as_str()) != Some("HS256") {
+        return Err("Unsupported token algorithm; only HS256 is accepted".to_string());
+    }
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| "Malformed token")?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(config.secret.as_bytes()).map_err(|e| e.to_string())?;
+    mac.update(signed.as_bytes());
+    mac.verify_slice(&signature).map_err(|_| "Invalid token signature")?;
+
+    let claims: Claims = decode_part(payload)?;
+    let now = chrono::Utc::now().timestamp();
+    if claims.exp.is_some_and(|exp| now > exp + config.leeway_secs) {
+        return Err("Token expired".to_string());
+    }
+    if claims.nbf.is_some_and(|nbf| now + config.leeway_secs < nbf) {
+        return Err("Token not valid yet".to_string());
+    }
+    if config.issuer.as_ref().is_some_and(|issuer| claims.iss.as_ref() != Some(issuer)) {
+        return Err("Token has the wrong issuer".to_string());
+    }
+    if let Some(audience) = &config.audience {
+        let matches = match &claims.aud {
+            Some(serde_json::Value::String(aud)) => aud == audience,
+            Some(serde_json::Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+            _ => false,
+        };
+        if !matches {
+            return Err("Token has the wrong audience".to_string());
+        }
+    }
+    match claims.sub {
+        Some(subject) if !subject.is_empty() => Ok(Identity { subject }),
+        _ => Err("Token has no subject".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &str, claims: serde_json::Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.{}", header, payload).as_bytes());
+        format!("{}.{}.{}", header, payload, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+
+    fn authenticator() -> Authenticator {
+        let config: AuthConfig = serde_json::from_str(
+            r#"{"api_keys": {"dividend": "k1"}, "jwt": {"secret": "s3cret", "audience": "grpc-hub"}}"#,
+        )
+        .unwrap();
+        Authenticator::new(config)
+    }
+
+    #[test]
+    fn test_anonymous_callers_pass_when_auth_is_off() {
+        assert_eq!(Authenticator::default().authenticate(None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_api_keys_name_their_subject() {
+        let auth = authenticator();
+        assert_eq!(auth.authenticate(Some("k1")).unwrap().unwrap().subject, "dividend");
+        assert_eq!(auth.authenticate(None).unwrap_err().code, Code::Unauthenticated);
+        assert!(auth.authenticate(Some("k2")).is_err());
+    }
+
+    #[test]
+    fn test_valid_jwt_names_its_subject() {
+        let now = chrono::Utc::now().timestamp();
+        let token = sign("s3cret", serde_json::json!({"sub": "ops", "aud": ["grpc-hub"], "exp": now + 60}));
+        assert_eq!(authenticator().authenticate(Some(&token)).unwrap().unwrap().subject, "ops");
+    }
+
+    #[test]
+    fn test_expired_jwt_is_rejected() {
+        let expired = sign("s3cret", serde_json::json!({"sub": "ops", "aud": "grpc-hub", "exp": chrono::Utc::now().timestamp() - 60}));
+        assert!(authenticator().authenticate(Some(&expired)).unwrap_err().message.contains("expired"));
+    }
+
+    #[test]
+    fn test_forged_jwt_is_rejected() {
+        let forged = sign("guess", serde_json::json!({"sub": "ops", "aud": "grpc-hub"}));
+        assert!(authenticator().authenticate(Some(&forged)).unwrap_err().message.contains("signature"));
+    }
+
+    #[test]
+    fn test_jwt_for_another_audience_is_rejected() {
+        let elsewhere = sign("s3cret", serde_json::json!({"sub": "ops", "aud": "other"}));
+        assert!(authenticator().authenticate(Some(&elsewhere)).is_err());
+    }
+
+    #[test]
+    fn test_bearer_credential_is_read_from_headers() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("authorization", "Bearer k1".parse().unwrap());
+        assert_eq!(header_credential(&headers), Some("k1"));
+    }
+
+    #[test]
+    fn test_client_certificate_identifies_peers_without_a_credential() {
+        let auth = authenticator();
+        let certificate = Identity { subject: "spiffe://hub/dividend".to_string() };
+        assert_eq!(auth.authenticate_peer(None, Some(certificate.clone())).unwrap(), Some(certificate.clone()));
+        assert_eq!(auth.authenticate_peer(Some("k1"), Some(certificate)).unwrap().unwrap().subject, "dividend");
+    }
+}
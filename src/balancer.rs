@@ -0,0 +1,414 @@
+//! Choosing which instance of a service gets a call.
+//!
+//! Each service name gets its own [`Balancer`], built from the [`BalancerKind`]
+//! configured for it (round-robin by default). Balancers pick among the instances
+//! that are currently `online`, using their registration weight (`hub.weight`
+//! metadata) and the load the hub tracks around every call: outstanding calls and
+//! an exponentially weighted moving average of call latency. Instances take one
+//! call at a time unless their `max_in_flight` limit allows more (see the `limits`
+//! module), so outstanding calls only tell apart instances that take several.
+//!
+//! The `consistent_hash` strategy instead maps a key taken from each call (a header
+//! or a field of the JSON request, see [`HashKey`]) onto a hash ring of the instances,
+//! so calls with the same key keep reaching the same instance. The ring holds every
+//! instance that is up (online or busy); a busy owner's calls spill over to the next
+//! instance on the ring, and an instance joining or leaving only moves its own keys.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Weight given to the newest sample in the latency average
+const EWMA_ALPHA: f64 = 0.3;
+
+/// Points per instance on the consistent-hash ring
+const VIRTUAL_NODES: usize = 100;
+
+/// Load-balancing strategies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalancerKind {
+    #[default]
+    RoundRobin,
+    /// Smooth weighted round-robin over the `hub.weight` registration metadata
+    WeightedRoundRobin,
+    LeastOutstanding,
+    /// Compare two random instances and take the less loaded one
+    PowerOfTwo,
+    /// Lowest average latency, scaled by outstanding calls
+    EwmaLatency,
+    /// Same key, same instance; the key is configured in `hash_keys`
+    ConsistentHash,
+}
+
+impl BalancerKind {
+    fn build(self) -> Arc<dyn Balancer> {
+        match self {
+            Self::RoundRobin => Arc::new(RoundRobin::default()),
+            Self::WeightedRoundRobin => Arc::new(WeightedRoundRobin::default()),
+            Self::LeastOutstanding => Arc::new(LeastOutstanding::default()),
+            Self::PowerOfTwo => Arc::new(PowerOfTwo),
+            Self::EwmaLatency => Arc::new(EwmaLatency::default()),
+            Self::ConsistentHash => Arc::new(ConsistentHash::default()),
+        }
+    }
+}
+
+/// Which strategy each service name uses
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BalancerConfig {
+    pub default: BalancerKind,
+    pub services: HashMap<String, BalancerKind>,
+    /// Where `consistent_hash` services take each call's key from
+    pub hash_keys: HashMap<String, HashKey>,
+}
+
+/// The part of a call that keys consistent-hash routing
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashKey {
+    /// A request header (case-insensitive), e.g. `{"header": "x-user-id"}`
+    Header(String),
+    /// A dotted path into the JSON request, e.g. `{"json_path": "user.id"}` or `"items.0.id"`
+    JsonPath(String),
+}
+
+impl HashKey {
+    /// The key of a call, if it carries one
+    pub fn extract(&self, headers: &HashMap<String, String>, input: Option<&serde_json::Value>) -> Option<String> {
+        match self {
+            Self::Header(name) => headers.iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone()),
+            Self::JsonPath(path) => {
+                let mut value = input?;
+                for segment in path.trim_start_matches("$.").split('.').filter(|s| !s.is_empty()) {
+                    value = match segment.parse::<usize>() {
+                        Ok(index) if value.is_array() => value.get(index)?,
+                        _ => value.get(segment)?,
+                    };
+                }
+                match value {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(s) => Some(s.clone()),
+                    other => Some(other.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Load the hub has seen on one instance
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct InstanceLoad {
+    pub outstanding: u32,
+    /// `None` until the first call finishes
+    pub ewma_latency_ms: Option<f64>,
+}
+
+/// An `online` instance the balancer may pick
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub service_id: String,
+    pub weight: u32,
+    pub load: InstanceLoad,
+}
+
+/// What a balancer knows about the call being routed, besides the candidates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Route<'a> {
+    /// The call's consistent-hash key, if the service has one configured and the call carries it
+    pub hash_key: Option<&'a str>,
+    /// Every instance of the service that is up, whether or not it is free right now
+    pub ring: &'a [String],
+}
+
+/// A load-balancing strategy for one service name
+pub trait Balancer: std::fmt::Debug + Send + Sync {
+    /// Index of the candidate that gets the call; `candidates` is never empty
+    fn pick(&self, candidates: &[Candidate], route: &Route) -> usize;
+}
+
+#[derive(Debug, Default)]
+struct RoundRobin {
+    counter: AtomicU64,
+}
+
+impl RoundRobin {
+    fn next(&self, len: usize) -> usize {
+        (self.counter.fetch_add(1, Ordering::Relaxed) as usize) % len
+    }
+}
+
+impl Balancer for RoundRobin {
+    fn pick(&self, candidates: &[Candidate], _route: &Route) -> usize {
+        self.next(candidates.len())
+    }
+}
+
+#[derive(Debug, Default)]
+struct WeightedRoundRobin {
+    /// Current weight per service ID (nginx's smooth weighted round-robin)
+    current: Mutex<HashMap<String, i64>>,
+}
+
+impl Balancer for WeightedRoundRobin {
+    fn pick(&self, candidates: &[Candidate], _route: &Route) -> usize {
+        let mut current = self.current.lock().unwrap();
+        current.retain(|id, _| candidates.iter().any(|c| &c.service_id == id));
+
+        let total: i64 = candidates.iter().map(|c| c.weight as i64).sum();
+        let mut best = 0;
+        let mut best_weight = i64::MIN;
+        for (index, candidate) in candidates.iter().enumerate() {
+            let weight = current.entry(candidate.service_id.clone()).or_default();
+            *weight += candidate.weight as i64;
+            if *weight > best_weight {
+                best = index;
+                best_weight = *weight;
+            }
+        }
+        *current.get_mut(&candidates[best].service_id).unwrap() -= total;
+        best
+    }
+}
+
+#[derive(Debug, Default)]
+struct LeastOutstanding {
+    /// Rotates between equally loaded instances
+    ties: RoundRobin,
+}
+
+impl Balancer for LeastOutstanding {
+    fn pick(&self, candidates: &[Candidate], _route: &Route) -> usize {
+        let least = candidates.iter().map(|c| c.load.outstanding).min().unwrap_or(0);
+        let tied: Vec<usize> = (0..candidates.len()).filter(|&i| candidates[i].load.outstanding == least).collect();
+        tied[self.ties.next(tied.len())]
+    }
+}
+
+#[derive(Debug)]
+struct PowerOfTwo;
+
+impl Balancer for PowerOfTwo {
+    fn pick(&self, candidates: &[Candidate], _route: &Route) -> usize {
+        if candidates.len() == 1 {
+            return 0;
+        }
+        let mut rng = rand::thread_rng();
+        let first = rng.gen_range(0..candidates.len());
+        let second = (first + rng.gen_range(1..candidates.len())) % candidates.len();
+        let key = |i: usize| (candidates[i].load.outstanding, candidates[i].load.ewma_latency_ms.unwrap_or(0.0));
+        if key(second) < key(first) { second } else { first }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EwmaLatency {
+    ties: RoundRobin,
+}
+
+impl Balancer for EwmaLatency {
+    fn pick(&self, candidates: &[Candidate], _route: &Route) -> usize {
+        // Instances without a sample yet score 0, so each gets tried
+        let score = |c: &Candidate| c.load.ewma_latency_ms.unwrap_or(0.0) * (c.load.outstanding + 1) as f64;
+        let best = candidates.iter().map(score).fold(f64::INFINITY, f64::min);
+        let tied: Vec<usize> = (0..candidates.len()).filter(|&i| score(&candidates[i]) == best).collect();
+        tied[self.ties.next(tied.len())]
+    }
+}
+
+/// Points on the ring for one set of members, sorted by hash
+#[derive(Debug, Default)]
+struct HashRing {
+    members: Vec<String>,
+    /// (point, index into `members`)
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    fn new(members: &[String]) -> Self {
+        let mut points: Vec<(u64, usize)> = members.iter().enumerate()
+            .flat_map(|(member, id)| (0..VIRTUAL_NODES).map(move |i| (fnv1a(format!("{}#{}", id, i).as_bytes()), member)))
+            .collect();
+        points.sort_unstable();
+        Self { members: members.to_vec(), points }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ConsistentHash {
+    /// Rebuilt whenever the members change
+    ring: Mutex<HashRing>,
+    /// For calls without a key
+    fallback: RoundRobin,
+}
+
+impl Balancer for ConsistentHash {
+    fn pick(&self, candidates: &[Candidate], route: &Route) -> usize {
+        let Some(key) = route.hash_key else {
+            return self.fallback.next(candidates.len());
+        };
+
+        let mut ring = self.ring.lock().unwrap();
+        if ring.members != route.ring {
+            *ring = HashRing::new(route.ring);
+        }
+
+        // Walk clockwise from the key to the first instance that is free
+        let start = ring.points.partition_point(|(point, _)| *point < fnv1a(key.as_bytes()));
+        for offset in 0..ring.points.len() {
+            let member = &ring.members[ring.points[(start + offset) % ring.points.len()].1];
+            if let Some(index) = candidates.iter().position(|c| &c.service_id == member) {
+                return index;
+            }
+        }
+        self.fallback.next(candidates.len())
+    }
+}
+
+/// 64-bit FNV-1a: stable across processes, so every hub in a cluster builds the same ring
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// The balancer for every service name, plus the load they balance on
+#[derive(Debug, Default)]
+pub struct Balancers {
+    config: BalancerConfig,
+    balancers: Mutex<HashMap<String, Arc<dyn Balancer>>>,
+    loads: Mutex<HashMap<String, InstanceLoad>>,
+}
+
+impl Balancers {
+    pub fn new(config: BalancerConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// The strategy used for `service_name`
+    pub fn kind(&self, service_name: &str) -> BalancerKind {
+        self.config.services.get(service_name).copied().unwrap_or(self.config.default)
+    }
+
+    /// The consistent-hash key of a call to `service_name`, if it uses one and the call carries it
+    pub fn hash_key(&self, service_name: &str, headers: &HashMap<String, String>, input: Option<&serde_json::Value>) -> Option<String> {
+        if self.kind(service_name) != BalancerKind::ConsistentHash {
+            return None;
+        }
+        self.config.hash_keys.get(service_name)?.extract(headers, input)
+    }
+
+    /// Pick one of `candidates` (never empty) for a call to `service_name`
+    pub fn pick(&self, service_name: &str, candidates: &[Candidate], route: &Route) -> usize {
+        let balancer = self.balancers.lock().unwrap()
+            .entry(service_name.to_string())
+            .or_insert_with(|| self.kind(service_name).build())
+            .clone();
+        balancer.pick(candidates, route)
+    }
+
+    pub fn load(&self, service_id: &str) -> InstanceLoad {
+        self.loads.lock().unwrap().get(service_id).copied().unwrap_or_default()
+    }
+
+    pub fn loads(&self) -> HashMap<String, InstanceLoad> {
+        self.loads.lock().unwrap().clone()
+    }
+
+    /// A call to the instance started
+    pub fn call_started(&self, service_id: &str) {
+        self.loads.lock().unwrap().entry(service_id.to_string()).or_default().outstanding += 1;
+    }
+
+    /// A call to the instance ended; `latency` is `None` if it didn't complete (e.g. the instance was unreachable)
+    pub fn call_finished(&self, service_id: &str, latency: Option<Duration>) {
+        let mut loads = self.loads.lock().unwrap();
+        let Some(load) = loads.get_mut(service_id) else {
+            return;
+        };
+        load.outstanding = load.outstanding.saturating_sub(1);
+        if let Some(latency) = latency {
+            let sample = latency.as_secs_f64() * 1000.0;
+            load.ewma_latency_ms = Some(match load.ewma_latency_ms {
+                Some(average) => average + EWMA_ALPHA * (sample - average),
+                None => sample,
+            });
+        }
+    }
+
+    /// Drop the load kept for a removed instance
+    pub fn forget(&self, service_id: &str) {
+        self.loads.lock().unwrap().remove(service_id);
+    }
+}
+
+/// The `hub.weight` registration metadata, defaulting to 1
+pub fn instance_weight(metadata: &HashMap<String, String>) -> u32 {
+    metadata.get("hub.weight").and_then(|w| w.parse().ok()).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(weights: &[u32]) -> Vec<Candidate> {
+        weights.iter().enumerate()
+            .map(|(i, &weight)| Candidate { service_id: i.to_string(), weight, load: InstanceLoad::default() })
+            .collect()
+    }
+
+    #[test]
+    fn test_weighted_round_robin_follows_weights() {
+        let balancer = BalancerKind::WeightedRoundRobin.build();
+        let candidates = candidates(&[5, 1, 1]);
+        let picks: Vec<usize> = (0..7).map(|_| balancer.pick(&candidates, &Route::default())).collect();
+        assert_eq!(picks.iter().filter(|&&i| i == 0).count(), 5);
+        // Smooth: the heavy instance never takes every call in a row
+        assert_eq!(picks, vec![0, 0, 1, 0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn test_consistent_hash_is_sticky_and_moves_few_keys() {
+        let balancer = BalancerKind::ConsistentHash.build();
+        let ring: Vec<String> = (0..4).map(|i| i.to_string()).collect();
+        let owner = |candidates: &[Candidate], ring: &[String], key: &str| {
+            let index = balancer.pick(candidates, &Route { hash_key: Some(key), ring });
+            candidates[index].service_id.clone()
+        };
+
+        let all = candidates(&[1, 1, 1, 1]);
+        let keys: Vec<String> = (0..200).map(|i| format!("user-{}", i)).collect();
+        let before: Vec<String> = keys.iter().map(|k| owner(&all, &ring, k)).collect();
+        assert_eq!(before, keys.iter().map(|k| owner(&all, &ring, k)).collect::<Vec<_>>());
+
+        // Instance 3 leaves: only its own keys move
+        let after: Vec<String> = keys.iter().map(|k| owner(&all[..3], &ring[..3], k)).collect();
+        for (before, after) in before.iter().zip(&after) {
+            assert!(before == after || before == "3");
+        }
+
+        // Instance 0 is busy: its keys spill over, but it stays on the ring
+        let spilled: Vec<String> = keys.iter().map(|k| owner(&all[1..], &ring, k)).collect();
+        for (before, spilled) in before.iter().zip(&spilled) {
+            assert!(before == spilled || before == "0");
+        }
+    }
+
+    #[test]
+    fn test_hash_key_from_header_or_json_path() {
+        let headers = HashMap::from([("X-User-Id".to_string(), "42".to_string())]);
+        let input = serde_json::json!({"user": {"id": 7}, "items": [{"sku": "a"}]});
+        assert_eq!(HashKey::Header("x-user-id".to_string()).extract(&headers, None), Some("42".to_string()));
+        assert_eq!(HashKey::JsonPath("user.id".to_string()).extract(&headers, Some(&input)), Some("7".to_string()));
+        assert_eq!(HashKey::JsonPath("$.items.0.sku".to_string()).extract(&headers, Some(&input)), Some("a".to_string()));
+        assert_eq!(HashKey::JsonPath("user.name".to_string()).extract(&headers, Some(&input)), None);
+    }
+}
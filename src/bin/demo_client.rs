@@ -40,6 +40,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             service_port: port.to_string(),
             methods: methods.iter().map(|s| s.to_string()).collect(),
             metadata,
+            ..Default::default()
         });
         
         let response = client.register_service(register_request).await?;
@@ -47,7 +48,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         
         if response.success {
             println!("✅ Registered {} v{} (ID: {})", name, version, response.service_id);
-            registered_services.push(response.service_id);
+            registered_services.push((response.service_id, response.instance_secret));
         } else {
             println!("❌ Failed to register {}: {}", name, response.message);
         }
@@ -58,6 +59,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // List all services
     let list_request = Request::new(grpc_hub::ListServicesRequest {
         filter: None,
+        ..Default::default()
     });
     
     let list_response = client.list_services(list_request).await?;
@@ -80,9 +82,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     for i in 1..=15 {
         sleep(Duration::from_secs(2)).await;
         
-        for service_id in &registered_services {
+        for (service_id, instance_secret) in &registered_services {
             let health_request = Request::new(grpc_hub::HealthCheckRequest {
                 service_id: service_id.clone(),
+                instance_secret: instance_secret.clone(),
             });
             
             let health_response = client.health_check(health_request).await?;
@@ -99,6 +102,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Test filtering
     let filter_request = Request::new(grpc_hub::ListServicesRequest {
         filter: Some("service".to_string()),
+        ..Default::default()
     });
     
     let filter_response = client.list_services(filter_request).await?;
@@ -107,7 +111,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  Services matching 'service': {}", filter_response.services.len());
     
     // Test specific service lookup
-    if let Some(service_id) = registered_services.first() {
+    if let Some((service_id, _)) = registered_services.first() {
         let get_request = Request::new(grpc_hub::GetServiceRequest {
             service_id: service_id.clone(),
         });
@@ -141,9 +145,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         
         println!("💓 Health check round #{} for {} services...", health_check_count, registered_services.len());
         
-        for service_id in &registered_services {
+        for (service_id, instance_secret) in &registered_services {
             let health_request = Request::new(grpc_hub::HealthCheckRequest {
                 service_id: service_id.clone(),
+                instance_secret: instance_secret.clone(),
             });
             
             let health_response = client.health_check(health_request).await?;
@@ -1,6 +1,5 @@
 use std::collections::HashMap;
 use tonic::{transport::Server, Request, Response, Status};
-use uuid::Uuid;
 use chrono::Utc;
 
 mod grpc_hub {
@@ -19,6 +18,7 @@ use grpc_hub::{RegisterServiceRequest, ServiceCallRequest};
 #[derive(Debug)]
 struct DividendConsumerService {
     // In-memory storage for dividend calculations
+    #[allow(dead_code)]
     dividend_history: std::collections::HashMap<String, Vec<serde_json::Value>>,
 }
 
@@ -65,7 +65,7 @@ impl dividend_service::dividend_service_server::DividendService for DividendCons
         println!("💰 DividendService.GetDividendHistory called for user: {}", req.user_id);
         
         // Return mock dividend history
-        let dividends = vec![
+        let dividends = [
             serde_json::json!({
                 "date": "2024-01-15",
                 "amount": 2.50,
@@ -133,6 +133,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "ProcessDividendData".to_string(),
         ],
         metadata,
+        ..Default::default()
     });
     
     let register_response = hub_client.register_service(register_request).await?;
@@ -153,6 +154,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }).to_string(),
         caller_service: "dividend-consumer".to_string(),
         headers: HashMap::new(),
+        ..Default::default()
     });
     
     match hub_client.call_service(extract_request).await {
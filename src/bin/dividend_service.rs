@@ -43,6 +43,7 @@ struct Args {
 // Dividend Service Implementation
 #[derive(Debug, Clone)]
 struct DividendService {
+    #[allow(dead_code)]
     dividend_history: std::collections::HashMap<String, Vec<serde_json::Value>>,
     hub_connector: grpc_hub_connector::GrpcHubConnector,
     web_content_mutex: Arc<Mutex<()>>, // Mutex to prevent concurrent web content service calls
@@ -59,6 +60,7 @@ impl DividendService {
         }
     }
 
+    #[allow(dead_code)]
     fn new_with_hub_endpoint(hub_endpoint: String) -> Self {
         Self {
             dividend_history: std::collections::HashMap::new(),
@@ -68,6 +70,7 @@ impl DividendService {
         }
     }
 
+    #[allow(dead_code)]
     fn new_with_service_id(hub_endpoint: String, service_id: String) -> Self {
         Self {
             dividend_history: std::collections::HashMap::new(),
@@ -106,7 +109,7 @@ impl DividendService {
         
         // Call web content service through the hub to track busy status
         let hub_endpoint = self.hub_connector.get_hub_endpoint();
-        let hub_url = format!("{}/api/grpc-call", hub_endpoint.replace("http://", "http://").replace(":50099", ":8080"));
+        let hub_url = format!("{}/api/grpc-call", hub_endpoint.replace(":50099", ":8080"));
         
         let request_body = serde_json::json!({
             "service": "web_content_extract.WebContentExtract",
@@ -311,6 +314,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         service_port: args.port.to_string(),
         methods: methods.clone(),
         metadata: metadata.clone(),
+        ..Default::default()
     };
     
     let register_request = Request::new(registration_details.clone());
@@ -325,12 +329,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         args.grpc_hub_port, 
         service_id.clone()
     );
+    let hub_connector = dividend_service_instance.hub_connector.clone();
+    hub_connector.set_instance_secret(&service_id, &register_response.instance_secret).await;
     
     // Note: Polling task removed to prevent race conditions with user requests
     // The dividend service works on-demand when users call GetDividendHistory
     
     // Spawn heartbeat task
     let service_id_for_heartbeat = service_id.clone();
+    let mut current_secret = register_response.instance_secret.clone();
     let registration_details_for_heartbeat = registration_details.clone();
     tokio::spawn(async move {
         let hub_addr = "http://127.0.0.1:50099";
@@ -350,10 +357,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         println!("✅ Connected to gRPC hub!");
                         
                         if let Some(ref mut client) = heartbeat_client {
-                            let re_register_request = Request::new(registration_details_for_heartbeat.clone());
+                            // The hub only lets a live instance register again with its current secret
+                            let re_register_request = Request::new(RegisterServiceRequest {
+                                instance_secret: current_secret.clone(),
+                                ..registration_details_for_heartbeat.clone()
+                            });
                             match client.register_service(re_register_request).await {
                                 Ok(response) => {
-                                    current_service_id = response.into_inner().service_id;
+                                    let response = response.into_inner();
+                                    current_service_id = response.service_id;
+                                    current_secret = response.instance_secret;
+                                    // Status updates need the secret of the new registration
+                                    hub_connector.set_instance_secret(&current_service_id, &current_secret).await;
                                     println!("✅ Service registered with ID: {}", current_service_id);
                                     needs_re_register = false;
                                 }
@@ -374,6 +389,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             if let Some(ref mut client) = heartbeat_client {
                 let health_request = Request::new(HealthCheckRequest {
                     service_id: current_service_id.clone(),
+                    instance_secret: current_secret.clone(),
                 });
                 
                 match client.health_check(health_request).await {
@@ -17,6 +17,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("📡 Step 1: Discovering available services...");
     let list_request = Request::new(grpc_hub::ListServicesRequest {
         filter: None,
+        ..Default::default()
     });
     
     let list_response = client.list_services(list_request).await?;
@@ -71,18 +72,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Step 4: Show service health
     println!("💓 Step 4: Checking service health...");
+    // Heartbeats need the instance's own secret, so just report what the hub last saw
     for service in &services {
-        let health_request = Request::new(grpc_hub::HealthCheckRequest {
-            service_id: service.service_id.clone(),
-        });
-        
-        let health_response = client.health_check(health_request).await?;
-        let health_result = health_response.into_inner();
-        
-        if health_result.healthy {
-            println!("  💚 {}: {}", service.service_name, health_result.message);
+        if service.status == "online" {
+            println!("  💚 {}: {} (last heartbeat {})", service.service_name, service.status, service.last_heartbeat);
         } else {
-            println!("  ❌ {}: {}", service.service_name, health_result.message);
+            println!("  ❌ {}: {} (last heartbeat {})", service.service_name, service.status, service.last_heartbeat);
         }
     }
     
@@ -92,6 +87,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Filter by name
     let user_filter_request = Request::new(grpc_hub::ListServicesRequest {
         filter: Some("user".to_string()),
+        ..Default::default()
     });
     
     let user_filter_response = client.list_services(user_filter_request).await?;
@@ -101,6 +97,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Filter by version
     let order_filter_request = Request::new(grpc_hub::ListServicesRequest {
         filter: Some("order".to_string()),
+        ..Default::default()
     });
     
     let order_filter_response = client.list_services(order_filter_request).await?;
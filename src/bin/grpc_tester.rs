@@ -26,6 +26,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Get user service details
     let list_request = Request::new(grpc_hub::ListServicesRequest {
         filter: Some("user".to_string()),
+        ..Default::default()
     });
     
     let list_response = hub_client.list_services(list_request).await?;
@@ -81,6 +82,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Test order service
     let order_list_request = Request::new(grpc_hub::ListServicesRequest {
         filter: Some("order".to_string()),
+        ..Default::default()
     });
     
     let order_list_response = hub_client.list_services(order_list_request).await?;
@@ -8,7 +8,7 @@ mod grpc_hub {
 }
 
 use grpc_hub::grpc_hub_client::GrpcHubClient;
-use grpc_hub::{ServiceCallRequest, SubscribeRequest};
+use grpc_hub::ServiceCallRequest;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -48,6 +48,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             service_port: port.to_string(),
             methods: methods.iter().map(|s| s.to_string()).collect(),
             metadata,
+            ..Default::default()
         });
         
         let response = client.register_service(register_request).await?;
@@ -55,7 +56,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         
         if response.success {
             println!("✅ Registered {} v{} (ID: {})", name, version, response.service_id);
-            registered_services.push(response.service_id);
+            registered_services.push((response.service_id, response.instance_secret));
         } else {
             println!("❌ Failed to register {}: {}", name, response.message);
         }
@@ -78,6 +79,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }).to_string(),
         caller_service: "order-service".to_string(),
         headers: HashMap::new(),
+        ..Default::default()
     });
     
     match client.call_service(order_to_user_request).await {
@@ -103,6 +105,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }).to_string(),
         caller_service: "payment-service".to_string(),
         headers: HashMap::new(),
+        ..Default::default()
     });
     
     match client.call_service(payment_to_order_request).await {
@@ -131,6 +134,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }).to_string(),
         caller_service: "analytics-service".to_string(),
         headers: HashMap::new(),
+        ..Default::default()
     });
     
     match client.call_service(analytics_to_user_request).await {
@@ -156,6 +160,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }).to_string(),
         caller_service: "analytics-service".to_string(),
         headers: HashMap::new(),
+        ..Default::default()
     });
     
     match client.call_service(analytics_to_order_request).await {
@@ -182,6 +187,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }).to_string(),
         caller_service: "dividend-consumer".to_string(),
         headers: HashMap::new(),
+        ..Default::default()
     });
     
     match client.call_service(web_extract_request).await {
@@ -201,6 +207,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }).to_string(),
                     caller_service: "web-content-extract".to_string(),
                     headers: HashMap::new(),
+                    ..Default::default()
                 });
                 
                 match client.call_service(dividend_request).await {
@@ -235,9 +242,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("💓 Health check round #{} for {} services...", health_check_count, registered_services.len());
         
         let mut healthy_count = 0;
-        for service_id in &registered_services {
+        for (service_id, instance_secret) in &registered_services {
             let health_request = Request::new(grpc_hub::HealthCheckRequest {
                 service_id: service_id.clone(),
+                instance_secret: instance_secret.clone(),
             });
             
             let health_response = client.health_check(health_request).await?;
@@ -312,6 +312,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "ListUsers".to_string(),
         ],
         metadata,
+        ..Default::default()
     });
     
     let user_response = hub_client.register_service(user_register_request).await?;
@@ -337,6 +338,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "ListOrders".to_string(),
         ],
         metadata: order_metadata,
+        ..Default::default()
     });
     
     let order_response = hub_client.register_service(order_register_request).await?;
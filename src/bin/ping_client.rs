@@ -81,6 +81,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             request_data: serde_json::to_string(&input_data)?,
             caller_service: "ping-client".to_string(),
             headers: std::collections::HashMap::new(),
+            ..Default::default()
         });
 
         match hub_client.call_service(request).await {
@@ -6,7 +6,7 @@ mod grpc_hub {
 }
 
 use grpc_hub::grpc_hub_client::GrpcHubClient;
-use grpc_hub::{ServiceCallRequest, SubscribeRequest};
+use grpc_hub::ServiceCallRequest;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -28,6 +28,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }).to_string(),
         caller_service: "dividend-service".to_string(),
         headers: HashMap::new(),
+        ..Default::default()
     });
     
     let call_response = hub_client.call_service(call_request).await?;
@@ -54,6 +55,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }).to_string(),
             caller_service: "bridge-demo".to_string(),
             headers: HashMap::new(),
+            ..Default::default()
         });
         
         let dividend_response = hub_client.call_service(dividend_request).await?;
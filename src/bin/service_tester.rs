@@ -16,6 +16,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // List all registered services
     let list_request = Request::new(grpc_hub::ListServicesRequest {
         filter: None,
+        ..Default::default()
     });
     
     let list_response = client.list_services(list_request).await?;
@@ -60,18 +61,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("  ❌ Service not found");
         }
         
-        // Test health check
-        let health_request = Request::new(grpc_hub::HealthCheckRequest {
-            service_id: service.service_id.clone(),
-        });
-        
-        let health_response = client.health_check(health_request).await?;
-        let health_result = health_response.into_inner();
-        
-        if health_result.healthy {
-            println!("  💚 Health check: {}", health_result.message);
+        // Heartbeats need the instance's own secret, so just report what the hub last saw
+        if service.status == "online" {
+            println!("  💚 Status: {} (last heartbeat {})", service.status, service.last_heartbeat);
         } else {
-            println!("  ❌ Health check failed: {}", health_result.message);
+            println!("  ❌ Status: {} (last heartbeat {})", service.status, service.last_heartbeat);
         }
         
         println!();
@@ -83,6 +77,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Filter by name containing "user"
     let filter_request = Request::new(grpc_hub::ListServicesRequest {
         filter: Some("user".to_string()),
+        ..Default::default()
     });
     
     let filter_response = client.list_services(filter_request).await?;
@@ -30,6 +30,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "DeleteUser".to_string(),
         ],
         metadata,
+        ..Default::default()
     });
     
     let response = client.register_service(register_request).await?;
@@ -42,10 +43,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     if response.success {
         let service_id = response.service_id;
+        let instance_secret = response.instance_secret;
         
         // Send a health check
         let health_request = Request::new(grpc_hub::HealthCheckRequest {
             service_id: service_id.clone(),
+            instance_secret: instance_secret.clone(),
         });
         
         let health_response = client.health_check(health_request).await?;
@@ -58,6 +61,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // List all services
         let list_request = Request::new(grpc_hub::ListServicesRequest {
             filter: None,
+            ..Default::default()
         });
         
         let list_response = client.list_services(list_request).await?;
@@ -100,6 +104,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             let health_request = Request::new(grpc_hub::HealthCheckRequest {
                 service_id: service_id.clone(),
+                instance_secret: instance_secret.clone(),
             });
             
             let health_response = client.health_check(health_request).await?;
@@ -111,6 +116,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Unregister the service
         let unregister_request = Request::new(grpc_hub::UnregisterServiceRequest {
             service_id: service_id.clone(),
+            instance_secret: instance_secret.clone(),
         });
         
         let unregister_response = client.unregister_service(unregister_request).await?;
@@ -95,6 +95,7 @@ impl WebContentExtractService {
         }
     }
 
+    #[allow(dead_code)]
     fn new_with_service_id(hub_endpoint: String, service_id: String) -> Self {
         let mut extracted_data = std::collections::HashMap::new();
         
@@ -308,6 +309,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         service_port: args.port.to_string(),
         methods: methods.clone(),
         metadata: metadata.clone(),
+        ..Default::default()
     };
     
     let register_request = Request::new(registration_details.clone());
@@ -323,6 +325,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         args.grpc_hub_port, 
         service_id.clone()
     );
+    let hub_connector = web_extract_service.hub_connector.clone();
+    hub_connector.set_instance_secret(&service_id, &register_response.instance_secret).await;
     
     println!("🚀 Web Content Extract Service starting on {}", addr);
     
@@ -344,6 +348,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Send periodic heartbeats to the hub in a separate task with reconnection logic
     let service_id_for_heartbeat = service_id.clone();
+    let mut current_secret = register_response.instance_secret.clone();
     let registration_details_for_heartbeat = registration_details.clone();
     let heartbeat_task = tokio::spawn(async move {
         let hub_addr = "http://127.0.0.1:50099";
@@ -373,10 +378,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         // Always re-register when connecting (covers both initial connection and reconnection)
                         println!("📝 Registering/re-registering service with hub...");
                         if let Some(ref mut client) = heartbeat_client {
-                            let re_register_request = Request::new(registration_details_for_heartbeat.clone());
+                            // The hub only lets a live instance register again with its current secret
+                            let re_register_request = Request::new(RegisterServiceRequest {
+                                instance_secret: current_secret.clone(),
+                                ..registration_details_for_heartbeat.clone()
+                            });
                             match client.register_service(re_register_request).await {
                                 Ok(response) => {
-                                    current_service_id = response.into_inner().service_id;
+                                    let response = response.into_inner();
+                                    current_service_id = response.service_id;
+                                    current_secret = response.instance_secret;
+                                    // Status updates need the secret of the new registration
+                                    hub_connector.set_instance_secret(&current_service_id, &current_secret).await;
                                     println!("✅ Service registered with ID: {}", current_service_id);
                                     needs_re_register = false;
                                 }
@@ -398,6 +411,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             if let Some(ref mut client) = heartbeat_client {
                 let health_request = Request::new(HealthCheckRequest {
                     service_id: current_service_id.clone(),
+                    instance_secret: current_secret.clone(),
                 });
                 
                 match client.health_check(health_request).await {
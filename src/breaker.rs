@@ -0,0 +1,206 @@
+//! Circuit breakers per instance, driven by the outcomes of calls routed through the hub.
+//!
+//! A circuit starts `closed`. After `failure_threshold` failed calls in a row it opens,
+//! and the instance gets no calls for `open_duration_ms`. It is then `half_open`: the
+//! next call decides whether it closes again (success) or reopens (failure). Only
+//! failures that point at the instance count; a caller's bad request doesn't.
+//!
+//! ```json
+//! { "circuit_breaker": { "failure_threshold": 5, "open_duration_ms": 30000 } }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tonic::Code;
+
+use crate::grpc_client::GrpcCallError;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BreakerConfig {
+    /// Failed calls in a row that open a circuit; 0 disables the breakers
+    pub failure_threshold: u32,
+    /// How long an open circuit keeps its instance out of rotation
+    pub open_duration_ms: u64,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    #[default]
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Closed => "closed",
+            Self::Open => "open",
+            Self::HalfOpen => "half_open",
+        })
+    }
+}
+
+/// A circuit moving from one state to another
+pub type Transition = (CircuitState, CircuitState);
+
+#[derive(Debug, Default)]
+struct Circuit {
+    state: CircuitState,
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Whether a failed call counts against the instance that served it
+pub fn is_failure(error: &GrpcCallError) -> bool {
+    error.connection_failure
+        || matches!(error.code, Code::Unavailable | Code::Internal | Code::Unknown | Code::DeadlineExceeded | Code::DataLoss)
+}
+
+/// The circuit of every instance that has had calls
+#[derive(Debug, Default)]
+pub struct CircuitBreakers {
+    config: BreakerConfig,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn state(&self, service_id: &str) -> CircuitState {
+        self.circuits.lock().unwrap().get(service_id).map(|c| c.state).unwrap_or_default()
+    }
+
+    /// Whether the instance may be given calls
+    pub fn allows(&self, service_id: &str) -> bool {
+        self.state(service_id) != CircuitState::Open
+    }
+
+    /// Count one call outcome, returning the transition it caused
+    pub fn record(&self, service_id: &str, failed: bool) -> Option<Transition> {
+        if self.config.failure_threshold == 0 {
+            return None;
+        }
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(service_id.to_string()).or_default();
+        let from = circuit.state;
+        match (from, failed) {
+            (CircuitState::Closed, false) => circuit.failures = 0,
+            (CircuitState::Closed, true) => {
+                circuit.failures += 1;
+                if circuit.failures >= self.config.failure_threshold {
+                    circuit.state = CircuitState::Open;
+                    circuit.opened_at = Some(Instant::now());
+                }
+            }
+            (CircuitState::HalfOpen, false) => {
+                *circuit = Circuit::default();
+            }
+            (CircuitState::HalfOpen, true) => {
+                circuit.state = CircuitState::Open;
+                circuit.opened_at = Some(Instant::now());
+            }
+            // A call that was already under way when the circuit opened
+            (CircuitState::Open, _) => {}
+        }
+        (circuit.state != from).then_some((from, circuit.state))
+    }
+
+    /// Move every circuit that has been open long enough to half-open
+    pub fn half_open_expired(&self) -> Vec<(String, Transition)> {
+        let open_duration = Duration::from_millis(self.config.open_duration_ms);
+        let mut circuits = self.circuits.lock().unwrap();
+        circuits.iter_mut()
+            .filter(|(_, c)| c.state == CircuitState::Open && c.opened_at.is_some_and(|at| at.elapsed() >= open_duration))
+            .map(|(service_id, c)| {
+                c.state = CircuitState::HalfOpen;
+                (service_id.clone(), (CircuitState::Open, CircuitState::HalfOpen))
+            })
+            .collect()
+    }
+
+    pub fn forget(&self, service_id: &str) {
+        self.circuits.lock().unwrap().remove(service_id);
+    }
+
+    /// Every circuit that isn't closed
+    pub fn tripped(&self) -> HashMap<String, CircuitState> {
+        self.circuits.lock().unwrap().iter()
+            .filter(|(_, c)| c.state != CircuitState::Closed)
+            .map(|(service_id, c)| (service_id.clone(), c.state))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Breakers that trip after two failures in a row and half-open right away
+    fn tripped_breakers() -> CircuitBreakers {
+        let breakers = CircuitBreakers::new(BreakerConfig { failure_threshold: 2, open_duration_ms: 0 });
+        breakers.record("a", true);
+        breakers.record("a", true);
+        breakers
+    }
+
+    #[test]
+    fn test_circuit_opens_after_consecutive_failures() {
+        let breakers = CircuitBreakers::new(BreakerConfig { failure_threshold: 2, open_duration_ms: 0 });
+        assert_eq!(breakers.record("a", true), None);
+        assert_eq!(breakers.record("a", false), None);
+        assert_eq!(breakers.record("a", true), None);
+        assert_eq!(breakers.record("a", true), Some((CircuitState::Closed, CircuitState::Open)));
+        assert!(!breakers.allows("a"));
+        assert_eq!(breakers.tripped()["a"], CircuitState::Open);
+    }
+
+    #[test]
+    fn test_open_circuit_half_opens_after_its_duration() {
+        let breakers = tripped_breakers();
+        assert_eq!(breakers.half_open_expired(), vec![("a".to_string(), (CircuitState::Open, CircuitState::HalfOpen))]);
+        assert!(breakers.allows("a"));
+    }
+
+    #[test]
+    fn test_half_open_circuit_reopens_on_failure() {
+        let breakers = tripped_breakers();
+        breakers.half_open_expired();
+        assert_eq!(breakers.record("a", true), Some((CircuitState::HalfOpen, CircuitState::Open)));
+        assert!(!breakers.allows("a"));
+    }
+
+    #[test]
+    fn test_half_open_circuit_closes_on_success() {
+        let breakers = tripped_breakers();
+        breakers.half_open_expired();
+        assert_eq!(breakers.record("a", false), Some((CircuitState::HalfOpen, CircuitState::Closed)));
+        assert_eq!(breakers.state("a"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_only_server_side_errors_count_as_failures() {
+        assert!(!is_failure(&GrpcCallError::new(Code::InvalidArgument, "bad request")));
+        assert!(is_failure(&GrpcCallError::new(Code::Internal, "boom")));
+    }
+}
@@ -0,0 +1,204 @@
+//! Replicating the registry between several hub nodes.
+//!
+//! Every node pushes each registry change it makes (registration, unregistration,
+//! status change, heartbeat) to its peers' `POST /api/cluster/replicate` endpoint,
+//! together with the event it broadcast, so SSE and `SubscribeToService` clients on
+//! any node see the same events. Pushes to each peer are sent in order by a single
+//! task; a peer that was down catches up through periodic anti-entropy, which pulls
+//! `GET /api/cluster/snapshot` from every peer and takes the instances it is missing
+//! or holds an older copy of.
+//!
+//! Each record carries when it last changed, and the newer copy wins. An instance going
+//! busy or back online around one node's own calls is not replicated: every node routes
+//! its own calls, and keeps an instance it fills busy whatever its peers say.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, RwLock};
+
+use crate::storage::StoredService;
+
+/// Pending pushes per peer before new ones are dropped (anti-entropy repairs the gap)
+const OUTBOX_CAPACITY: usize = 1024;
+
+/// How long a removed instance is kept from being re-added by anti-entropy
+const TOMBSTONE_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    /// Identifies this node in replicated updates
+    pub node_id: String,
+    /// HTTP base URLs of the other hubs, e.g. `http://10.0.0.2:8080`
+    pub peers: Vec<String>,
+    /// How often to pull snapshots from every peer
+    pub sync_interval: Duration,
+    /// API key or bearer token sent to peers that require authentication
+    pub peer_token: Option<String>,
+}
+
+/// An instance as replicated between nodes, including its live status
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceRecord {
+    #[serde(flatten)]
+    pub service: StoredService,
+    pub status: String,
+    pub last_heartbeat: DateTime<Utc>,
+    /// When the record last changed on the node that changed it; the newest copy wins
+    #[serde(default)]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// One registry change, pushed from the node where it happened to every peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationUpdate {
+    pub origin: String,
+    pub service_id: String,
+    /// The instance after the change, or `None` if it was removed
+    pub record: Option<ServiceRecord>,
+    /// The event broadcast for the change
+    pub event_type: String,
+    pub event_data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterSnapshot {
+    pub node_id: String,
+    pub services: Vec<ServiceRecord>,
+}
+
+/// Last contact with a peer
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PeerState {
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Cluster {
+    config: ClusterConfig,
+    client: reqwest::Client,
+    outboxes: Vec<mpsc::Sender<ReplicationUpdate>>,
+    peer_states: std::sync::Arc<RwLock<HashMap<String, PeerState>>>,
+    /// Recently removed instances, so a peer that missed the removal can't bring them back
+    tombstones: RwLock<HashMap<String, DateTime<Utc>>>,
+}
+
+impl Cluster {
+    /// Start one push task per peer
+    pub fn start(config: ClusterConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .expect("Failed to build HTTP client");
+        let peer_states = std::sync::Arc::new(RwLock::new(
+            config.peers.iter().map(|peer| (peer.clone(), PeerState::default())).collect(),
+        ));
+
+        let mut outboxes = Vec::new();
+        for peer in &config.peers {
+            let (tx, mut rx) = mpsc::channel::<ReplicationUpdate>(OUTBOX_CAPACITY);
+            outboxes.push(tx);
+
+            let client = client.clone();
+            let token = config.peer_token.clone();
+            let url = format!("{}/api/cluster/replicate", peer.trim_end_matches('/'));
+            let peer = peer.clone();
+            let peer_states = peer_states.clone();
+            tokio::spawn(async move {
+                while let Some(update) = rx.recv().await {
+                    let result = authorized(client.post(&url), token.as_deref()).json(&update).send().await
+                        .and_then(|response| response.error_for_status());
+                    record_contact(&peer_states, &peer, result.err().map(|e| e.to_string())).await;
+                }
+            });
+        }
+
+        Self {
+            config,
+            client,
+            outboxes,
+            peer_states,
+            tombstones: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.config.node_id
+    }
+
+    pub fn peers(&self) -> &[String] {
+        &self.config.peers
+    }
+
+    pub fn sync_interval(&self) -> Duration {
+        self.config.sync_interval
+    }
+
+    /// Queue `update` for every peer
+    pub fn replicate(&self, update: ReplicationUpdate) {
+        for (outbox, peer) in self.outboxes.iter().zip(&self.config.peers) {
+            if outbox.try_send(update.clone()).is_err() {
+                println!("⚠️  [CLUSTER] Outbox for {} is full, dropping update for {}", peer, update.service_id);
+            }
+        }
+    }
+
+    /// Fetch a peer's full registry
+    pub async fn fetch_snapshot(&self, peer: &str) -> anyhow::Result<ClusterSnapshot> {
+        let url = format!("{}/api/cluster/snapshot", peer.trim_end_matches('/'));
+        let result = async {
+            let snapshot = authorized(self.client.get(&url), self.config.peer_token.as_deref()).send().await?.error_for_status()?.json().await?;
+            Ok::<ClusterSnapshot, reqwest::Error>(snapshot)
+        }
+        .await;
+        record_contact(&self.peer_states, peer, result.as_ref().err().map(|e| e.to_string())).await;
+        Ok(result?)
+    }
+
+    /// Remember that an instance was removed
+    pub async fn add_tombstone(&self, service_id: &str) {
+        let mut tombstones = self.tombstones.write().await;
+        let expired_before = Utc::now() - TOMBSTONE_TTL;
+        tombstones.retain(|_, removed_at| *removed_at > expired_before);
+        tombstones.insert(service_id.to_string(), Utc::now());
+    }
+
+    /// Whether an instance was removed within the tombstone TTL
+    pub async fn is_tombstoned(&self, service_id: &str) -> bool {
+        self.tombstones.read().await
+            .get(service_id)
+            .is_some_and(|removed_at| *removed_at > Utc::now() - TOMBSTONE_TTL)
+    }
+
+    pub async fn peer_states(&self) -> HashMap<String, PeerState> {
+        self.peer_states.read().await.clone()
+    }
+}
+
+async fn record_contact(peer_states: &RwLock<HashMap<String, PeerState>>, peer: &str, error: Option<String>) {
+    let mut states = peer_states.write().await;
+    let state = states.entry(peer.to_string()).or_default();
+    match error {
+        Some(e) => {
+            if state.last_error.as_deref() != Some(e.as_str()) {
+                println!("⚠️  [CLUSTER] Peer {} unreachable: {}", peer, e);
+            }
+            state.last_error = Some(e);
+        }
+        None => {
+            state.last_success = Some(Utc::now());
+            state.last_error = None;
+        }
+    }
+}
+/// `request` with the peer token, if any, as its bearer credential
+fn authorized(request: reqwest::RequestBuilder, token: Option<&str>) -> reqwest::RequestBuilder {
+    match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
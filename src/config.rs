@@ -0,0 +1,128 @@
+//! The optional JSON hub configuration file (`--config`).
+//!
+//! ```json
+//! {
+//!   "load_balancing": {
+//!     "default": "round_robin",
+//!     "services": { "dividend-service": "consistent_hash" },
+//!     "hash_keys": { "dividend-service": { "json_path": "user_id" } }
+//!   },
+//!   "traffic_splits": {
+//!     "dividend-service": [{ "version": "1.0.0", "weight": 95 }, { "version": "1.1.0", "weight": 5 }]
+//!   },
+//!   "namespaces": {
+//!     "shared": { "exports": ["auth-service"] }
+//!   },
+//!   "retries": {
+//!     "services": { "dividend-service": { "max_attempts": 4, "idempotent": true } }
+//!   },
+//!   "circuit_breaker": { "failure_threshold": 5, "open_duration_ms": 30000 },
+//!   "limits": {
+//!     "callers": { "*": { "rate_per_sec": 20, "max_in_flight": 10 } }
+//!   },
+//!   "auth": {
+//!     "api_keys": { "dividend-service": "5f0c8e..." },
+//!     "jwt": { "secret": "change-me" }
+//!   },
+//!   "rbac": {
+//!     "subjects": { "dividend-service": { "roles": ["registrar"] }, "*": { "roles": ["reader"] } }
+//!   },
+//!   "tls": {
+//!     "cert_path": "/etc/grpc-hub/hub.pem",
+//!     "key_path": "/etc/grpc-hub/hub-key.pem",
+//!     "backends": { "dividend-service": { "ca_path": "/etc/grpc-hub/backends-ca.pem" } }
+//!   }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::auth::AuthConfig;
+use crate::balancer::BalancerConfig;
+use crate::breaker::BreakerConfig;
+use crate::limits::LimitsConfig;
+use crate::namespace::NamespaceConfig;
+use crate::rbac::RbacConfig;
+use crate::retry::RetryConfig;
+use crate::tls::TlsConfig;
+use crate::versions::{validate_split, SplitEntry};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HubConfig {
+    /// Load-balancing strategy per service name
+    pub load_balancing: BalancerConfig,
+    /// Initial traffic split between versions per service name
+    pub traffic_splits: HashMap<String, Vec<SplitEntry>>,
+    /// Export lists per namespace
+    pub namespaces: HashMap<String, NamespaceConfig>,
+    /// Retry policy per service name
+    pub retries: RetryConfig,
+    /// When instances' circuits open, and for how long
+    pub circuit_breaker: BreakerConfig,
+    /// Rate and in-flight limits per service, caller and instance
+    pub limits: LimitsConfig,
+    /// API keys and JWT settings callers authenticate with; none means no authentication
+    pub auth: AuthConfig,
+    /// Roles per caller; none means every caller may do anything
+    pub rbac: RbacConfig,
+    /// Certificates for the listeners and backends; none means plaintext everywhere
+    pub tls: TlsConfig,
+}
+
+impl HubConfig {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        for (service, entries) in &config.traffic_splits {
+            validate_split(entries)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Invalid traffic split for '{}' in {}", service, path.display()))?;
+        }
+        for namespace in config.namespaces.keys() {
+            crate::namespace::validate(namespace)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Invalid config file {}", path.display()))?;
+        }
+        config.retries.validate()
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.limits.validate()
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.auth.validate()
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.rbac.validate()
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        config.tls.validate()
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balancer::BalancerKind;
+
+    #[test]
+    fn test_parse_config() {
+        let config: HubConfig = serde_json::from_str(
+            r#"{"load_balancing": {"services": {"dividend": "power_of_two"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.load_balancing.default, BalancerKind::RoundRobin);
+        assert_eq!(config.load_balancing.services["dividend"], BalancerKind::PowerOfTwo);
+
+        assert!(serde_json::from_str::<HubConfig>(r#"{"load_balancing": {"default": "random"}}"#).is_err());
+    }
+}
@@ -0,0 +1,524 @@
+//! Fanning one call out to several instances of a service.
+//!
+//! By default a call goes to a single instance, and is retried on another one as the
+//! service's retry policy allows (see [`crate::retry`]). A caller can also pick a
+//! dispatch mode:
+//!
+//! - `first_success`: call every free instance and return the first successful response
+//! - `gather`: call every free instance and return all responses
+//! - `quorum`: call every free instance and return once enough of them agree on a response
+//! - `hedged`: call one instance, then another each time the delay passes without an answer
+//!
+//! As soon as the outcome is decided the remaining calls are dropped, which cancels
+//! them downstream and puts their instances back `online`.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde_json::Value;
+use tonic::Code;
+
+use crate::grpc_client::{CallOptions, GrpcCallError, GrpcCallResult};
+use crate::{AcquiredInstance, BusyGuard, CallRouting, GrpcHubService, ServiceInfo};
+
+/// Delay between hedged attempts when the caller doesn't choose one
+pub const DEFAULT_HEDGE_DELAY: Duration = Duration::from_millis(100);
+
+/// How a call is spread over the instances of its target service
+#[derive(Debug, Clone, PartialEq)]
+pub enum DispatchMode {
+    Single,
+    FirstSuccess,
+    Gather,
+    /// Matching responses required; 0 means a majority of the instances called
+    Quorum(usize),
+    Hedged(Duration),
+}
+
+impl DispatchMode {
+    pub fn parse(mode: &str, quorum: i64, hedge_delay_ms: i64) -> Result<Self, GrpcCallError> {
+        match mode {
+            "" | "single" => Ok(Self::Single),
+            "first_success" => Ok(Self::FirstSuccess),
+            "gather" => Ok(Self::Gather),
+            "quorum" => Ok(Self::Quorum(quorum.max(0) as usize)),
+            "hedged" if hedge_delay_ms > 0 => Ok(Self::Hedged(Duration::from_millis(hedge_delay_ms as u64))),
+            "hedged" => Ok(Self::Hedged(DEFAULT_HEDGE_DELAY)),
+            other => Err(GrpcCallError::new(
+                Code::InvalidArgument,
+                format!("Unknown dispatch mode '{}' (expected single, first_success, gather, quorum or hedged)", other),
+            )),
+        }
+    }
+}
+
+/// The method and request being dispatched
+#[derive(Debug, Clone)]
+pub struct DispatchCall {
+    pub service_name: String,
+    pub grpc_service: String,
+    pub method: String,
+    pub input: Value,
+    pub options: CallOptions,
+    pub routing: CallRouting,
+}
+
+/// What one instance answered
+#[derive(Debug)]
+pub struct InstanceOutcome {
+    pub service_id: String,
+    pub result: Result<GrpcCallResult, GrpcCallError>,
+}
+
+/// The overall result of a dispatched call plus every instance outcome that arrived before it was decided
+#[derive(Debug)]
+pub struct DispatchResult {
+    pub result: Result<GrpcCallResult, GrpcCallError>,
+    pub outcomes: Vec<InstanceOutcome>,
+}
+
+/// Dispatch `call` according to `mode`
+pub async fn dispatch(hub: &GrpcHubService, call: &DispatchCall, mode: &DispatchMode) -> DispatchResult {
+    if *mode == DispatchMode::Single {
+        return single(hub, call).await;
+    }
+    println!("📣 [DISPATCH] {:?} call to {}/{}", mode, call.service_name, call.method);
+    match mode {
+        DispatchMode::Hedged(delay) => hedged(hub, call, *delay).await,
+        _ => fan_out(hub, call, mode).await,
+    }
+}
+
+/// Call one instance, then another each time an attempt fails and the service's retry policy allows
+async fn single(hub: &GrpcHubService, call: &DispatchCall) -> DispatchResult {
+    let policy = hub.retries.policy(&call.service_name);
+    // Every retry avoids the instances that already failed
+    let mut routing = call.routing.clone();
+    let mut outcomes = Vec::new();
+
+    for attempt in 1.. {
+        let instance = match hub.acquire_service_by_name(&call.service_name, &routing).await {
+            Ok(instance) => instance,
+            // Nowhere left to retry: the caller gets the last failure, not the lack of instances
+            Err(e) if outcomes.is_empty() => return DispatchResult { result: Err(e), outcomes },
+            Err(_) => break,
+        };
+        println!("🎯 [DEBUG] Hub: Selected service {} at {}:{}", instance.guard.service_id(), instance.host, instance.port);
+
+        let outcome = call_instance(hub, instance, call).await;
+        let error = match &outcome.result {
+            Ok(result) => {
+                let result = Ok(result.clone());
+                outcomes.push(outcome);
+                return DispatchResult { result, outcomes };
+            }
+            Err(e) => e.clone(),
+        };
+        routing.excluded.insert(outcome.service_id.clone());
+        outcomes.push(outcome);
+
+        if attempt >= policy.max_attempts || !policy.should_retry(&error) {
+            break;
+        }
+        let backoff = policy.backoff(attempt);
+        if routing.deadline.is_some_and(|deadline| tokio::time::Instant::now() + backoff >= deadline) {
+            break;
+        }
+        println!("🔁 [RETRY] Attempt {} of {}/{} failed ({:?}); retrying on another instance in {:?}",
+                 attempt, call.service_name, call.method, error.code, backoff);
+        tokio::time::sleep(backoff).await;
+    }
+
+    DispatchResult { result: Err(last_error(&outcomes)), outcomes }
+}
+
+async fn fan_out(hub: &GrpcHubService, call: &DispatchCall, mode: &DispatchMode) -> DispatchResult {
+    let mut instances = acquire_free_instances(hub, call, &HashSet::new()).await;
+    if instances.is_empty() {
+        // Every instance is busy: wait for one like a single call would
+        let routing = CallRouting { hash_key: None, ..call.routing.clone() };
+        match hub.acquire_service_by_name(&call.service_name, &routing).await {
+            Ok(instance) => instances.push(instance),
+            Err(e) => return DispatchResult { result: Err(e), outcomes: Vec::new() },
+        }
+    }
+
+    let called = instances.len();
+    let required = match mode {
+        DispatchMode::Quorum(0) => called / 2 + 1,
+        DispatchMode::Quorum(quorum) => *quorum,
+        _ => 0,
+    };
+    if required > called {
+        return DispatchResult {
+            result: Err(GrpcCallError::new(
+                Code::FailedPrecondition,
+                format!("Quorum of {} needs more instances than the {} available", required, called),
+            )),
+            outcomes: Vec::new(),
+        };
+    }
+
+    let mut pending: FuturesUnordered<_> = instances.into_iter().map(|instance| call_instance(hub, instance, call)).collect();
+    let mut outcomes = Vec::new();
+    // Distinct successful responses and how many instances returned each
+    let mut votes: Vec<(Value, usize)> = Vec::new();
+
+    while let Some(outcome) = pending.next().await {
+        if let Ok(result) = &outcome.result {
+            match mode {
+                DispatchMode::FirstSuccess => {
+                    let result = Ok(result.clone());
+                    outcomes.push(outcome);
+                    return DispatchResult { result, outcomes };
+                }
+                DispatchMode::Quorum(_) => {
+                    let count = match votes.iter_mut().find(|(response, _)| *response == result.response) {
+                        Some((_, count)) => {
+                            *count += 1;
+                            *count
+                        }
+                        None => {
+                            votes.push((result.response.clone(), 1));
+                            1
+                        }
+                    };
+                    if count >= required {
+                        let result = Ok(result.clone());
+                        outcomes.push(outcome);
+                        return DispatchResult { result, outcomes };
+                    }
+                }
+                _ => {}
+            }
+        }
+        outcomes.push(outcome);
+
+        // Stop early once no answer still in flight could complete a quorum
+        if let DispatchMode::Quorum(_) = mode {
+            let best = votes.iter().map(|(_, count)| *count).max().unwrap_or(0);
+            if best + pending.len() < required {
+                break;
+            }
+        }
+    }
+
+    let result = match mode {
+        DispatchMode::Gather if outcomes.iter().any(|o| o.result.is_ok()) => {
+            let responses = outcomes.iter()
+                .filter_map(|o| o.result.as_ref().ok().map(|r| r.response.clone()))
+                .collect();
+            Ok(GrpcCallResult { response: Value::Array(responses), metadata: HashMap::new() })
+        }
+        DispatchMode::Quorum(_) if outcomes.iter().any(|o| o.result.is_ok()) => Err(GrpcCallError::new(
+            Code::Aborted,
+            format!("No {} of {} instances agreed on a response", required, called),
+        )),
+        _ => Err(last_error(&outcomes)),
+    };
+    DispatchResult { result, outcomes }
+}
+
+async fn hedged(hub: &GrpcHubService, call: &DispatchCall, delay: Duration) -> DispatchResult {
+    let first = match hub.acquire_service_by_name(&call.service_name, &call.routing).await {
+        Ok(instance) => instance,
+        Err(e) => return DispatchResult { result: Err(e), outcomes: Vec::new() },
+    };
+
+    let mut used: HashSet<String> = HashSet::from([first.guard.service_id().to_string()]);
+    let mut pending = FuturesUnordered::new();
+    pending.push(call_instance(hub, first, call));
+    let mut outcomes = Vec::new();
+
+    loop {
+        tokio::select! {
+            outcome = pending.next() => match outcome {
+                Some(outcome) => {
+                    if let Ok(result) = &outcome.result {
+                        let result = Ok(result.clone());
+                        outcomes.push(outcome);
+                        return DispatchResult { result, outcomes };
+                    }
+                    // A failed attempt is hedged right away
+                    outcomes.push(outcome);
+                }
+                None => break,
+            },
+            _ = tokio::time::sleep(delay) => {}
+        }
+
+        match acquire_free_instances(hub, call, &used).await.into_iter().next() {
+            Some(instance) => {
+                println!("📣 [DISPATCH] Hedging {}/{} to {}", call.service_name, call.method, instance.guard.service_id());
+                used.insert(instance.guard.service_id().to_string());
+                pending.push(call_instance(hub, instance, call));
+            }
+            None => {
+                // Nothing left to hedge to; just wait for the attempts in flight
+                while let Some(outcome) = pending.next().await {
+                    if let Ok(result) = &outcome.result {
+                        let result = Ok(result.clone());
+                        outcomes.push(outcome);
+                        return DispatchResult { result, outcomes };
+                    }
+                    outcomes.push(outcome);
+                }
+                break;
+            }
+        }
+    }
+
+    DispatchResult { result: Err(last_error(&outcomes)), outcomes }
+}
+
+/// Reserve every online instance the call may use that is not listed in `exclude`
+async fn acquire_free_instances(hub: &GrpcHubService, call: &DispatchCall, exclude: &HashSet<String>) -> Vec<AcquiredInstance> {
+    let candidates: Vec<ServiceInfo> = hub.services.read().await.values()
+        .filter(|s| s.service_name == call.service_name && s.status == "online" && !exclude.contains(&s.service_id))
+        .filter(|s| call.routing.accepts(s) && hub.breakers.allows(&s.service_id) && hub.instance_wait(s).is_none())
+        .cloned()
+        .collect();
+
+    let mut instances = Vec::new();
+    for service in candidates {
+        let Ok(port) = service.service_port.parse() else { continue };
+        if let Some(guard) = BusyGuard::try_acquire(hub, &service.service_id).await {
+            hub.take_instance(&service);
+            instances.push(AcquiredInstance { guard, host: service.service_address, port });
+        }
+    }
+    instances
+}
+
+/// Call one reserved instance. Dropping the future cancels the call and releases the instance.
+async fn call_instance(hub: &GrpcHubService, instance: AcquiredInstance, call: &DispatchCall) -> InstanceOutcome {
+    let AcquiredInstance { guard, host, port } = instance;
+    let service_id = guard.service_id().to_string();
+    let result = hub.call_grpc_method(&host, port, &call.grpc_service, &call.method, call.input.clone(), &call.options).await;
+    hub.record_call_outcome(&service_id, &result).await;
+
+    match &result {
+        Err(e) if e.connection_failure => guard.release_offline("Direct connection failed").await,
+        _ => guard.release().await,
+    }
+    InstanceOutcome { service_id, result }
+}
+
+impl From<&InstanceOutcome> for crate::grpc_hub::InstanceCallResult {
+    fn from(outcome: &InstanceOutcome) -> Self {
+        match &outcome.result {
+            Ok(result) => Self {
+                service_id: outcome.service_id.clone(),
+                success: true,
+                response_data: result.response.to_string(),
+                error_message: String::new(),
+                grpc_code: Code::Ok as i32,
+            },
+            Err(e) => Self {
+                service_id: outcome.service_id.clone(),
+                success: false,
+                response_data: String::new(),
+                error_message: e.message.clone(),
+                grpc_code: e.code as i32,
+            },
+        }
+    }
+}
+
+fn last_error(outcomes: &[InstanceOutcome]) -> GrpcCallError {
+    outcomes.iter().rev()
+        .find_map(|o| o.result.as_ref().err().cloned())
+        .unwrap_or_else(|| GrpcCallError::new(Code::Unavailable, "No instance answered"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc_client::{call_deadline, DynamicGrpcClient};
+    use crate::grpc_hub::grpc_hub_server::{GrpcHub, GrpcHubServer};
+    use crate::grpc_hub::RegisterServiceRequest;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+    use tonic::Request;
+
+    fn test_hub() -> GrpcHubService {
+        GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap())
+    }
+
+    /// Register `name` at 127.0.0.1:`port` and return its ID
+    async fn register(hub: &GrpcHubService, name: &str, port: u16) -> String {
+        hub.register_service(Request::new(RegisterServiceRequest {
+            service_name: name.to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: port.to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .service_id
+    }
+
+    /// Another hub, whose ListServices answers with the services registered on it
+    async fn backend(services: &[&str]) -> u16 {
+        let backend = test_hub();
+        for name in services {
+            register(&backend, name, 1).await;
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(
+            Server::builder()
+                .add_service(GrpcHubServer::new(backend))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        port
+    }
+
+    /// A backend that accepts connections but never answers
+    async fn silent_backend() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        port
+    }
+
+    /// A ListServices call to the "grpc-hub" instances
+    fn list_services() -> DispatchCall {
+        DispatchCall {
+            service_name: "grpc-hub".to_string(),
+            grpc_service: "grpc_hub.GrpcHub".to_string(),
+            method: "ListServices".to_string(),
+            input: serde_json::json!({}),
+            options: CallOptions {
+                headers: HashMap::new(),
+                deadline: call_deadline(None, 5_000),
+                service_name: "grpc-hub".to_string(),
+            },
+            routing: CallRouting::default(),
+        }
+    }
+
+    async fn status(hub: &GrpcHubService, service_id: &str) -> String {
+        hub.services.read().await[service_id].status.clone()
+    }
+
+    #[test]
+    fn test_parse_dispatch_modes() {
+        assert_eq!(DispatchMode::parse("", 0, 0).unwrap(), DispatchMode::Single);
+        assert_eq!(DispatchMode::parse("quorum", 2, 0).unwrap(), DispatchMode::Quorum(2));
+        assert_eq!(DispatchMode::parse("hedged", 0, 0).unwrap(), DispatchMode::Hedged(DEFAULT_HEDGE_DELAY));
+        assert_eq!(
+            DispatchMode::parse("hedged", 0, 250).unwrap(),
+            DispatchMode::Hedged(Duration::from_millis(250))
+        );
+        assert_eq!(DispatchMode::parse("broadcast", 0, 0).unwrap_err().code, Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_first_success_skips_failed_instances() {
+        let hub = test_hub();
+        register(&hub, "grpc-hub", 1).await;
+        let healthy = register(&hub, "grpc-hub", backend(&[]).await).await;
+
+        let dispatched = dispatch(&hub, &list_services(), &DispatchMode::FirstSuccess).await;
+
+        assert!(dispatched.result.is_ok());
+        let winner = dispatched.outcomes.last().unwrap();
+        assert_eq!(winner.service_id, healthy);
+        assert!(winner.result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_first_success_cancels_the_losers_and_puts_them_back_online() {
+        let hub = test_hub();
+        let silent = register(&hub, "grpc-hub", silent_backend().await).await;
+        register(&hub, "grpc-hub", backend(&[]).await).await;
+
+        let started = tokio::time::Instant::now();
+        let dispatched = dispatch(&hub, &list_services(), &DispatchMode::FirstSuccess).await;
+
+        // The answer doesn't wait for the silent instance, whose call is dropped
+        assert!(dispatched.result.is_ok());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(dispatched.outcomes.iter().all(|o| o.service_id != silent));
+        for _ in 0..50 {
+            if status(&hub, &silent).await == "online" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(status(&hub, &silent).await, "online");
+    }
+
+    #[tokio::test]
+    async fn test_quorum_returns_the_response_enough_instances_agree_on() {
+        let hub = test_hub();
+        for services in [&[][..], &[], &["ledger"]] {
+            register(&hub, "grpc-hub", backend(services).await).await;
+        }
+
+        let dispatched = dispatch(&hub, &list_services(), &DispatchMode::Quorum(2)).await;
+
+        // Both empty backends agree; the one listing "ledger" doesn't
+        let response = dispatched.result.unwrap().response;
+        assert!(!response.to_string().contains("ledger"));
+    }
+
+    #[tokio::test]
+    async fn test_quorum_fails_when_instances_disagree() {
+        let hub = test_hub();
+        register(&hub, "grpc-hub", backend(&[]).await).await;
+        register(&hub, "grpc-hub", backend(&["ledger"]).await).await;
+
+        let dispatched = dispatch(&hub, &list_services(), &DispatchMode::Quorum(2)).await;
+
+        assert_eq!(dispatched.result.unwrap_err().code, Code::Aborted);
+        assert_eq!(dispatched.outcomes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_quorum_larger_than_the_instances_fails_without_calling_them() {
+        let hub = test_hub();
+        register(&hub, "grpc-hub", backend(&[]).await).await;
+
+        let dispatched = dispatch(&hub, &list_services(), &DispatchMode::Quorum(2)).await;
+
+        assert_eq!(dispatched.result.unwrap_err().code, Code::FailedPrecondition);
+        assert!(dispatched.outcomes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hedge_goes_to_another_instance_after_the_delay() {
+        let hub = test_hub();
+        let silent = register(&hub, "grpc-hub", silent_backend().await).await;
+        let healthy = register(&hub, "grpc-hub", backend(&[]).await).await;
+
+        // Keep the healthy instance taken so the first attempt goes to the silent one
+        let held = BusyGuard::try_acquire(&hub, &healthy).await.unwrap();
+        let delay = Duration::from_millis(200);
+        let started = tokio::time::Instant::now();
+        let hedging = {
+            let hub = hub.clone();
+            tokio::spawn(async move { dispatch(&hub, &list_services(), &DispatchMode::Hedged(delay)).await })
+        };
+        while status(&hub, &silent).await != "busy" {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        held.release().await;
+
+        let dispatched = hedging.await.unwrap();
+
+        assert!(dispatched.result.is_ok());
+        assert!(started.elapsed() >= delay);
+        assert_eq!(dispatched.outcomes.len(), 1);
+        assert_eq!(dispatched.outcomes[0].service_id, healthy);
+    }
+}
@@ -0,0 +1,594 @@
+//! Native dynamic gRPC invocation for calls routed through the hub.
+//!
+//! Method descriptors come either from descriptor sets registered with the hub
+//! (the hub's own build plus any `--descriptor-set` files) or from the target's
+//! server reflection service. Requests are transcoded from JSON with
+//! `prost-reflect` and sent over a pooled tonic `Channel` per target address, over TLS
+//! for services with backend TLS settings.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::metadata::{KeyAndValueRef, MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+use tonic_reflection::pb::v1::ServerReflectionRequest;
+
+use crate::tls::BackendTls;
+
+/// Descriptor set produced by `build.rs` for the protos compiled into the hub.
+const BUILTIN_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/proto_descriptor.bin"));
+
+/// Default deadline applied to calls made through the hub when the caller sets none
+const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a call made on a caller's behalf carries besides its request message
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// Sent to the target as request metadata
+    pub headers: HashMap<String, String>,
+    /// When the caller stops waiting; `None` means the hub's default timeout
+    pub deadline: Option<Instant>,
+    /// Registered name of the target service, whose backend TLS settings apply
+    pub service_name: String,
+}
+
+/// The deadline of a call received now, from its `grpc-timeout` header and its `timeout_ms`
+/// field (non-positive means unset), whichever is sooner
+pub fn call_deadline(grpc_timeout: Option<&str>, timeout_ms: i64) -> Option<Instant> {
+    let requested = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms as u64));
+    let timeout = match (grpc_timeout.and_then(parse_grpc_timeout), requested) {
+        (Some(header), Some(requested)) => header.min(requested),
+        (header, requested) => header.or(requested)?,
+    };
+    Some(Instant::now() + timeout)
+}
+
+/// Parse a `grpc-timeout` value: at most 8 digits followed by a unit (`H`, `M`, `S`, `m`, `u` or `n`)
+pub fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.len() < 2 || value.len() > 9 || !value.is_ascii() {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    if !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 3600)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+/// Format the time left until `deadline` as a `grpc-timeout` value
+pub fn grpc_timeout_value(deadline: Instant) -> Result<String, GrpcCallError> {
+    // 8 digits of milliseconds cover a bit over a day
+    Ok(format!("{}m", remaining(deadline)?.as_millis().clamp(1, 99_999_999)))
+}
+
+/// The time left until `deadline`, or DEADLINE_EXCEEDED once it has passed
+pub fn remaining(deadline: Instant) -> Result<Duration, GrpcCallError> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(left) if !left.is_zero() => Ok(left),
+        _ => Err(deadline_exceeded()),
+    }
+}
+
+fn deadline_exceeded() -> GrpcCallError {
+    GrpcCallError::new(Code::DeadlineExceeded, "Deadline exceeded before the call completed")
+}
+
+/// A successful call: the decoded response plus the response headers and trailers
+#[derive(Debug, Clone)]
+pub struct GrpcCallResult {
+    pub response: serde_json::Value,
+    pub metadata: HashMap<String, String>,
+}
+
+/// A failed call, carrying the gRPC status returned by the target (or synthesized by the hub)
+#[derive(Debug, Clone)]
+pub struct GrpcCallError {
+    pub code: Code,
+    pub message: String,
+    pub metadata: HashMap<String, String>,
+    /// True when the hub could not reach the target at all, as opposed to the
+    /// target answering with an error status
+    pub connection_failure: bool,
+}
+
+impl GrpcCallError {
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            metadata: HashMap::new(),
+            connection_failure: false,
+        }
+    }
+
+    pub fn from_status(status: Status) -> Self {
+        // Statuses produced by the transport carry the underlying error as their
+        // source; statuses sent by the target never do.
+        let connection_failure = status.code() == Code::Unavailable && std::error::Error::source(&status).is_some();
+        Self {
+            code: status.code(),
+            message: status.message().to_string(),
+            metadata: metadata_to_map(status.metadata()),
+            connection_failure,
+        }
+    }
+
+    /// HTTP status code matching this error's gRPC code
+    pub fn http_status(&self) -> u16 {
+        http_status_for_code(self.code)
+    }
+}
+
+impl std::fmt::Display for GrpcCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "gRPC call failed ({:?}): {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for GrpcCallError {}
+
+impl From<GrpcCallError> for Status {
+    fn from(error: GrpcCallError) -> Self {
+        Status::new(error.code, error.message)
+    }
+}
+
+/// Map a gRPC status code to the closest HTTP status code
+pub fn http_status_for_code(code: Code) -> u16 {
+    match code {
+        Code::Ok => 200,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => 400,
+        Code::Unauthenticated => 401,
+        Code::PermissionDenied => 403,
+        Code::NotFound => 404,
+        Code::AlreadyExists | Code::Aborted => 409,
+        Code::ResourceExhausted => 429,
+        Code::Cancelled => 499,
+        Code::Unimplemented => 501,
+        Code::Unavailable => 503,
+        Code::DeadlineExceeded => 504,
+        _ => 500,
+    }
+}
+
+/// Convert the ASCII entries of a metadata map into plain strings
+pub fn metadata_to_map(metadata: &MetadataMap) -> HashMap<String, String> {
+    metadata
+        .iter()
+        .filter_map(|entry| match entry {
+            KeyAndValueRef::Ascii(key, value) => value
+                .to_str()
+                .ok()
+                .map(|v| (key.as_str().to_string(), v.to_string())),
+            KeyAndValueRef::Binary(_, _) => None,
+        })
+        .collect()
+}
+
+/// Dynamic gRPC client shared by every call the hub makes on behalf of a caller
+#[derive(Debug)]
+pub struct DynamicGrpcClient {
+    registered: DescriptorPool,
+    reflected: RwLock<HashMap<String, DescriptorPool>>,
+    channels: RwLock<HashMap<String, Channel>>,
+    /// TLS settings per service name, and the reload the pooled channels were made with
+    tls: Arc<BackendTls>,
+    tls_generation: std::sync::atomic::AtomicU64,
+}
+
+impl DynamicGrpcClient {
+    /// Create a client that knows the hub's built-in protos plus the given descriptor sets
+    pub fn new(descriptor_sets: &[Vec<u8>]) -> anyhow::Result<Self> {
+        let mut registered = DescriptorPool::decode(BUILTIN_DESCRIPTOR_SET)?;
+        for bytes in descriptor_sets {
+            registered.decode_file_descriptor_set(bytes.as_slice())?;
+        }
+        Ok(Self {
+            registered,
+            reflected: RwLock::new(HashMap::new()),
+            channels: RwLock::new(HashMap::new()),
+            tls: Arc::new(BackendTls::default()),
+            tls_generation: std::sync::atomic::AtomicU64::new(0),
+        })
+    }
+
+    /// Call the instances of the services in `tls` over TLS
+    pub fn with_tls(mut self, tls: Arc<BackendTls>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Get (or lazily create) the pooled channel for an instance of `service_name` at a target address
+    pub async fn channel(&self, service_name: &str, host: &str, port: u16) -> Result<Channel, GrpcCallError> {
+        // Certificates were reloaded: reconnect everything with the new ones
+        let generation = self.tls.generation();
+        if self.tls_generation.swap(generation, std::sync::atomic::Ordering::Relaxed) != generation {
+            self.channels.write().await.clear();
+        }
+
+        let tls = self.tls.client_config(service_name, host);
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let address = format!("{}://{}:{}", scheme, host, port);
+        if let Some(channel) = self.channels.read().await.get(&address) {
+            return Ok(channel.clone());
+        }
+
+        let mut endpoint = Endpoint::from_str(&address)
+            .map_err(|e| GrpcCallError::new(Code::InvalidArgument, format!("Invalid address {}: {}", address, e)))?;
+        if let Some(tls) = tls {
+            endpoint = endpoint.tls_config(tls)
+                .map_err(|e| GrpcCallError::new(Code::InvalidArgument, format!("Invalid TLS settings for '{}': {}", service_name, e)))?;
+        }
+        let channel = endpoint.connect_lazy();
+
+        let mut channels = self.channels.write().await;
+        Ok(channels.entry(address).or_insert(channel).clone())
+    }
+
+    /// Resolve a method descriptor from registered descriptors or the target's reflection service
+    pub async fn resolve_method(
+        &self,
+        service_name: &str,
+        host: &str,
+        port: u16,
+        service: &str,
+        method: &str,
+    ) -> Result<MethodDescriptor, GrpcCallError> {
+        if let Some(found) = find_method(&self.registered, service, method) {
+            return Ok(found);
+        }
+
+        let address = format!("{}:{}", host, port);
+        if let Some(pool) = self.reflected.read().await.get(&address) {
+            if let Some(found) = find_method(pool, service, method) {
+                return Ok(found);
+            }
+        }
+
+        // Unknown or stale: (re)fetch descriptors from the target
+        println!("🔍 [DEBUG] Hub: Fetching descriptors for {} from {} via reflection", service, address);
+        let channel = self.channel(service_name, host, port).await?;
+        let pool = fetch_reflection_descriptors(channel, service).await?;
+        let found = find_method(&pool, service, method);
+        self.reflected.write().await.insert(address, pool);
+
+        found.ok_or_else(|| GrpcCallError::new(Code::Unimplemented, format!("Method {}/{} not found", service, method)))
+    }
+
+    /// Invoke a unary method with a JSON request, returning the JSON response. The call is
+    /// abandoned (and cancelled on the target) once the deadline in `options` passes.
+    pub async fn call_unary(
+        &self,
+        host: &str,
+        port: u16,
+        service: &str,
+        method: &str,
+        input: serde_json::Value,
+        options: &CallOptions,
+    ) -> Result<GrpcCallResult, GrpcCallError> {
+        let deadline = options.deadline.unwrap_or_else(|| Instant::now() + DEFAULT_CALL_TIMEOUT);
+        let call = async {
+            let descriptor = self.resolve_method(&options.service_name, host, port, service, method).await?;
+            let message = json_to_message(descriptor.input(), input)?;
+
+            let mut request = tonic::Request::new(message);
+            apply_headers(request.metadata_mut(), &options.headers);
+            // The target only gets what is left after the hub's own queueing and lookups
+            request.set_timeout(remaining(deadline)?);
+
+            let channel = self.channel(&options.service_name, host, port).await?;
+            let mut grpc = tonic::client::Grpc::new(channel);
+            grpc.ready()
+                .await
+                .map_err(|e| GrpcCallError::from_status(Status::from_error(Box::new(e))))?;
+
+            let path = method_path(service, method)?;
+            let codec = DynamicCodec::new(descriptor.output());
+            let response = grpc.unary(request, path, codec).await.map_err(GrpcCallError::from_status)?;
+
+            let (metadata, message, _) = response.into_parts();
+            Ok::<_, GrpcCallError>(GrpcCallResult {
+                response: message_to_json(&message)?,
+                metadata: metadata_to_map(&metadata),
+            })
+        };
+        match tokio::time::timeout_at(deadline, call).await {
+            // The channel enforces the timeout set on the request too, reporting CANCELLED
+            Ok(Err(e)) if e.code == Code::Cancelled && remaining(deadline).is_err() => Err(deadline_exceeded()),
+            Ok(result) => result,
+            Err(_) => Err(deadline_exceeded()),
+        }
+    }
+
+    /// Open a streaming call for any method kind; requests are sent as `requests` yields them
+    /// and the response stream is returned together with the response headers
+    pub async fn call_streaming<S>(
+        &self,
+        service_name: &str,
+        host: &str,
+        port: u16,
+        descriptor: &MethodDescriptor,
+        requests: S,
+        headers: &HashMap<String, String>,
+    ) -> Result<(HashMap<String, String>, tonic::Streaming<DynamicMessage>), GrpcCallError>
+    where
+        S: tokio_stream::Stream<Item = DynamicMessage> + Send + 'static,
+    {
+        let mut request = tonic::Request::new(requests);
+        apply_headers(request.metadata_mut(), headers);
+
+        let channel = self.channel(service_name, host, port).await?;
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready()
+            .await
+            .map_err(|e| GrpcCallError::from_status(Status::from_error(Box::new(e))))?;
+
+        let path = method_path(descriptor.parent_service().full_name(), descriptor.name())?;
+        let codec = DynamicCodec::new(descriptor.output());
+        let response = grpc.streaming(request, path, codec).await.map_err(GrpcCallError::from_status)?;
+
+        let (metadata, stream, _) = response.into_parts();
+        Ok((metadata_to_map(&metadata), stream))
+    }
+}
+
+fn find_method(pool: &DescriptorPool, service: &str, method: &str) -> Option<MethodDescriptor> {
+    pool.get_service_by_name(service)?
+        .methods()
+        .find(|m| m.name() == method)
+}
+
+/// Build the `/package.Service/Method` request path
+pub fn method_path(service: &str, method: &str) -> Result<http::uri::PathAndQuery, GrpcCallError> {
+    http::uri::PathAndQuery::from_str(&format!("/{}/{}", service, method))
+        .map_err(|e| GrpcCallError::new(Code::InvalidArgument, format!("Invalid method path: {}", e)))
+}
+
+/// Copy caller-supplied headers into outgoing request metadata, skipping invalid entries
+pub fn apply_headers(metadata: &mut MetadataMap, headers: &HashMap<String, String>) {
+    for (key, value) in headers {
+        match (MetadataKey::from_bytes(key.to_lowercase().as_bytes()), MetadataValue::try_from(value.as_str())) {
+            (Ok(key), Ok(value)) => {
+                metadata.insert(key, value);
+            }
+            _ => println!("⚠️  Skipping invalid header '{}'", key),
+        }
+    }
+}
+
+pub fn json_to_message(descriptor: MessageDescriptor, input: serde_json::Value) -> Result<DynamicMessage, GrpcCallError> {
+    DynamicMessage::deserialize(descriptor, input)
+        .map_err(|e| GrpcCallError::new(Code::InvalidArgument, format!("Invalid request data: {}", e)))
+}
+
+pub fn message_to_json(message: &DynamicMessage) -> Result<serde_json::Value, GrpcCallError> {
+    serde_json::to_value(message)
+        .map_err(|e| GrpcCallError::new(Code::Internal, format!("Failed to encode response as JSON: {}", e)))
+}
+
+/// Fetch the file descriptors defining `service` (and their dependencies) via server reflection
+async fn fetch_reflection_descriptors(channel: Channel, service: &str) -> Result<DescriptorPool, GrpcCallError> {
+    let reflection_request = |message_request| ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(message_request),
+    };
+
+    let (tx, rx) = tokio::sync::mpsc::channel(16);
+    let _ = tx.send(reflection_request(MessageRequest::FileContainingSymbol(service.to_string()))).await;
+
+    let mut client = ServerReflectionClient::new(channel);
+    let mut responses = client
+        .server_reflection_info(ReceiverStream::new(rx))
+        .await
+        .map_err(GrpcCallError::from_status)?
+        .into_inner();
+
+    let mut files: HashMap<String, prost_types::FileDescriptorProto> = HashMap::new();
+    let mut requested: HashSet<String> = HashSet::new();
+    let mut pending = 1;
+
+    while pending > 0 {
+        let response = responses
+            .message()
+            .await
+            .map_err(GrpcCallError::from_status)?
+            .ok_or_else(|| GrpcCallError::new(Code::Unavailable, "Reflection stream closed early"))?;
+        pending -= 1;
+
+        match response.message_response {
+            Some(MessageResponse::FileDescriptorResponse(descriptors)) => {
+                for bytes in descriptors.file_descriptor_proto {
+                    let file = prost_types::FileDescriptorProto::decode(bytes.as_slice())
+                        .map_err(|e| GrpcCallError::new(Code::Internal, format!("Invalid file descriptor: {}", e)))?;
+                    files.insert(file.name().to_string(), file);
+                }
+
+                // Ask for any dependency the server did not send along
+                let missing: Vec<String> = files
+                    .values()
+                    .flat_map(|file| file.dependency.iter())
+                    .filter(|dep| !files.contains_key(*dep))
+                    .cloned()
+                    .collect();
+                for dependency in missing {
+                    if requested.insert(dependency.clone()) {
+                        let _ = tx.send(reflection_request(MessageRequest::FileByFilename(dependency))).await;
+                        pending += 1;
+                    }
+                }
+            }
+            Some(MessageResponse::ErrorResponse(error)) => {
+                return Err(GrpcCallError::new(Code::from(error.error_code), error.error_message));
+            }
+            _ => {
+                return Err(GrpcCallError::new(Code::Internal, "Unexpected reflection response"));
+            }
+        }
+    }
+
+    let mut pool = DescriptorPool::new();
+    pool.add_file_descriptor_protos(files.into_values())
+        .map_err(|e| GrpcCallError::new(Code::Internal, format!("Invalid descriptors from reflection: {}", e)))?;
+    Ok(pool)
+}
+
+/// Codec that encodes and decodes `DynamicMessage`s for a known output type
+#[derive(Debug, Clone)]
+pub struct DynamicCodec {
+    output: MessageDescriptor,
+}
+
+impl DynamicCodec {
+    pub fn new(output: MessageDescriptor) -> Self {
+        Self { output }
+    }
+}
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.output.clone())
+    }
+}
+
+#[derive(Debug)]
+pub struct DynamicEncoder;
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst)
+            .map_err(|e| Status::internal(format!("Failed to encode request: {}", e)))
+    }
+}
+
+#[derive(Debug)]
+pub struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|e| Status::internal(format!("Failed to decode response: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reflection_descriptors_resolve_methods() {
+        let reflection = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(BUILTIN_DESCRIPTOR_SET)
+            .build_v1()
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(reflection)
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let client = DynamicGrpcClient::new(&[]).unwrap();
+        let channel = client.channel("dividend", "127.0.0.1", port).await.unwrap();
+        let pool = fetch_reflection_descriptors(channel, "dividend_service.DividendService").await.unwrap();
+
+        let method = find_method(&pool, "dividend_service.DividendService", "CalculateDividends").unwrap();
+        assert_eq!(method.input().full_name(), "dividend_service.CalculateDividendsRequest");
+    }
+
+    #[test]
+    fn test_json_round_trip_through_dynamic_message() {
+        let client = DynamicGrpcClient::new(&[]).unwrap();
+        let method = find_method(&client.registered, "dividend_service.DividendService", "CalculateDividends").unwrap();
+
+        let message = json_to_message(method.input(), serde_json::json!({"amount": 10.5, "user_id": "u1"})).unwrap();
+        let json = message_to_json(&message).unwrap();
+        assert_eq!(json, serde_json::json!({"amount": 10.5, "userId": "u1"}));
+
+        assert_eq!(json_to_message(method.input(), serde_json::json!({"amount": "abc"})).unwrap_err().code, Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_grpc_timeout_and_call_deadline() {
+        assert_eq!(parse_grpc_timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_grpc_timeout("2S"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_grpc_timeout("1H"), Some(Duration::from_secs(3600)));
+        for bad in ["", "m", "10", "10x", "-5m", "123456789m"] {
+            assert_eq!(parse_grpc_timeout(bad), None, "{}", bad);
+        }
+
+        assert!(call_deadline(None, 0).is_none());
+        let deadline = call_deadline(Some("5S"), 200).unwrap();
+        assert!(remaining(deadline).unwrap() <= Duration::from_millis(200));
+        assert!(grpc_timeout_value(deadline).unwrap().ends_with('m'));
+        assert_eq!(remaining(Instant::now()).unwrap_err().code, Code::DeadlineExceeded);
+    }
+
+    #[tokio::test]
+    async fn test_unary_call_gives_up_at_the_deadline() {
+        // A target that accepts connections but never speaks HTTP/2
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let client = DynamicGrpcClient::new(&[]).unwrap();
+        let options = CallOptions { deadline: call_deadline(None, 200), ..Default::default() };
+        let started = Instant::now();
+        let error = client
+            .call_unary("127.0.0.1", port, "dividend_service.DividendService", "CalculateDividends", serde_json::json!({}), &options)
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, Code::DeadlineExceeded);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_http_status_for_code() {
+        assert_eq!(http_status_for_code(Code::NotFound), 404);
+        assert_eq!(http_status_for_code(Code::ResourceExhausted), 429);
+        assert_eq!(http_status_for_code(Code::Internal), 500);
+    }
+}
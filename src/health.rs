@@ -0,0 +1,227 @@
+//! Probing registered instances with the standard gRPC health checking protocol.
+//!
+//! An instance is asked `grpc.health.v1.Health/Check` for its registered service name,
+//! and for the server as a whole ("") if it doesn't know that name. Instances that
+//! don't implement the health service at all are probed by opening a TCP connection.
+//!
+//! A single failed probe doesn't take an instance offline: it has to fail
+//! `failure_threshold` probes in a row, and then pass `success_threshold` in a row
+//! before heartbeats or probes can bring it back. The hub-wide [`HealthConfig`] can be
+//! overridden per instance with `hub.*` registration metadata (see [`HealthConfig::for_service`]).
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tonic::Code;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+
+use crate::grpc_client::DynamicGrpcClient;
+
+/// Heartbeat and probe settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthConfig {
+    /// How long an instance may go without a heartbeat before it is marked offline (its lease)
+    pub heartbeat_ttl: Duration,
+    /// How long an instance stays listed after its lease lapses before it is evicted
+    pub eviction_grace: Duration,
+    /// How often heartbeats are checked and due probes are started
+    pub sweep_interval: Duration,
+    /// How often each instance is probed
+    pub probe_interval: Duration,
+    /// How long a probe may take before it counts as failed
+    pub probe_timeout: Duration,
+    /// Failed probes in a row before an instance is marked offline
+    pub failure_threshold: u32,
+    /// Passed probes in a row before an instance taken offline by probes may come back
+    pub success_threshold: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_ttl: Duration::from_secs(10),
+            eviction_grace: Duration::from_secs(300),
+            sweep_interval: Duration::from_secs(1),
+            probe_interval: Duration::from_secs(5),
+            probe_timeout: Duration::from_secs(2),
+            failure_threshold: 3,
+            success_threshold: 1,
+        }
+    }
+}
+
+impl HealthConfig {
+    /// This config with the overrides from an instance's registration metadata applied:
+    /// `hub.heartbeat_ttl_ms`, `hub.eviction_grace_ms`, `hub.probe_interval_ms`, `hub.probe_timeout_ms`,
+    /// `hub.probe_failure_threshold` and `hub.probe_success_threshold`. Invalid values are ignored.
+    pub fn for_service(&self, metadata: &HashMap<String, String>) -> Self {
+        let millis = |key: &str, default: Duration| {
+            metadata.get(key)
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis)
+                .unwrap_or(default)
+        };
+        let count = |key: &str, default: u32| {
+            metadata.get(key)
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default)
+        };
+
+        Self {
+            heartbeat_ttl: millis("hub.heartbeat_ttl_ms", self.heartbeat_ttl),
+            eviction_grace: millis("hub.eviction_grace_ms", self.eviction_grace),
+            sweep_interval: self.sweep_interval,
+            probe_interval: millis("hub.probe_interval_ms", self.probe_interval),
+            probe_timeout: millis("hub.probe_timeout_ms", self.probe_timeout),
+            failure_threshold: count("hub.probe_failure_threshold", self.failure_threshold),
+            success_threshold: count("hub.probe_success_threshold", self.success_threshold),
+        }
+    }
+}
+
+/// Recent probe results for one instance
+#[derive(Debug, Default)]
+pub struct ProbeState {
+    pub last_probe: Option<Instant>,
+    pub failures: u32,
+    pub successes: u32,
+    /// Taken offline by failed probes and not yet recovered
+    pub down: bool,
+}
+
+impl ProbeState {
+    /// Record a probe result. Returns `Some(false)` when the instance has just failed enough
+    /// probes to go offline, and `Some(true)` when it has just passed enough to come back.
+    pub fn record(&mut self, healthy: bool, config: &HealthConfig) -> Option<bool> {
+        if healthy {
+            self.failures = 0;
+            self.successes += 1;
+            if self.down && self.successes >= config.success_threshold {
+                self.down = false;
+                return Some(true);
+            }
+        } else {
+            self.successes = 0;
+            self.failures += 1;
+            if !self.down && self.failures >= config.failure_threshold {
+                self.down = true;
+                return Some(false);
+            }
+        }
+        None
+    }
+}
+
+/// Whether the instance of `service_name` at `host:port` is serving
+pub async fn probe_instance(client: &DynamicGrpcClient, service_name: &str, host: &str, port: u16, timeout: Duration) -> bool {
+    let address = format!("{}:{}", host, port);
+    let channel = match client.channel(service_name, host, port).await {
+        Ok(channel) => channel,
+        Err(e) => {
+            println!("🔍 [HEALTH] Service {} has an invalid address: {}", address, e);
+            return false;
+        }
+    };
+    let mut health = HealthClient::new(channel);
+
+    for name in [service_name, ""] {
+        let request = HealthCheckRequest { service: name.to_string() };
+        match tokio::time::timeout(timeout, health.check(request)).await {
+            Ok(Ok(response)) => {
+                let status = response.into_inner().status();
+                if status != ServingStatus::Serving {
+                    println!("🔍 [HEALTH] Service {} reports {:?} for '{}'", address, status, name);
+                }
+                return status == ServingStatus::Serving;
+            }
+            // The server doesn't know this name; ask about the server as a whole
+            Ok(Err(status)) if status.code() == Code::NotFound => continue,
+            Ok(Err(status)) if status.code() == Code::Unimplemented => break,
+            Ok(Err(status)) => {
+                println!("🔍 [HEALTH] Service {} health check failed: {}", address, status.message());
+                return false;
+            }
+            Err(_) => {
+                println!("🔍 [HEALTH] Service {} health check timeout", address);
+                return false;
+            }
+        }
+    }
+
+    // No usable health service: settle for the port accepting connections
+    tcp_probe(&address, timeout).await
+}
+
+async fn tcp_probe(address: &str, timeout: Duration) -> bool {
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(address)).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            println!("🔍 [HEALTH] Service {} connection failed: {}", address, e);
+            false
+        }
+        Err(_) => {
+            println!("🔍 [HEALTH] Service {} connection timeout", address);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    async fn serve(router: tonic::transport::server::Router) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+        port
+    }
+
+    #[tokio::test]
+    async fn test_probe_uses_health_service_then_tcp() {
+        let client = DynamicGrpcClient::new(&[]).unwrap();
+
+        let (mut reporter, health_service) = tonic_health::server::health_reporter();
+        reporter.set_service_status("dividend", tonic_health::ServingStatus::NotServing).await;
+        let with_health = serve(tonic::transport::Server::builder().add_service(health_service)).await;
+        assert!(!probe_instance(&client, "dividend", "127.0.0.1", with_health, TIMEOUT).await);
+        // Unknown names fall back to the server-wide status
+        assert!(probe_instance(&client, "other", "127.0.0.1", with_health, TIMEOUT).await);
+
+        let reflection = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(tonic_reflection::pb::v1::FILE_DESCRIPTOR_SET)
+            .build_v1()
+            .unwrap();
+        let without_health = serve(tonic::transport::Server::builder().add_service(reflection)).await;
+        assert!(probe_instance(&client, "dividend", "127.0.0.1", without_health, TIMEOUT).await);
+
+        assert!(!probe_instance(&client, "dividend", "127.0.0.1", 1, TIMEOUT).await);
+    }
+
+    #[test]
+    fn test_thresholds_and_metadata_overrides() {
+        let metadata = HashMap::from([
+            ("hub.probe_failure_threshold".to_string(), "2".to_string()),
+            ("hub.probe_success_threshold".to_string(), "2".to_string()),
+            ("hub.heartbeat_ttl_ms".to_string(), "bogus".to_string()),
+        ]);
+        let config = HealthConfig::default().for_service(&metadata);
+        assert_eq!(config.heartbeat_ttl, Duration::from_secs(10));
+
+        let mut state = ProbeState::default();
+        assert_eq!(state.record(false, &config), None);
+        assert_eq!(state.record(true, &config), None);
+        assert_eq!(state.record(false, &config), None);
+        assert_eq!(state.record(false, &config), Some(false));
+        assert_eq!(state.record(false, &config), None);
+        assert_eq!(state.record(true, &config), None);
+        assert_eq!(state.record(true, &config), Some(true));
+    }
+}
@@ -0,0 +1,385 @@
+//! Rate and concurrency limits on calls routed through the hub.
+//!
+//! Limits can be set per target service name, per calling service (`caller_service` on
+//! `ServiceCallRequest` or in the `/api/grpc-call` and `/api/grpc-stream` bodies, or the
+//! `x-hub-caller` header on proxied calls) and per instance. Each is a token bucket
+//! (`rate_per_sec` calls a second, in bursts of up to `burst`) plus a cap on calls in flight
+//! (`max_in_flight`):
+//!
+//! ```json
+//! {
+//!   "limits": {
+//!     "services": { "dividend-service": { "rate_per_sec": 50, "burst": 10, "max_in_flight": 8 } },
+//!     "callers": { "*": { "rate_per_sec": 20 }, "batch-job": { "rate_per_sec": 2 } },
+//!     "instances": { "max_in_flight": 4 }
+//!   }
+//! }
+//! ```
+//!
+//! `*` gives every service or caller without an entry of its own the same limit, counted
+//! separately for each. Idle ones are forgotten once many are tracked; past that, the rest
+//! share a single `*` count. Instances can override `instances` with `hub.rate_per_sec`,
+//! `hub.rate_burst` and `hub.max_in_flight` registration metadata. An instance's `max_in_flight`
+//! is how many calls the hub routes to it at once (one when unset); it shows as `busy` once
+//! they are all taken.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tonic::Code;
+
+use crate::grpc_client::GrpcCallError;
+
+/// What a rejected call is told to wait when only a slot in flight would let it through
+const IN_FLIGHT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Services and callers tracked before idle ones are forgotten
+const MAX_TRACKED: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limit {
+    /// Calls a second; 0 means no rate limit
+    pub rate_per_sec: f64,
+    /// Calls that may arrive at once; defaults to one second's worth
+    pub burst: u32,
+    /// Calls in flight at once; 0 means no limit
+    pub max_in_flight: u32,
+}
+
+impl Limit {
+    pub fn is_unlimited(&self) -> bool {
+        self.rate_per_sec <= 0.0 && self.max_in_flight == 0
+    }
+
+    fn capacity(&self) -> f64 {
+        if self.burst > 0 {
+            self.burst as f64
+        } else {
+            self.rate_per_sec.ceil().max(1.0)
+        }
+    }
+
+    /// This limit with an instance's `hub.rate_per_sec`, `hub.rate_burst` and `hub.max_in_flight`
+    /// metadata applied. Invalid values are ignored.
+    pub fn for_instance(&self, metadata: &HashMap<String, String>) -> Self {
+        let number = |key: &str| metadata.get(key).and_then(|v| v.parse::<f64>().ok()).filter(|n| n.is_finite() && *n >= 0.0);
+        Self {
+            rate_per_sec: number("hub.rate_per_sec").unwrap_or(self.rate_per_sec),
+            burst: number("hub.rate_burst").map_or(self.burst, |n| n as u32),
+            max_in_flight: number("hub.max_in_flight").map_or(self.max_in_flight, |n| n as u32),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.rate_per_sec.is_finite() && self.rate_per_sec >= 0.0 {
+            Ok(())
+        } else {
+            Err(format!("Invalid rate_per_sec {}", self.rate_per_sec))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Per target service name, or "*" for every other service
+    pub services: HashMap<String, Limit>,
+    /// Per `caller_service`, or "*" for every other caller
+    pub callers: HashMap<String, Limit>,
+    /// For every instance
+    pub instances: Limit,
+}
+
+impl LimitsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, limit) in self.services.iter().chain(&self.callers) {
+            limit.validate().map_err(|e| format!("Invalid limit for '{}': {}", name, e))?;
+        }
+        self.instances.validate().map_err(|e| format!("Invalid instance limit: {}", e))
+    }
+}
+
+fn limit_for(limits: &HashMap<String, Limit>, name: &str) -> Option<Limit> {
+    limits.get(name).or_else(|| limits.get("*")).copied().filter(|limit| !limit.is_unlimited())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Scope {
+    Service(String),
+    Caller(String),
+    Instance(String),
+}
+
+impl Scope {
+    fn describe(&self) -> String {
+        match self {
+            Self::Service(name) => format!("service '{}'", name),
+            Self::Caller(name) => format!("caller '{}'", name),
+            Self::Instance(id) => format!("instance {}", id),
+        }
+    }
+}
+
+/// The token bucket and calls in flight of one service, caller or instance
+#[derive(Debug)]
+struct Usage {
+    tokens: f64,
+    refilled: Instant,
+    in_flight: u32,
+}
+
+impl Usage {
+    fn new(limit: &Limit) -> Self {
+        Self {
+            tokens: limit.capacity(),
+            refilled: Instant::now(),
+            in_flight: 0,
+        }
+    }
+
+    fn refill(&mut self, limit: &Limit) {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.refilled).as_secs_f64() * limit.rate_per_sec).min(limit.capacity());
+        self.refilled = now;
+    }
+
+    /// How long until one more call fits, or `None` if it fits now
+    fn wait(&self, limit: &Limit, in_flight: u32) -> Option<Duration> {
+        if limit.max_in_flight > 0 && in_flight >= limit.max_in_flight {
+            return Some(IN_FLIGHT_RETRY_AFTER);
+        }
+        if limit.rate_per_sec > 0.0 && self.tokens < 1.0 {
+            return Some(Duration::from_secs_f64((1.0 - self.tokens) / limit.rate_per_sec));
+        }
+        None
+    }
+
+    fn take(&mut self, limit: &Limit) {
+        if limit.rate_per_sec > 0.0 {
+            self.tokens -= 1.0;
+        }
+    }
+}
+
+/// Counts calls against the configured limits
+#[derive(Debug)]
+pub struct Limiter {
+    config: LimitsConfig,
+    usage: Mutex<HashMap<Scope, Usage>>,
+    max_tracked: usize,
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self::new(LimitsConfig::default())
+    }
+}
+
+impl Limiter {
+    pub fn new(config: LimitsConfig) -> Self {
+        Self {
+            config,
+            usage: Mutex::new(HashMap::new()),
+            max_tracked: MAX_TRACKED,
+        }
+    }
+
+    /// The limit of an instance registered with `metadata`
+    pub fn instance_limit(&self, metadata: &HashMap<String, String>) -> Limit {
+        self.config.instances.for_instance(metadata)
+    }
+
+    /// Let a call from `caller` to `service_name` in, holding its place in flight until the permit
+    /// is dropped, or reject it with RESOURCE_EXHAUSTED and how long to wait
+    pub fn admit(self: &Arc<Self>, caller: &str, service_name: &str) -> Result<Permit, GrpcCallError> {
+        let mut usage = self.usage.lock().unwrap();
+        if usage.len() >= self.max_tracked {
+            self.forget_idle(&mut usage);
+        }
+        let scopes: Vec<(Scope, Limit)> = [
+            self.scope_for(&usage, &self.config.callers, caller, Scope::Caller),
+            self.scope_for(&usage, &self.config.services, service_name, Scope::Service),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        for (scope, limit) in &scopes {
+            let entry = usage.entry(scope.clone()).or_insert_with(|| Usage::new(limit));
+            entry.refill(limit);
+            if let Some(wait) = entry.wait(limit, entry.in_flight) {
+                return Err(rejection(&format!("Call limit of {} reached", scope.describe()), wait));
+            }
+        }
+        for (scope, limit) in &scopes {
+            let entry = usage.get_mut(scope).unwrap();
+            entry.take(limit);
+            entry.in_flight += 1;
+        }
+
+        Ok(Permit {
+            limiter: self.clone(),
+            scopes: scopes.into_iter().map(|(scope, _)| scope).collect(),
+        })
+    }
+
+    /// Where calls to or from `name` are counted, if `limits` has a limit for it. Past `max_tracked`,
+    /// names only `*` covers share its count instead of adding one of their own.
+    fn scope_for(
+        &self,
+        usage: &HashMap<Scope, Usage>,
+        limits: &HashMap<String, Limit>,
+        name: &str,
+        scope: fn(String) -> Scope,
+    ) -> Option<(Scope, Limit)> {
+        let limit = limit_for(limits, name)?;
+        let own = scope(name.to_string());
+        if usage.len() >= self.max_tracked && !limits.contains_key(name) && !usage.contains_key(&own) {
+            return Some((scope("*".to_string()), limit));
+        }
+        Some((own, limit))
+    }
+
+    /// Drop the services and callers with nothing in flight and a full bucket, which a fresh count
+    /// would match
+    fn forget_idle(&self, usage: &mut HashMap<Scope, Usage>) {
+        usage.retain(|scope, entry| {
+            let limit = match scope {
+                Scope::Service(name) => limit_for(&self.config.services, name),
+                Scope::Caller(name) => limit_for(&self.config.callers, name),
+                // Dropped when the instance is removed
+                Scope::Instance(_) => return true,
+            };
+            let Some(limit) = limit else {
+                return false;
+            };
+            entry.refill(&limit);
+            entry.in_flight > 0 || entry.tokens < limit.capacity()
+        });
+    }
+
+    /// How long until the instance's rate lets one more call through, or `None` if it does now.
+    /// Its `max_in_flight` is enforced by the hub, which marks it busy when full.
+    pub fn instance_wait(&self, service_id: &str, limit: &Limit) -> Option<Duration> {
+        if limit.rate_per_sec <= 0.0 {
+            return None;
+        }
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage.entry(Scope::Instance(service_id.to_string())).or_insert_with(|| Usage::new(limit));
+        entry.refill(limit);
+        entry.wait(limit, 0)
+    }
+
+    /// Count a call given to an instance against its rate
+    pub fn take_instance(&self, service_id: &str, limit: &Limit) {
+        if let Some(entry) = self.usage.lock().unwrap().get_mut(&Scope::Instance(service_id.to_string())) {
+            entry.take(limit);
+        }
+    }
+
+    pub fn forget_instance(&self, service_id: &str) {
+        self.usage.lock().unwrap().remove(&Scope::Instance(service_id.to_string()));
+    }
+}
+
+/// A call's place in flight with its caller and service limits; dropping it frees the place
+#[derive(Debug)]
+pub struct Permit {
+    limiter: Arc<Limiter>,
+    scopes: Vec<Scope>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut usage = self.limiter.usage.lock().unwrap();
+        for scope in &self.scopes {
+            if let Some(entry) = usage.get_mut(scope) {
+                entry.in_flight = entry.in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// RESOURCE_EXHAUSTED for a call over a limit, with `retry-after` (whole seconds, as in HTTP)
+/// and `retry-after-ms` metadata
+pub fn rejection(reason: &str, retry_after: Duration) -> GrpcCallError {
+    let millis = retry_after.as_millis().max(1);
+    let mut error = GrpcCallError::new(Code::ResourceExhausted, format!("{}; retry after {} ms", reason, millis));
+    error.metadata.insert("retry-after".to_string(), millis.div_ceil(1000).to_string());
+    error.metadata.insert("retry-after-ms".to_string(), millis.to_string());
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(config: &str) -> Arc<Limiter> {
+        Arc::new(Limiter::new(serde_json::from_str(config).unwrap()))
+    }
+
+    #[test]
+    fn test_service_in_flight_limit_applies_to_every_caller() {
+        let limiter = limiter(r#"{"services": {"dividend": {"max_in_flight": 1}}}"#);
+        let first = limiter.admit("batch-job", "dividend").unwrap();
+        let busy = limiter.admit("web", "dividend").unwrap_err();
+        assert_eq!(busy.code, Code::ResourceExhausted);
+        assert_eq!(busy.metadata["retry-after"], "1");
+
+        drop(first);
+        assert!(limiter.admit("web", "dividend").is_ok());
+    }
+
+    #[test]
+    fn test_each_caller_gets_its_own_rate_bucket() {
+        let limiter = limiter(r#"{"callers": {"*": {"rate_per_sec": 1, "burst": 2}}}"#);
+        limiter.admit("batch-job", "dividend").unwrap();
+        limiter.admit("batch-job", "dividend").unwrap();
+        let limited = limiter.admit("batch-job", "dividend").unwrap_err();
+        assert!(limited.message.contains("caller 'batch-job'"));
+        assert!(limited.metadata["retry-after-ms"].parse::<u64>().unwrap() <= 1000);
+
+        assert!(limiter.admit("web", "dividend").is_ok());
+    }
+
+    #[test]
+    fn test_instance_metadata_overrides_the_instance_limit() {
+        let limiter = limiter("{}");
+        let limit = Limit { rate_per_sec: 0.0, burst: 0, max_in_flight: 2 }.for_instance(&HashMap::from([
+            ("hub.max_in_flight".to_string(), "1".to_string()),
+            ("hub.rate_per_sec".to_string(), "1".to_string()),
+        ]));
+        assert_eq!(limit, Limit { rate_per_sec: 1.0, burst: 0, max_in_flight: 1 });
+        assert!(limiter.instance_wait("a", &limit).is_none());
+        limiter.take_instance("a", &limit);
+        assert!(limiter.instance_wait("a", &limit).is_some());
+    }
+
+    fn tracking(max_tracked: usize) -> Arc<Limiter> {
+        let config = serde_json::from_str(r#"{"callers": {"*": {"max_in_flight": 1}}}"#).unwrap();
+        Arc::new(Limiter { max_tracked, ..Limiter::new(config) })
+    }
+
+    #[test]
+    fn test_idle_callers_are_forgotten() {
+        let limiter = tracking(2);
+        drop(limiter.admit("a", "dividend").unwrap());
+        drop(limiter.admit("b", "dividend").unwrap());
+        let _in_flight = limiter.admit("c", "dividend").unwrap();
+        assert_eq!(limiter.usage.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_callers_past_the_cap_share_one_count() {
+        let limiter = tracking(2);
+        let _a = limiter.admit("a", "dividend").unwrap();
+        let _b = limiter.admit("b", "dividend").unwrap();
+        let _c = limiter.admit("c", "dividend").unwrap();
+        let rejected = limiter.admit("d", "dividend").unwrap_err();
+        assert!(rejected.message.contains("caller '*'"));
+        assert_eq!(limiter.usage.lock().unwrap().len(), 3);
+    }
+}
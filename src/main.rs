@@ -1,16 +1,55 @@
 use clap::Parser;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::RwLock;
 use tokio_stream::wrappers::ReceiverStream;
-use futures_util::StreamExt;
+use tokio_stream::StreamExt;
 use tonic::{transport::Server, Request, Response, Status};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use tokio::process::Command;
 use http_body_util::BodyExt;
 
+mod auth;
+mod balancer;
+mod breaker;
+mod cluster;
+mod config;
+mod dispatch;
+mod grpc_client;
+mod health;
+mod limits;
+mod metrics;
+mod namespace;
+mod proxy;
+mod queue;
+mod rbac;
+mod retry;
+mod selector;
+mod storage;
+mod streaming;
+mod tls;
+mod versions;
+
+use auth::{AuthConfig, AuthInterceptor, Authenticator, Identity};
+use balancer::{instance_weight, Balancers, Candidate, Route};
+use breaker::{BreakerConfig, CircuitBreakers, Transition};
+use cluster::{Cluster, ClusterConfig, ClusterSnapshot, ReplicationUpdate, ServiceRecord};
+use config::HubConfig;
+use dispatch::{dispatch, DispatchCall, DispatchMode};
+use grpc_client::{call_deadline, CallOptions, DynamicGrpcClient, GrpcCallError, GrpcCallResult};
+use health::{HealthConfig, ProbeState};
+use limits::{Limiter, LimitsConfig};
+use metrics::{CallApi, Metrics};
+use namespace::{qualified_name, Namespaces, DEFAULT_NAMESPACE};
+use proxy::GrpcProxy;
+use queue::{CallQueue, QueueConfig};
+use rbac::{Action, Authorizer, RbacConfig, Role};
+use retry::RetryConfig;
+use selector::LabelSelector;
+use storage::{FileRegistryStore, RegistryStore, StoredService};
+use streaming::{relay_stream, RelayEvent, StreamTarget};
+use tls::{BackendTls, TlsListener};
+use versions::{split_target, SplitEntry, TrafficSplits};
 
 mod grpc_hub {
     tonic::include_proto!("grpc_hub");
@@ -37,54 +76,71 @@ struct Args {
     /// gRPC server host
     #[arg(long, default_value = "0.0.0.0")]
     grpc_host: String,
-}
-
-// grpcurl-based gRPC calling functions
-async fn call_grpc_method(
-    host: &str,
-    port: u16,
-    service: &str,
-    method: &str,
-    input: serde_json::Value,
-) -> Result<serde_json::Value, anyhow::Error> {
-    let address = format!("{}:{}", host, port);
-    let full_method = format!("{}/{}", service, method);
     
-    println!("🔍 [DEBUG] Hub: Starting gRPC call to {} at {}", full_method, address);
+    /// Protobuf descriptor set files used to call services without server reflection (repeatable)
+    #[arg(long = "descriptor-set")]
+    descriptor_sets: Vec<std::path::PathBuf>,
     
-    // Convert input to JSON string
-    let input_json = serde_json::to_string(&input)?;
-    println!("🔍 [DEBUG] Hub: Input JSON: {}", input_json);
+    /// Most calls that may wait for a busy service before new ones are rejected
+    #[arg(long, default_value = "100")]
+    queue_max_depth: usize,
     
-    // Call grpcurl with timeout
-    println!("🔍 [DEBUG] Hub: Executing grpcurl command with timeout");
-    let output = tokio::time::timeout(
-        std::time::Duration::from_secs(10), // 10 second timeout
-        tokio::process::Command::new("grpcurl")
-            .arg("-plaintext")
-            .arg("-d")
-            .arg(&input_json)
-            .arg(&address)
-            .arg(&full_method)
-            .output()
-    ).await??;
+    /// How long a queued call waits for a free instance, in milliseconds
+    #[arg(long, default_value = "30000")]
+    queue_max_wait_ms: u64,
     
-    println!("🔍 [DEBUG] Hub: grpcurl command completed with status: {}", output.status);
+    /// JSON file the registry is persisted to and reloaded from on startup
+    #[arg(long)]
+    registry_file: Option<std::path::PathBuf>,
     
-    if !output.status.success() {
-        let error = String::from_utf8_lossy(&output.stderr);
-        println!("❌ [DEBUG] Hub: gRPC call failed: {}", error);
-        return Err(anyhow::anyhow!("gRPC call failed: {}", error));
-    }
+    /// HTTP URL of another hub in the cluster, e.g. http://10.0.0.2:8080 (repeatable)
+    #[arg(long = "peer")]
+    peers: Vec<String>,
     
-    let result = String::from_utf8_lossy(&output.stdout).to_string();
-    println!("🔍 [DEBUG] Hub: gRPC call successful, result: {}", result);
+    /// Name of this hub in the cluster (defaults to a random ID)
+    #[arg(long)]
+    node_id: Option<String>,
     
-    // Try to parse as JSON, if it fails return as string
-    match serde_json::from_str::<serde_json::Value>(&result) {
-        Ok(json) => Ok(json),
-        Err(_) => Ok(serde_json::Value::String(result)),
-    }
+    /// How often to pull the registry from every peer, in seconds
+    #[arg(long, default_value = "10")]
+    cluster_sync_interval_secs: u64,
+    
+    /// API key or bearer token this hub presents to its peers (required with --peer)
+    #[arg(long)]
+    peer_token: Option<String>,
+    
+    /// JSON hub configuration file: balancing, splits, namespaces, retries, breakers, limits,
+    /// auth, RBAC and TLS (see the `config` module)
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+    
+    /// How long a service may go without a heartbeat before it is marked offline, in milliseconds
+    #[arg(long, default_value = "10000")]
+    heartbeat_ttl_ms: u64,
+    
+    /// How long a service stays listed after it went offline for missing heartbeats, in milliseconds
+    #[arg(long, default_value = "300000")]
+    eviction_grace_ms: u64,
+    
+    /// How often heartbeats are checked and due probes are started, in milliseconds
+    #[arg(long, default_value = "1000")]
+    sweep_interval_ms: u64,
+    
+    /// How often each service is probed, in milliseconds
+    #[arg(long, default_value = "5000")]
+    probe_interval_ms: u64,
+    
+    /// How long a probe may take before it counts as failed, in milliseconds
+    #[arg(long, default_value = "2000")]
+    probe_timeout_ms: u64,
+    
+    /// Failed probes in a row before a service is marked offline
+    #[arg(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..))]
+    probe_failure_threshold: u32,
+    
+    /// Passed probes in a row before a service taken offline by probes comes back
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    probe_success_threshold: u32,
 }
 
 use grpc_hub::grpc_hub_server::{GrpcHub, GrpcHubServer};
@@ -107,9 +163,18 @@ struct Cli {
     http_port: u16,
 }
 
+/// Extract the registered service name from a full gRPC service name
+/// (e.g., "web_content_extract.WebContentExtract" -> "web-content-extract")
+fn short_service_name(grpc_service: &str) -> String {
+    grpc_service.split('.').next().unwrap_or(grpc_service)
+        .replace("_", "-")
+        .to_lowercase()
+}
+
 #[derive(Debug, Clone)]
 struct ServiceInfo {
     service_id: String,
+    namespace: String,
     service_name: String,
     service_version: String,
     service_address: String,
@@ -118,7 +183,69 @@ struct ServiceInfo {
     metadata: HashMap<String, String>,
     registered_at: DateTime<Utc>,
     last_heartbeat: DateTime<Utc>,
-    status: String, // "online", "offline", or "busy"
+    status: String, // "online", "offline", "busy", or "recovering" (reloaded from storage, awaiting a heartbeat)
+    instance_secret: String, // Issued at registration; only its holder may heartbeat, update or unregister the instance
+    updated_at: DateTime<Utc>, // Last replicated change; the newer copy wins between cluster nodes
+    busy_with_calls: bool, // Busy only because this hub's calls fill it, which peers don't see
+}
+
+impl From<&ServiceInfo> for ServiceRecord {
+    fn from(info: &ServiceInfo) -> Self {
+        // Peers route their own calls, so an instance this hub fills is online to them
+        let status = if info.busy_with_calls && info.status == "busy" { "online" } else { &info.status };
+        ServiceRecord {
+            service: info.into(),
+            status: status.to_string(),
+            last_heartbeat: info.last_heartbeat,
+            updated_at: info.updated_at,
+        }
+    }
+}
+
+impl From<ServiceRecord> for ServiceInfo {
+    fn from(record: ServiceRecord) -> Self {
+        ServiceInfo {
+            service_id: record.service.service_id,
+            namespace: record.service.namespace,
+            service_name: record.service.service_name,
+            service_version: record.service.service_version,
+            service_address: record.service.service_address,
+            service_port: record.service.service_port,
+            methods: record.service.methods,
+            metadata: record.service.metadata,
+            registered_at: record.service.registered_at,
+            last_heartbeat: record.last_heartbeat,
+            status: record.status,
+            instance_secret: record.service.instance_secret,
+            updated_at: record.updated_at,
+            busy_with_calls: false,
+        }
+    }
+}
+
+impl From<&ServiceInfo> for StoredService {
+    fn from(info: &ServiceInfo) -> Self {
+        StoredService {
+            service_id: info.service_id.clone(),
+            namespace: info.namespace.clone(),
+            service_name: info.service_name.clone(),
+            service_version: info.service_version.clone(),
+            service_address: info.service_address.clone(),
+            service_port: info.service_port.clone(),
+            methods: info.methods.clone(),
+            metadata: info.metadata.clone(),
+            registered_at: info.registered_at,
+            instance_secret: info.instance_secret.clone(),
+        }
+    }
+}
+
+impl ServiceInfo {
+    /// Whether `secret` is the instance's secret; instances restored from a registry that
+    /// predates secrets have none, and accept any
+    fn has_secret(&self, secret: &str) -> bool {
+        self.instance_secret.is_empty() || auth::constant_time_eq(self.instance_secret.as_bytes(), secret.as_bytes())
+    }
 }
 
 impl From<ServiceInfo> for grpc_hub::ServiceInfo {
@@ -134,6 +261,8 @@ impl From<ServiceInfo> for grpc_hub::ServiceInfo {
             registered_at: info.registered_at.to_rfc3339(),
             last_heartbeat: info.last_heartbeat.to_rfc3339(),
             status: info.status, // Use actual status from the service
+            namespace: info.namespace,
+            circuit_state: String::new(),
         }
     }
 }
@@ -142,7 +271,95 @@ impl From<ServiceInfo> for grpc_hub::ServiceInfo {
 struct GrpcHubService {
     services: Arc<RwLock<HashMap<String, ServiceInfo>>>,
     event_senders: Arc<RwLock<Vec<tokio::sync::broadcast::Sender<SSEEvent>>>>,
-    service_counters: Arc<RwLock<HashMap<String, AtomicU64>>>, // Round-robin counters per service name
+    balancers: Arc<Balancers>, // Load-balancing strategy and tracked load per service name
+    grpc_client: Arc<DynamicGrpcClient>, // Descriptor cache and channel pool for routed calls
+    call_queue: Arc<CallQueue<AcquiredInstance, CallRouting>>, // Calls waiting for a busy service, per service name
+    traffic_splits: Arc<TrafficSplits>, // Share of each service's calls per version
+    namespaces: Arc<Namespaces>, // Services each namespace exports to the others
+    retries: Arc<RetryConfig>, // Retry policy per service name
+    breakers: Arc<CircuitBreakers>, // Circuit breaker per service ID
+    limits: Arc<Limiter>, // Rate and in-flight limits per service, caller and instance
+    auth: Arc<Authenticator>, // API keys and bearer tokens callers must present, if configured
+    rbac: Arc<Authorizer>, // Roles each caller has, if configured
+    store: Option<Arc<dyn RegistryStore>>, // Durable copy of the registry, if configured
+    cluster: Option<Arc<Cluster>>, // Peers the registry is replicated to, if clustered
+    health_config: HealthConfig, // Heartbeat TTL and probe settings, before per-service overrides
+    probe_states: Arc<RwLock<HashMap<String, ProbeState>>>, // Recent probe results per service ID
+    metrics: Arc<Metrics>, // Call and probe counts served at /metrics
+}
+
+/// Outcome of picking an instance for a service name
+#[derive(Debug)]
+enum Selection {
+    /// (service_id, host, port) of the instance to use
+    Instance(String, String, u16),
+    /// Every instance that isn't offline is busy
+    AllBusy,
+    /// Every free instance is at its limit; one frees up after the duration at the latest
+    Limited(std::time::Duration),
+    NotFound,
+}
+
+/// The target of a call, `[namespace/]service[@range]`, resolved from the caller's namespace
+#[derive(Debug)]
+struct ResolvedTarget {
+    namespace: String,
+    /// Full gRPC service name, e.g. "dividend.DividendService"
+    grpc_service: String,
+    /// Registered service name, e.g. "dividend"
+    service_name: String,
+    version: Option<semver::VersionReq>,
+}
+
+/// How one call picks among the instances of its target service
+#[derive(Debug, Clone)]
+struct CallRouting {
+    /// Only instances registered in this namespace
+    namespace: String,
+    /// Place in the queue while every instance is busy (higher first)
+    priority: i32,
+    /// Consistent-hash key, for services balanced that way
+    hash_key: Option<String>,
+    /// Only instances whose version satisfies this range
+    version: Option<semver::VersionReq>,
+    /// Only instances whose metadata matches this selector
+    selector: Option<LabelSelector>,
+    /// When the caller stops waiting; queueing past it fails the call
+    deadline: Option<tokio::time::Instant>,
+    /// Instances this call already failed on
+    excluded: HashSet<String>,
+}
+
+impl Default for CallRouting {
+    fn default() -> Self {
+        Self {
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            priority: 0,
+            hash_key: None,
+            version: None,
+            selector: None,
+            deadline: None,
+            excluded: HashSet::new(),
+        }
+    }
+}
+
+impl CallRouting {
+    /// Whether `service` may serve the call
+    fn accepts(&self, service: &ServiceInfo) -> bool {
+        service.namespace == self.namespace
+            && !self.excluded.contains(&service.service_id)
+            && self.version.as_ref().is_none_or(|range| versions::version_matches(&service.service_version, range))
+            && self.selector.as_ref().is_none_or(|selector| selector.matches(&service.metadata))
+    }
+}
+
+/// A place on an instance reserved for a single call, held until `guard` is released
+#[derive(Debug)]
+struct AcquiredInstance {
+    guard: BusyGuard,
+    host: String,
+    port: u16,
 }
 
 #[derive(Debug, Clone)]
@@ -151,19 +368,447 @@ struct SSEEvent {
     data: String,
 }
 
-impl Default for GrpcHubService {
-    fn default() -> Self {
+impl GrpcHubService {
+    fn new(grpc_client: DynamicGrpcClient) -> Self {
         Self {
             services: Arc::new(RwLock::new(HashMap::new())),
             event_senders: Arc::new(RwLock::new(Vec::new())),
-            service_counters: Arc::new(RwLock::new(HashMap::new())),
+            balancers: Arc::new(Balancers::default()),
+            grpc_client: Arc::new(grpc_client),
+            call_queue: Arc::new(CallQueue::new(QueueConfig::default())),
+            traffic_splits: Arc::new(TrafficSplits::default()),
+            namespaces: Arc::new(Namespaces::default()),
+            retries: Arc::new(RetryConfig::default()),
+            breakers: Arc::new(CircuitBreakers::default()),
+            limits: Arc::new(Limiter::default()),
+            auth: Arc::new(Authenticator::default()),
+            rbac: Arc::new(Authorizer::default()),
+            store: None,
+            cluster: None,
+            health_config: HealthConfig::default(),
+            probe_states: Arc::new(RwLock::new(HashMap::new())),
+            metrics: Arc::new(Metrics::default()),
         }
     }
-}
 
-impl GrpcHubService {
-    async fn broadcast_event(&self, event: SSEEvent) {
-        let senders = self.event_senders.read().await;
+    /// Balance calls with the strategies in `config`
+    fn with_balancers(mut self, config: balancer::BalancerConfig) -> Self {
+        self.balancers = Arc::new(Balancers::new(config));
+        self
+    }
+
+    /// Split traffic between versions as in `splits`, until changed through the HTTP API
+    fn with_traffic_splits(mut self, splits: HashMap<String, Vec<SplitEntry>>) -> Self {
+        self.traffic_splits = Arc::new(TrafficSplits::new(splits));
+        self
+    }
+
+    /// Serve the namespaces in `config`, with their export lists
+    fn with_namespaces(mut self, config: HashMap<String, namespace::NamespaceConfig>) -> Self {
+        self.namespaces = Arc::new(Namespaces::new(config));
+        self
+    }
+
+    /// Retry failed calls as `config` sets out per service name
+    fn with_retries(mut self, config: RetryConfig) -> Self {
+        self.retries = Arc::new(config);
+        self
+    }
+
+    /// Open instances' circuits after repeated failures as `config` sets out
+    fn with_circuit_breakers(mut self, config: BreakerConfig) -> Self {
+        self.breakers = Arc::new(CircuitBreakers::new(config));
+        self
+    }
+
+    /// Reject calls over the rate and in-flight limits in `config`
+    fn with_limits(mut self, config: LimitsConfig) -> Self {
+        self.limits = Arc::new(Limiter::new(config));
+        self
+    }
+
+    /// Require callers of the gRPC and HTTP APIs to present credentials from `config`
+    fn with_auth(mut self, config: AuthConfig) -> Self {
+        self.auth = Arc::new(Authenticator::new(config));
+        self
+    }
+
+    /// Only let callers do what the roles in `config` allow
+    fn with_rbac(mut self, config: RbacConfig) -> Self {
+        self.rbac = Arc::new(Authorizer::new(config));
+        self
+    }
+
+    /// Check `action` against the roles of the caller `request` authenticated as
+    fn authorize<T>(&self, request: &Request<T>, action: Action) -> Result<(), GrpcCallError> {
+        self.rbac.authorize(request.extensions().get::<Identity>(), action)
+    }
+
+    /// The service name of a registered instance
+    async fn instance_service_name(&self, service_id: &str) -> Option<String> {
+        self.services.read().await.get(service_id).map(|service| service.service_name.clone())
+    }
+
+    /// The namespace and service name of a registered instance, which its grants are scoped to
+    async fn instance_scope(&self, service_id: &str) -> Option<(String, String)> {
+        self.services.read().await.get(service_id).map(|service| (service.namespace.clone(), service.service_name.clone()))
+    }
+
+    /// Whether `secret` is the one the instance was issued at registration; PERMISSION_DENIED
+    /// if not. Operators need no secret, and unknown instances pass, so callers still learn
+    /// they are not found.
+    async fn verify_instance_secret(&self, identity: Option<&Identity>, service_id: &str, secret: &str) -> Result<(), GrpcCallError> {
+        if self.rbac.holds(identity, Role::Operator) {
+            return Ok(());
+        }
+        let services = self.services.read().await;
+        match services.get(service_id) {
+            Some(service) if !service.has_secret(secret) => {
+                println!("🔒 [AUTH] Rejected a request for instance {} without its secret", service_id);
+                Err(GrpcCallError::new(tonic::Code::PermissionDenied, format!("Invalid instance secret for service {}", service_id)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// How long until `service` may take one more call under its instance rate limit, or `None` if it may now
+    fn instance_wait(&self, service: &ServiceInfo) -> Option<std::time::Duration> {
+        let limit = self.limits.instance_limit(&service.metadata);
+        self.limits.instance_wait(&service.service_id, &limit)
+    }
+
+    /// How many hub-routed calls `service` takes at once: its instance `max_in_flight`, or one
+    fn instance_capacity(&self, service: &ServiceInfo) -> u32 {
+        self.limits.instance_limit(&service.metadata).max_in_flight.max(1)
+    }
+
+    /// Whether `service` can take one more call on top of the ones the hub has in flight to it
+    fn has_room(&self, service: &ServiceInfo) -> bool {
+        self.balancers.load(&service.service_id).outstanding < self.instance_capacity(service)
+    }
+
+    /// Count a routed call for `/metrics`; services nothing is registered as count as "unknown",
+    /// so callers can't add series by naming services that don't exist
+    async fn record_call_metrics<T>(&self, api: CallApi, service_name: &str, method: &str, result: &Result<T, GrpcCallError>, elapsed: std::time::Duration) {
+        let registered = self.services.read().await.values().any(|service| service.service_name == service_name);
+        let service_name = if registered { service_name } else { metrics::UNKNOWN };
+        self.metrics.record_call(api, service_name, method, result, elapsed);
+    }
+
+    /// Count a call given to `service` against its instance rate limit
+    fn take_instance(&self, service: &ServiceInfo) {
+        self.limits.take_instance(&service.service_id, &self.limits.instance_limit(&service.metadata));
+    }
+
+    /// An instance as listed to clients, with its circuit state
+    fn service_info(&self, service: &ServiceInfo) -> grpc_hub::ServiceInfo {
+        grpc_hub::ServiceInfo {
+            circuit_state: self.breakers.state(&service.service_id).to_string(),
+            ..service.clone().into()
+        }
+    }
+
+    /// Feed the outcome of a call on `service_id` to its circuit breaker
+    async fn record_call_outcome(&self, service_id: &str, result: &Result<GrpcCallResult, GrpcCallError>) {
+        let failed = result.as_ref().err().is_some_and(breaker::is_failure);
+        if let Some(transition) = self.breakers.record(service_id, failed) {
+            self.broadcast_circuit_change(service_id, transition).await;
+        }
+    }
+
+    /// Let one trial call through to every instance whose circuit has been open long enough
+    async fn half_open_expired_circuits(&self) {
+        for (service_id, transition) in self.breakers.half_open_expired() {
+            self.broadcast_circuit_change(&service_id, transition).await;
+            self.hand_off_to_queued_call(&service_id).await;
+        }
+    }
+
+    async fn broadcast_circuit_change(&self, service_id: &str, (from, to): Transition) {
+        let service_name = self.services.read().await.get(service_id).map(|s| s.service_name.clone()).unwrap_or_default();
+        println!("⚡ [CIRCUIT] Circuit of {} (ID: {}) {} -> {}", service_name, service_id, from, to);
+        self.broadcast_event(SSEEvent {
+            event_type: "circuit_breaker".to_string(),
+            data: serde_json::json!({
+                "service_id": service_id,
+                "service_name": service_name,
+                "circuit_state": to,
+                "previous_circuit_state": from
+            }).to_string(),
+        }).await;
+    }
+
+    /// Resolve a call to `target` from a caller in `caller_namespace`; another namespace can
+    /// only be named for a service it exports
+    fn resolve_target(&self, caller_namespace: &str, target: &str) -> Result<ResolvedTarget, GrpcCallError> {
+        let (target_namespace, target) = namespace::split_namespace(target);
+        let (grpc_service, version) = split_target(target)?;
+        let service_name = short_service_name(grpc_service);
+        Ok(ResolvedTarget {
+            namespace: self.namespaces.resolve(caller_namespace, target_namespace, &service_name)?,
+            grpc_service: grpc_service.to_string(),
+            service_name,
+            version,
+        })
+    }
+
+    /// How a call to `target` picks its instance: its queue `priority`, the instances its namespace,
+    /// version range and label `selector` allow, and its consistent-hash key taken from `headers` or `input`
+    fn call_routing(
+        &self,
+        target: &ResolvedTarget,
+        priority: i32,
+        selector: Option<LabelSelector>,
+        headers: &HashMap<String, String>,
+        input: Option<&serde_json::Value>,
+    ) -> CallRouting {
+        CallRouting {
+            namespace: target.namespace.clone(),
+            priority,
+            hash_key: self.balancers.hash_key(&target.service_name, headers, input),
+            version: target.version.clone(),
+            selector,
+            deadline: None,
+            excluded: HashSet::new(),
+        }
+    }
+
+    /// Use `config` for heartbeat timeouts and health probes
+    fn with_health_config(mut self, config: HealthConfig) -> Self {
+        self.health_config = config;
+        self
+    }
+
+    /// Replicate the registry to (and from) the other hubs in `cluster`
+    fn with_cluster(mut self, cluster: Cluster) -> Self {
+        self.cluster = Some(Arc::new(cluster));
+        self
+    }
+
+    /// Persist registrations to `store`
+    fn with_store(mut self, store: Arc<dyn RegistryStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Reload the instances kept in the store; they stay `recovering` until they send a heartbeat
+    async fn restore_registry(&self) -> anyhow::Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        let stored = store.load()?;
+        let mut services = self.services.write().await;
+        for service in &stored {
+            services.insert(service.service_id.clone(), ServiceInfo {
+                service_id: service.service_id.clone(),
+                namespace: service.namespace.clone(),
+                service_name: service.service_name.clone(),
+                service_version: service.service_version.clone(),
+                service_address: service.service_address.clone(),
+                service_port: service.service_port.clone(),
+                methods: service.methods.clone(),
+                metadata: service.metadata.clone(),
+                registered_at: service.registered_at,
+                last_heartbeat: Utc::now(),
+                status: "recovering".to_string(),
+                instance_secret: service.instance_secret.clone(),
+                // Any copy a peer has is newer than this one
+                updated_at: DateTime::<Utc>::UNIX_EPOCH,
+                busy_with_calls: false,
+            });
+        }
+        Ok(stored.len())
+    }
+
+    /// Apply a registry change replicated from a peer, and publish its event to local subscribers
+    async fn apply_replication(&self, update: ReplicationUpdate) {
+        let Some(cluster) = &self.cluster else {
+            return;
+        };
+        if update.origin == cluster.node_id() {
+            return;
+        }
+        
+        match update.record {
+            Some(record) => {
+                let mut service = ServiceInfo::from(record);
+                let mut services = self.services.write().await;
+                if let Some(local) = services.get(&update.service_id) {
+                    // Last writer wins: a copy older than ours arrived late
+                    if service.updated_at <= local.updated_at {
+                        println!("🔗 [CLUSTER] Ignored a stale update of {} from {}", update.service_id, update.origin);
+                        return;
+                    }
+                }
+                // Calls this hub has in flight keep a full instance busy here
+                if service.status == "online" && !self.has_room(&service) {
+                    service.status = "busy".to_string();
+                    service.busy_with_calls = true;
+                }
+                self.persist_service(&service);
+                let came_online = service.status == "online";
+                services.insert(update.service_id.clone(), service);
+                drop(services);
+                if came_online {
+                    self.hand_off_to_queued_call(&update.service_id).await;
+                }
+            }
+            None => {
+                self.services.write().await.remove(&update.service_id);
+                cluster.add_tombstone(&update.service_id).await;
+                if let Some(store) = &self.store {
+                    if let Err(e) = store.remove(&update.service_id) {
+                        println!("⚠️  Failed to remove service {} from storage: {:#}", update.service_id, e);
+                    }
+                }
+            }
+        }
+        
+        self.publish_event(SSEEvent {
+            event_type: update.event_type,
+            data: update.event_data,
+        }).await;
+    }
+
+    /// Every instance in the registry, as replicated to peers
+    async fn cluster_snapshot(&self) -> ClusterSnapshot {
+        ClusterSnapshot {
+            node_id: self.cluster.as_ref().map(|c| c.node_id().to_string()).unwrap_or_default(),
+            services: self.services.read().await.values().map(ServiceRecord::from).collect(),
+        }
+    }
+
+    /// Periodically pull every peer's registry and take the instances missing here (e.g. registered
+    /// while this node was down) or changed there since
+    fn start_cluster_sync(&self) {
+        let Some(cluster) = self.cluster.clone() else {
+            return;
+        };
+        let hub_service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(cluster.sync_interval());
+            loop {
+                interval.tick().await;
+                for peer in cluster.peers() {
+                    let Ok(snapshot) = cluster.fetch_snapshot(peer).await else {
+                        continue;
+                    };
+                    for record in snapshot.services {
+                        let service_id = record.service.service_id.clone();
+                        let known = hub_service.services.read().await.get(&service_id).map(|local| local.updated_at);
+                        if known.is_some_and(|updated_at| record.updated_at <= updated_at)
+                            || cluster.is_tombstoned(&service_id).await
+                        {
+                            continue;
+                        }
+                        println!("🔗 [CLUSTER] Learned service {} ({}) from {}", record.service.service_name, service_id, snapshot.node_id);
+                        let event_type = if known.is_some() { "status_change" } else { "service_registered" };
+                        let event_data = serde_json::json!({
+                            "service_id": service_id,
+                            "namespace": record.service.namespace,
+                            "service_name": record.service.service_name,
+                            "status": record.status
+                        }).to_string();
+                        hub_service.apply_replication(ReplicationUpdate {
+                            origin: snapshot.node_id.clone(),
+                            service_id,
+                            record: Some(record),
+                            event_type: event_type.to_string(),
+                            event_data,
+                        }).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Write a registration to the store, if any
+    fn persist_service(&self, service: &ServiceInfo) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save(&service.into()) {
+                println!("⚠️  Failed to persist service {}: {:#}", service.service_id, e);
+            }
+        }
+    }
+
+    /// Use custom limits for calls queued while a service is busy
+    fn with_queue_config(mut self, config: QueueConfig) -> Self {
+        self.call_queue = Arc::new(CallQueue::new(config));
+        self
+    }
+
+    /// Call a method on a service instance, transcoding the JSON input with its descriptors
+    async fn call_grpc_method(
+        &self,
+        host: &str,
+        port: u16,
+        service: &str,
+        method: &str,
+        input: serde_json::Value,
+        options: &CallOptions,
+    ) -> Result<GrpcCallResult, GrpcCallError> {
+        println!("🔍 [DEBUG] Hub: Starting gRPC call to {}/{} at {}:{}", service, method, host, port);
+        
+        let result = self.grpc_client.call_unary(host, port, service, method, input, options).await;
+        match &result {
+            Ok(_) => println!("🔍 [DEBUG] Hub: gRPC call to {}/{} succeeded", service, method),
+            Err(e) => println!("❌ [DEBUG] Hub: {}", e),
+        }
+        result
+    }
+
+    /// Broadcast an event for a registry change to local subscribers and, when clustered,
+    /// replicate the change (with its event) to every peer
+    async fn broadcast_event(&self, mut event: SSEEvent) {
+        let service_id = self.tag_namespace(&mut event).await;
+        
+        if let Some(cluster) = &self.cluster {
+            if let Some(service_id) = service_id {
+                // Stamp the change so peers holding an older copy take it, and newer ones ignore it
+                let record = self.services.write().await.get_mut(&service_id).map(|service| {
+                    service.updated_at = Utc::now().max(service.updated_at + chrono::Duration::microseconds(1));
+                    ServiceRecord::from(&*service)
+                });
+                cluster.replicate(ReplicationUpdate {
+                    origin: cluster.node_id().to_string(),
+                    service_id,
+                    record,
+                    event_type: event.event_type.clone(),
+                    event_data: event.data.clone(),
+                });
+            }
+        }
+        self.publish_event(event).await;
+    }
+
+    /// Broadcast an instance going busy or back online around this hub's own calls. Peers route
+    /// their own calls, so the change is not replicated.
+    async fn broadcast_load_event(&self, mut event: SSEEvent) {
+        self.tag_namespace(&mut event).await;
+        self.publish_event(event).await;
+    }
+
+    /// Tag an event about an instance with the instance's namespace, so subscribers can be scoped
+    /// to one; returns the instance's ID
+    async fn tag_namespace(&self, event: &mut SSEEvent) -> Option<String> {
+        let service_id = serde_json::from_str::<serde_json::Value>(&event.data).ok()
+            .and_then(|data| data.get("service_id").and_then(|v| v.as_str()).map(str::to_string));
+        if let (Some(service_id), Ok(serde_json::Value::Object(mut data))) = (&service_id, serde_json::from_str(&event.data)) {
+            if !data.contains_key("namespace") {
+                if let Some(service) = self.services.read().await.get(service_id) {
+                    data.insert("namespace".to_string(), serde_json::json!(service.namespace));
+                    event.data = serde_json::Value::Object(data).to_string();
+                }
+            }
+        }
+        service_id
+    }
+
+    /// Send an event to the SSE and `SubscribeToService` subscribers of this node only
+    async fn publish_event(&self, event: SSEEvent) {
+        let mut senders = self.event_senders.write().await;
+        // Drop senders whose subscribers (SSE clients or SubscribeToService streams) have gone away
+        senders.retain(|sender| sender.receiver_count() > 0);
         println!("📡 Broadcasting event '{}' to {} subscribers", event.event_type, senders.len());
         for sender in senders.iter() {
             if let Err(e) = sender.send(event.clone()) {
@@ -177,6 +822,58 @@ impl GrpcHubService {
         senders.push(sender);
     }
 
+    /// Instance counts, queue depths, heartbeat lag and subscribers for `/metrics`
+    async fn metrics_snapshot(&self) -> metrics::Snapshot {
+        let mut snapshot = metrics::Snapshot::default();
+        let now = Utc::now();
+        for service in self.services.read().await.values() {
+            let key = (service.namespace.clone(), service.service_name.clone(), service.status.clone());
+            *snapshot.instances.entry(key).or_default() += 1;
+            let lag = (now - service.last_heartbeat).to_std().unwrap_or_default();
+            snapshot.heartbeat_lag.insert((service.service_name.clone(), service.service_id.clone()), lag);
+        }
+        snapshot.queue_depths = self.call_queue.depths().await.into_iter().collect();
+        snapshot.sse_subscribers = self.event_senders.read().await.iter().filter(|sender| sender.receiver_count() > 0).count();
+        snapshot
+    }
+
+    /// Remove a service from the registry and notify subscribers
+    async fn remove_service(&self, service_id: &str) -> Option<ServiceInfo> {
+        let removed = self.services.write().await.remove(service_id);
+        
+        if let Some(service) = &removed {
+            println!("Service unregistered: {}", service_id);
+            self.forget_service(service, "service_unregistered", "unregistered").await;
+        }
+        
+        removed
+    }
+
+    /// Clean up after an instance was taken out of the registry: tombstone it in the cluster,
+    /// drop it from the store and announce it with `event_type`
+    async fn forget_service(&self, service: &ServiceInfo, event_type: &str, status: &str) {
+        self.balancers.forget(&service.service_id);
+        self.breakers.forget(&service.service_id);
+        self.limits.forget_instance(&service.service_id);
+        if let Some(cluster) = &self.cluster {
+            cluster.add_tombstone(&service.service_id).await;
+        }
+        if let Some(store) = &self.store {
+            if let Err(e) = store.remove(&service.service_id) {
+                println!("⚠️  Failed to remove service {} from storage: {:#}", service.service_id, e);
+            }
+        }
+        self.broadcast_event(SSEEvent {
+            event_type: event_type.to_string(),
+            data: serde_json::json!({
+                "service_id": service.service_id,
+                "namespace": service.namespace,
+                "service_name": service.service_name,
+                "status": status
+            }).to_string(),
+        }).await;
+    }
+
     async fn set_service_busy(&self, service_id: &str) {
         println!("🔍 [DEBUG] set_service_busy: Attempting to set service {} to busy", service_id);
         let mut services = self.services.write().await;
@@ -185,17 +882,20 @@ impl GrpcHubService {
             let old_status = service.status.clone();
             if old_status != "busy" {
                 service.status = "busy".to_string();
+                service.busy_with_calls = true;
                 println!("🔄 Service {} status changed: {} -> busy", service.service_name, old_status);
                 
-                // Broadcast status change
-                self.broadcast_event(SSEEvent {
+                // Broadcast status change after releasing the lock
+                let event = SSEEvent {
                     event_type: "status_change".to_string(),
                     data: serde_json::json!({
                         "service_id": service_id,
                         "service_name": service.service_name,
                         "status": "busy"
                     }).to_string(),
-                }).await;
+                };
+                drop(services);
+                self.broadcast_load_event(event).await;
             } else {
                 println!("⚠️  Service {} is already busy (no change needed)", service.service_name);
             }
@@ -207,27 +907,34 @@ impl GrpcHubService {
     async fn set_service_online(&self, service_id: &str) {
         println!("🔍 [DEBUG] set_service_online: Attempting to set service {} to online", service_id);
         let mut services = self.services.write().await;
-        if let Some(service) = services.get_mut(service_id) {
-            let old_status = service.status.clone();
-            if old_status != "online" {
-                service.status = "online".to_string();
-                println!("✅ Service {} status changed: {} -> online", service.service_name, old_status);
-                
-                // Broadcast status change
-                self.broadcast_event(SSEEvent {
-                    event_type: "status_change".to_string(),
-                    data: serde_json::json!({
-                        "service_id": service_id,
-                        "service_name": service.service_name,
-                        "status": "online"
-                    }).to_string(),
-                }).await;
-            } else {
-                println!("⚠️  Service {} is already online (no change needed)", service.service_name);
-            }
-        } else {
+        let Some(service) = services.get_mut(service_id) else {
             println!("❌ [DEBUG] set_service_online: Service {} not found in services map", service_id);
+            return;
+        };
+        // An instance the hub already has at capacity stays busy until one of its calls ends
+        let status = if self.has_room(service) { "online" } else { "busy" };
+        let old_status = std::mem::replace(&mut service.status, status.to_string());
+        service.busy_with_calls = status == "busy";
+        // A queued call gets the instance before it is visible as online, so new calls can't overtake it
+        self.hand_off_locked(&mut services, service_id).await;
+        let service = &services[service_id];
+        if service.status == old_status {
+            println!("⚠️  Service {} is already {} (no change needed)", service.service_name, old_status);
+            return;
         }
+        println!("✅ Service {} status changed: {} -> {}", service.service_name, old_status, service.status);
+        
+        // Broadcast status change after releasing the lock
+        let event = SSEEvent {
+            event_type: "status_change".to_string(),
+            data: serde_json::json!({
+                "service_id": service_id,
+                "service_name": service.service_name,
+                "status": service.status
+            }).to_string(),
+        };
+        drop(services);
+        self.broadcast_load_event(event).await;
     }
 
     async fn get_service_by_address(&self, address: &str, port: u16) -> Option<String> {
@@ -245,78 +952,264 @@ impl GrpcHubService {
     }
 
     /// Get the best available service by name (prioritizes online, non-busy services)
-    async fn get_best_service_by_name(&self, service_name: &str) -> Option<(String, String, u16)> {
+    async fn get_best_service_by_name(&self, service_name: &str, routing: &CallRouting) -> Selection {
         let services = self.services.read().await;
         
         println!("🔍 [DEBUG] get_best_service_by_name: Looking for service '{}'", service_name);
         
-        // Find all services with the matching name
+        // Find all services with the matching name (and version, if the call asks for one)
         let matching_services: Vec<_> = services.values()
-            .filter(|service| service.service_name == service_name)
+            .filter(|service| service.service_name == service_name && routing.accepts(service))
+            // Instances whose circuit is open get no calls until it half-opens
+            .filter(|service| self.breakers.allows(&service.service_id))
             .collect();
         
         if matching_services.is_empty() {
             println!("❌ [DEBUG] get_best_service_by_name: No services found with name '{}'", service_name);
-            return None;
+            return Selection::NotFound;
         }
         
         println!("🔍 [DEBUG] get_best_service_by_name: Found {} services with name '{}'", matching_services.len(), service_name);
         
         // Prioritize services that are online and not busy
-        let available_services: Vec<_> = matching_services.iter()
-            .filter(|service| service.status == "online")
+        let mut available_services: Vec<_> = matching_services.iter()
+            .filter(|service| service.status == "online" && self.has_room(service))
             .collect();
         
+        // Instances at their rate or in-flight limit sit this call out
+        let mut limited_for = None;
+        available_services.retain(|service| match self.instance_wait(service) {
+            Some(wait) => {
+                limited_for = Some(limited_for.map_or(wait, |shortest: std::time::Duration| shortest.min(wait)));
+                false
+            }
+            None => true,
+        });
+        
+        // A traffic split first decides which version gets the call
+        let free_versions: Vec<&str> = available_services.iter().map(|service| service.service_version.as_str()).collect();
+        if let Some(entry) = self.traffic_splits.choose(service_name, &free_versions).await {
+            println!("🔀 [SPLIT] '{}' call routed to version {}", service_name, entry.version);
+            available_services.retain(|service| entry.matches(&service.service_version));
+        }
+        
         let selected_service = if !available_services.is_empty() {
-            // Implement round-robin load balancing
-            let service_name = &available_services[0].service_name;
-            let current_count = {
-                let mut counters = self.service_counters.write().await;
-                let counter = counters.entry(service_name.clone()).or_insert_with(|| AtomicU64::new(0));
-                counter.fetch_add(1, Ordering::Relaxed)
-            };
-            
-            let selected_index = (current_count as usize) % available_services.len();
+            // Let the service's load balancer pick among the available instances
+            let candidates: Vec<Candidate> = available_services.iter()
+                .map(|service| Candidate {
+                    service_id: service.service_id.clone(),
+                    weight: instance_weight(&service.metadata),
+                    load: self.balancers.load(&service.service_id),
+                })
+                .collect();
+            // Consistent hashing keeps busy instances on the ring so their keys don't move
+            let mut ring: Vec<String> = matching_services.iter()
+                .filter(|service| service.status == "online" || service.status == "busy")
+                .map(|service| service.service_id.clone())
+                .collect();
+            ring.sort();
+            let selected_index = self.balancers.pick(service_name, &candidates, &Route { hash_key: routing.hash_key.as_deref(), ring: &ring });
             let selected = available_services[selected_index];
             
-            println!("✅ [DEBUG] get_best_service_by_name: Found {} available services, using round-robin (index: {}/{})", 
-                     available_services.len(), selected_index, available_services.len());
-            println!("🎯 [DEBUG] Round-robin selected: {}:{} (counter: {})", 
-                     selected.service_address, selected.service_port, current_count);
+            println!("✅ [DEBUG] get_best_service_by_name: Found {} available services, using {:?} (index: {}/{})", 
+                     available_services.len(), self.balancers.kind(service_name), selected_index, available_services.len());
+            println!("🎯 [DEBUG] Balancer selected: {}:{} (load: {:?})", 
+                     selected.service_address, selected.service_port, candidates[selected_index].load);
             
+            self.take_instance(selected);
             selected
+        } else if matching_services.iter().any(|service| service.status == "busy" || (service.status == "online" && !self.has_room(service))) {
+            println!("⏳ [DEBUG] get_best_service_by_name: All instances of '{}' are busy", service_name);
+            return Selection::AllBusy;
+        } else if let Some(wait) = limited_for {
+            println!("🚦 [LIMIT] get_best_service_by_name: Every free instance of '{}' is at its limit", service_name);
+            return Selection::Limited(wait);
         } else {
             println!("⚠️  [DEBUG] get_best_service_by_name: No available services, selecting first available");
             matching_services[0]
         };
         
-        let port = selected_service.service_port.parse::<u16>().ok()?;
+        let Ok(port) = selected_service.service_port.parse::<u16>() else {
+            return Selection::NotFound;
+        };
         println!("🎯 [DEBUG] get_best_service_by_name: Selected service at {}:{} (status: {})", 
                  selected_service.service_address, port, selected_service.status);
         
-        Some((
+        Selection::Instance(
             selected_service.service_id.clone(),
             selected_service.service_address.clone(),
             port
-        ))
+        )
     }
 
-    /// Mark a service as offline instantly when a connection fails
-    async fn mark_service_offline(&self, service_id: &str, reason: &str) {
-        let service_name = {
-            let mut services = self.services.write().await;
-            if let Some(service) = services.get_mut(service_id) {
-                let was_online = service.status == "online" || service.status == "busy";
-                service.status = "offline".to_string();
-                let service_name = service.service_name.clone();
-                
-                println!("🔴 Service {} (ID: {}) marked offline: {}", service_name, service_id, reason);
-                println!("   Status updated to: {}", service.status);
-                
-                if was_online {
-                    Some(service_name)
-                } else {
-                    None
+    /// Reserve an instance of `service_name` for one call. While every instance `routing` accepts is
+    /// busy the call waits in the service's queue (higher priority first) until one is handed to it.
+    async fn acquire_service_by_name(&self, service_name: &str, routing: &CallRouting) -> Result<AcquiredInstance, GrpcCallError> {
+        // Each namespace queues separately
+        let queue_name = qualified_name(&routing.namespace, service_name);
+        let ticket = loop {
+            match self.get_best_service_by_name(service_name, routing).await {
+                Selection::Instance(service_id, host, port) => {
+                    if let Some(guard) = BusyGuard::try_acquire(self, &service_id).await {
+                        return Ok(AcquiredInstance { guard, host, port });
+                    }
+                    // Another call took this instance first; pick again
+                }
+                Selection::NotFound => {
+                    let mut message = format!("No available service found for '{}'", queue_name);
+                    if let Some(range) = &routing.version {
+                        message.push_str(&format!(" matching version {}", range));
+                    }
+                    if let Some(selector) = &routing.selector {
+                        message.push_str(&format!(" matching labels '{}'", selector));
+                    }
+                    return Err(GrpcCallError::new(tonic::Code::NotFound, message));
+                }
+                Selection::Limited(wait) => {
+                    return Err(limits::rejection(&format!("Every free instance of '{}' is at its limit", queue_name), wait));
+                }
+                Selection::AllBusy => {
+                    break self.call_queue.enqueue(&queue_name, routing.priority, routing.clone()).await.map_err(|full| {
+                        println!("🚫 [QUEUE] Queue for '{}' is full ({} waiting)", queue_name, full.depth);
+                        GrpcCallError::new(
+                            tonic::Code::ResourceExhausted,
+                            format!("All instances of '{}' are busy and the call queue is full ({} waiting)", queue_name, full.depth),
+                        )
+                    })?;
+                }
+            }
+        };
+        
+        // An instance may have been released between picking and queueing
+        if let Selection::Instance(service_id, host, port) = self.get_best_service_by_name(service_name, routing).await {
+            if let Some(guard) = BusyGuard::try_acquire(self, &service_id).await {
+                if self.call_queue.cancel(&queue_name, ticket.id).await {
+                    return Ok(AcquiredInstance { guard, host, port });
+                }
+                // Another instance was handed to us meanwhile; release this one and use that
+                drop(guard);
+            }
+        }
+        
+        println!("⏳ [QUEUE] Call queued for '{}' (ticket {})", queue_name, ticket.id);
+        let max_wait = self.call_queue.config().max_wait;
+        // Never wait longer than the caller will
+        let wait = routing.deadline.map_or(max_wait, |deadline| {
+            max_wait.min(deadline.saturating_duration_since(tokio::time::Instant::now()))
+        });
+        let mut receiver = ticket.receiver;
+        if let Ok(Ok(instance)) = tokio::time::timeout(wait, &mut receiver).await {
+            println!("📬 [QUEUE] Ticket {} got {}:{}", ticket.id, instance.host, instance.port);
+            return Ok(instance);
+        }
+        
+        if !self.call_queue.cancel(&queue_name, ticket.id).await {
+            // Handed an instance just as the wait ran out
+            if let Ok(instance) = receiver.await {
+                return Ok(instance);
+            }
+        }
+        println!("⌛ [QUEUE] Ticket {} for '{}' timed out after {:?}", ticket.id, queue_name, wait);
+        if wait < max_wait {
+            return Err(GrpcCallError::new(
+                tonic::Code::DeadlineExceeded,
+                format!("Deadline exceeded while waiting for a free instance of '{}'", queue_name),
+            ));
+        }
+        Err(GrpcCallError::new(
+            tonic::Code::ResourceExhausted,
+            format!("All instances of '{}' stayed busy for {:?}", queue_name, max_wait),
+        ))
+    }
+
+    /// Give an online instance straight to the next queued call for its service that accepts it,
+    /// marking it busy if that fills it. Returns false, leaving its status alone, when no such
+    /// call is waiting.
+    async fn hand_off_to_queued_call(&self, service_id: &str) -> bool {
+        let mut services = self.services.write().await;
+        if !self.hand_off_locked(&mut services, service_id).await {
+            return false;
+        }
+        let service = &services[service_id];
+        if service.status != "busy" {
+            return true;
+        }
+        let service_name = service.service_name.clone();
+        drop(services);
+        println!("🔄 Service {} status changed: online -> busy", service_name);
+        self.broadcast_load_event(SSEEvent {
+            event_type: "status_change".to_string(),
+            data: serde_json::json!({
+                "service_id": service_id,
+                "service_name": service_name,
+                "status": "busy"
+            }).to_string(),
+        }).await;
+        true
+    }
+
+    /// `hand_off_to_queued_call` with the registry already locked; the instance is only
+    /// marked busy once a call has taken it and it has no room left
+    async fn hand_off_locked(&self, services: &mut HashMap<String, ServiceInfo>, service_id: &str) -> bool {
+        let Some(service) = services.get(service_id) else {
+            return false;
+        };
+        // Busy, full, offline and recovering instances aren't free to hand on
+        if service.status != "online"
+            || !self.has_room(service)
+            || !self.breakers.allows(service_id)
+            || self.instance_wait(service).is_some()
+        {
+            return false;
+        }
+        let Ok(port) = service.service_port.parse::<u16>() else {
+            return false;
+        };
+        let service_name = qualified_name(&service.namespace, &service.service_name);
+        if !self.call_queue.has_waiters(&service_name, |routing| routing.accepts(service)).await {
+            return false;
+        }
+        
+        let instance = AcquiredInstance {
+            guard: BusyGuard::held(self, service_id),
+            host: service.service_address.clone(),
+            port,
+        };
+        match self.call_queue.hand_off(&service_name, instance, |routing| routing.accepts(service)).await {
+            Ok(()) => {
+                println!("📬 [QUEUE] Handed {} to the next queued call for '{}'", service_id, service_name);
+                self.take_instance(service);
+                if !self.has_room(service) {
+                    if let Some(service) = services.get_mut(service_id) {
+                        service.status = "busy".to_string();
+                        service.busy_with_calls = true;
+                    }
+                }
+                true
+            }
+            Err(instance) => {
+                instance.guard.disarm();
+                false
+            }
+        }
+    }
+
+    /// Mark a service as offline instantly when a connection fails
+    async fn mark_service_offline(&self, service_id: &str, reason: &str) {
+        let service_name = {
+            let mut services = self.services.write().await;
+            if let Some(service) = services.get_mut(service_id) {
+                let was_online = service.status == "online" || service.status == "busy";
+                service.status = "offline".to_string();
+                let service_name = service.service_name.clone();
+                
+                println!("🔴 Service {} (ID: {}) marked offline: {}", service_name, service_id, reason);
+                println!("   Status updated to: {}", service.status);
+                
+                if was_online {
+                    Some(service_name)
+                } else {
+                    None
                 }
             } else {
                 None
@@ -337,67 +1230,275 @@ impl GrpcHubService {
         }
     }
 
-    /// Perform active health check on a service
-    async fn health_check_service(&self, service_id: &str, service_address: &str, service_port: u16) -> bool {
-        // Try to connect to the service's gRPC endpoint
-        let address = format!("{}:{}", service_address, service_port);
-        
-        match tokio::time::timeout(
-            std::time::Duration::from_secs(2), // 2 second timeout for health checks
-            tokio::net::TcpStream::connect(&address)
-        ).await {
-            Ok(Ok(_)) => {
-                // Connection successful
-                true
-            }
-            Ok(Err(e)) => {
-                println!("🔍 [HEALTH] Service {} connection failed: {}", address, e);
-                false
-            }
-            Err(_) => {
-                println!("🔍 [HEALTH] Service {} connection timeout", address);
-                false
-            }
-        }
+    /// Perform active health check on a service: grpc.health.v1 if it implements it, TCP otherwise
+    async fn health_check_service(&self, service_name: &str, service_address: &str, service_port: u16, timeout: std::time::Duration) -> bool {
+        health::probe_instance(&self.grpc_client, service_name, service_address, service_port, timeout).await
     }
 
     /// Start active health monitoring for all services
     async fn start_health_monitoring(&self) {
         let hub_service = self.clone();
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(5)); // Check every 5 seconds
+            let mut interval = tokio::time::interval(hub_service.health_config.sweep_interval);
             
             loop {
                 interval.tick().await;
+                hub_service.half_open_expired_circuits().await;
                 
-                // Get all services for health checking
-                let services_to_check: Vec<(String, String, u16)> = {
-                    let services = hub_service.services.read().await;
-                    services.values()
-                        .filter(|s| s.status == "online" || s.status == "busy")
-                        .map(|s| (s.service_id.clone(), s.service_address.clone(), s.service_port.parse().unwrap_or(0)))
-                        .collect()
-                };
-                
-                // Check each service
-                for (service_id, address, port) in services_to_check {
-                    if port == 0 {
-                        continue; // Skip invalid ports
-                    }
-                    
-                    let is_healthy = hub_service.health_check_service(&service_id, &address, port).await;
-                    
-                    if !is_healthy {
-                        hub_service.mark_service_offline(&service_id, "Health check failed").await;
-                        
-                        // Verify the status was actually updated
-                        let services = hub_service.services.read().await;
-                        if let Some(service) = services.get(&service_id) {
-                            println!("   ✅ Verified: Service status in map is now: {}", service.status);
-                        }
+                // Probe every instance that is due, concurrently so one slow instance doesn't hold up the rest
+                let due = hub_service.services_due_for_probe().await;
+                futures::future::join_all(due.into_iter().map(|(service_id, service_name, address, port, config)| {
+                    let hub_service = hub_service.clone();
+                    async move {
+                        let is_healthy = hub_service.health_check_service(&service_name, &address, port, config.probe_timeout).await;
+                        hub_service.record_probe(&service_id, is_healthy, &config).await;
                     }
+                })).await;
+            }
+        });
+    }
+
+    /// Instances whose probe interval has passed, as (service_id, service_name, address, port, config).
+    /// Online and busy instances are probed, and so are offline ones that were taken offline by probes.
+    async fn services_due_for_probe(&self) -> Vec<(String, String, String, u16, HealthConfig)> {
+        let (candidates, registered): (Vec<_>, Vec<String>) = {
+            let services = self.services.read().await;
+            let candidates = services.values()
+                .filter(|s| s.status != "recovering")
+                .filter_map(|s| {
+                    let port: u16 = s.service_port.parse().ok().filter(|port| *port != 0)?; // Skip invalid ports
+                    let config = self.health_config.for_service(&s.metadata);
+                    Some((s.service_id.clone(), s.service_name.clone(), s.service_address.clone(), port, config, s.status == "offline"))
+                })
+                .collect();
+            (candidates, services.keys().cloned().collect())
+        };
+        
+        let mut probe_states = self.probe_states.write().await;
+        probe_states.retain(|service_id, _| registered.contains(service_id));
+        
+        let now = std::time::Instant::now();
+        candidates.into_iter()
+            .filter_map(|(service_id, service_name, address, port, config, offline)| {
+                let state = probe_states.entry(service_id.clone()).or_default();
+                if offline && !state.down {
+                    return None;
+                }
+                if state.last_probe.is_some_and(|last| now.duration_since(last) < config.probe_interval) {
+                    return None;
+                }
+                state.last_probe = Some(now);
+                Some((service_id, service_name, address, port, config))
+            })
+            .collect()
+    }
+
+    /// Apply one probe result, taking the instance offline or bringing it back once a threshold is crossed
+    async fn record_probe(&self, service_id: &str, is_healthy: bool, config: &HealthConfig) {
+        if let Some(service_name) = self.instance_service_name(service_id).await {
+            self.metrics.record_probe(&service_name, is_healthy);
+        }
+        let (transition, failures) = {
+            let mut probe_states = self.probe_states.write().await;
+            let state = probe_states.entry(service_id.to_string()).or_default();
+            (state.record(is_healthy, config), state.failures)
+        };
+        
+        match transition {
+            Some(false) => self.mark_service_offline(service_id, "Health check failed").await,
+            Some(true) => self.recover_probed_service(service_id, config).await,
+            None if !is_healthy => {
+                println!("🔍 [HEALTH] Service {} failed {}/{} health checks", service_id, failures, config.failure_threshold);
+            }
+            None => {}
+        }
+    }
+
+    /// Bring an instance that passed its probes again back online, unless its heartbeats have stopped too
+    async fn recover_probed_service(&self, service_id: &str, config: &HealthConfig) {
+        let heartbeat_ttl = chrono::Duration::from_std(config.heartbeat_ttl).unwrap_or(chrono::Duration::MAX);
+        let service_name = {
+            let mut services = self.services.write().await;
+            match services.get_mut(service_id) {
+                Some(service) if service.status == "offline" && Utc::now() - service.last_heartbeat <= heartbeat_ttl => {
+                    service.status = "online".to_string();
+                    Some(service.service_name.clone())
+                }
+                _ => None,
+            }
+        };
+        
+        if let Some(name) = service_name {
+            println!("🟢 Service {} (ID: {}) passed its health checks again", name, service_id);
+            self.broadcast_event(SSEEvent {
+                event_type: "status_change".to_string(),
+                data: serde_json::json!({
+                    "service_id": service_id,
+                    "service_name": name,
+                    "status": "online",
+                    "reason": "Health check recovered"
+                }).to_string(),
+            }).await;
+            self.hand_off_to_queued_call(service_id).await;
+        }
+    }
+}
+
+/// Whether event `data` belongs to `namespace` (empty or "*" for every namespace).
+/// Events that aren't about an instance belong to every namespace.
+fn event_in_namespace(data: &serde_json::Value, namespace: &str) -> bool {
+    namespace.is_empty()
+        || namespace == "*"
+        || data.get("namespace").and_then(|v| v.as_str()).is_none_or(|ns| ns == namespace)
+}
+
+/// Convert a broadcast event into a `ServiceEvent` if it matches the subscription filters.
+/// An empty `service_name` or `namespace` (or "*") matches every one, and empty `event_types` every event.
+fn to_service_event(event: &SSEEvent, subscription: &SubscribeRequest) -> Option<ServiceEvent> {
+    let data: serde_json::Value = serde_json::from_str(&event.data).unwrap_or_default();
+    let service_name = data.get("service_name").and_then(|v| v.as_str()).unwrap_or_default();
+    
+    let name_matches = subscription.service_name.is_empty()
+        || subscription.service_name == "*"
+        || subscription.service_name == service_name;
+    let type_matches = subscription.event_types.is_empty()
+        || subscription.event_types.iter().any(|t| t == &event.event_type);
+    
+    if !name_matches || !type_matches || !event_in_namespace(&data, &subscription.namespace) {
+        return None;
+    }
+    
+    Some(ServiceEvent {
+        event_type: event.event_type.clone(),
+        service_name: service_name.to_string(),
+        data: event.data.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+        namespace: data.get("namespace").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+    })
+}
+
+/// Build the `CallService` response for the outcome of a routed call
+fn call_response(result: Result<GrpcCallResult, GrpcCallError>) -> ServiceCallResponse {
+    match result {
+        Ok(result) => ServiceCallResponse {
+            success: true,
+            response_data: serde_json::to_string(&result.response).unwrap_or_else(|_| "{}".to_string()),
+            status_code: 200,
+            grpc_code: tonic::Code::Ok as i32,
+            metadata: result.metadata,
+            ..Default::default()
+        },
+        Err(e) => ServiceCallResponse {
+            success: false,
+            status_code: e.http_status() as i32,
+            grpc_code: e.code as i32,
+            error_message: e.message,
+            metadata: e.metadata,
+            ..Default::default()
+        },
+    }
+}
+
+/// A response stream carrying a single (error) response
+fn single_response_stream(
+    response: ServiceCallResponse,
+) -> <GrpcHubService as GrpcHub>::CallServiceStreamStream {
+    Box::pin(tokio_stream::once(Ok(response)))
+}
+
+/// Holds a place on a service instance, which is marked busy while every place is taken;
+/// dropping it frees the place and puts a busy instance back online
+#[derive(Debug)]
+struct BusyGuard {
+    hub_service: GrpcHubService,
+    service_id: String,
+    started: std::time::Instant, // For the instance's outstanding calls and latency
+}
+
+impl BusyGuard {
+    /// Take a place on the instance even if it is full, marking it busy once it is
+    async fn acquire(hub_service: &GrpcHubService, service_id: &str) -> Self {
+        let guard = Self::held(hub_service, service_id);
+        let full = hub_service.services.read().await.get(service_id).is_some_and(|service| !hub_service.has_room(service));
+        if full {
+            hub_service.set_service_busy(service_id).await;
+        }
+        guard
+    }
+
+    /// Take a place on the instance unless it is busy or full, marking it busy if this fills it
+    async fn try_acquire(hub_service: &GrpcHubService, service_id: &str) -> Option<Self> {
+        let mut services = hub_service.services.write().await;
+        match services.get_mut(service_id) {
+            Some(service) if service.status != "busy" && hub_service.has_room(service) => {
+                let guard = Self::held(hub_service, service_id);
+                if hub_service.has_room(service) {
+                    return Some(guard);
                 }
+                let old_status = std::mem::replace(&mut service.status, "busy".to_string());
+                service.busy_with_calls = true;
+                let service_name = service.service_name.clone();
+                drop(services);
+                println!("🔄 Service {} status changed: {} -> busy", service_name, old_status);
+                hub_service.broadcast_load_event(SSEEvent {
+                    event_type: "status_change".to_string(),
+                    data: serde_json::json!({
+                        "service_id": service_id,
+                        "service_name": service_name,
+                        "status": "busy"
+                    }).to_string(),
+                }).await;
+                Some(guard)
             }
+            _ => None,
+        }
+    }
+
+    /// Guard for a place on the instance, without changing its status
+    fn held(hub_service: &GrpcHubService, service_id: &str) -> Self {
+        hub_service.balancers.call_started(service_id);
+        Self {
+            hub_service: hub_service.clone(),
+            service_id: service_id.to_string(),
+            started: std::time::Instant::now(),
+        }
+    }
+
+    fn service_id(&self) -> &str {
+        &self.service_id
+    }
+
+    /// Forget the instance without changing its status
+    fn disarm(mut self) {
+        self.hub_service.balancers.call_finished(&self.service_id, None);
+        self.service_id.clear();
+    }
+
+    /// Put the instance back online (or hand it to the next queued call) now, rather than on drop
+    async fn release(mut self) {
+        let service_id = std::mem::take(&mut self.service_id);
+        self.hub_service.balancers.call_finished(&service_id, Some(self.started.elapsed()));
+        self.hub_service.set_service_online(&service_id).await;
+    }
+
+    /// Release the instance as offline instead of online, e.g. when the hub could not reach it
+    async fn release_offline(mut self, reason: &str) {
+        let service_id = std::mem::take(&mut self.service_id);
+        self.hub_service.balancers.call_finished(&service_id, None);
+        self.hub_service.mark_service_offline(&service_id, reason).await;
+    }
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        if self.service_id.is_empty() {
+            return; // Already released
+        }
+        let hub_service = self.hub_service.clone();
+        let service_id = std::mem::take(&mut self.service_id);
+        hub_service.balancers.call_finished(&service_id, Some(self.started.elapsed()));
+        tokio::spawn(async move {
+            hub_service.set_service_online(&service_id).await;
         });
     }
 }
@@ -408,18 +1509,35 @@ impl GrpcHub for GrpcHubService {
         &self,
         request: Request<RegisterServiceRequest>,
     ) -> Result<Response<RegisterServiceResponse>, Status> {
+        self.authorize(&request, Action::Register(namespace::normalize(&request.get_ref().namespace), &request.get_ref().service_name))?;
+        let identity = request.extensions().get::<Identity>().cloned();
         let req = request.into_inner();
+        let namespace = namespace::normalize(&req.namespace).to_string();
+        if let Err(message) = namespace::validate(&namespace) {
+            return Ok(Response::new(RegisterServiceResponse {
+                success: false,
+                message,
+                ..Default::default()
+            }));
+        }
         
         let mut services = self.services.write().await;
         
-        // Check if a service with the same name and address/port already exists
+        // Check if a service with the same namespace, name and address/port already exists
         let existing_service = services.values().find(|s| 
+            s.namespace == namespace &&
             s.service_name == req.service_name && 
             s.service_address == req.service_address && 
             s.service_port == req.service_port
         );
         
         let service_id = if let Some(existing) = existing_service {
+            // A live instance is only registered again with its current secret, so nobody else can
+            // take it over; an offline one by whoever restarted it
+            if existing.status != "offline" && !existing.has_secret(&req.instance_secret) && !self.rbac.holds(identity.as_ref(), Role::Operator) {
+                println!("🔒 [AUTH] Rejected registering instance {} again without its secret", existing.service_id);
+                return Err(Status::permission_denied(format!("Service {} is already registered; registering it again needs its instance secret", existing.service_id)));
+            }
             // Update existing service instead of creating a new one
             println!("Updating existing service: {}", existing.service_id);
             existing.service_id.clone()
@@ -430,9 +1548,11 @@ impl GrpcHub for GrpcHubService {
         
         let service_name = req.service_name.clone();
         let service_id_for_event = service_id.clone();
+        let lease_ttl = self.health_config.for_service(&req.metadata).heartbeat_ttl;
         
         let service_info = ServiceInfo {
             service_id: service_id.clone(),
+            namespace,
             service_name: req.service_name,
             service_version: req.service_version,
             service_address: req.service_address,
@@ -442,9 +1562,14 @@ impl GrpcHub for GrpcHubService {
             registered_at: Utc::now(),
             last_heartbeat: Utc::now(),
             status: "online".to_string(), // New services start as online
+            instance_secret: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+            updated_at: Utc::now(),
+            busy_with_calls: false,
         };
+        let instance_secret = service_info.instance_secret.clone();
         
-            services.insert(service_id.clone(), service_info);
+        self.persist_service(&service_info);
+        services.insert(service_id.clone(), service_info);
         drop(services); // Release the lock
         
         println!("Service registered: {}", service_id);
@@ -460,10 +1585,15 @@ impl GrpcHub for GrpcHubService {
         };
         self.broadcast_event(event).await;
         
+        // Calls queued for this service name can use the new instance right away
+        self.hand_off_to_queued_call(&service_id_for_event).await;
+        
         Ok(Response::new(RegisterServiceResponse {
             success: true,
             message: "Service registered successfully".to_string(),
             service_id,
+            lease_ttl_ms: lease_ttl.as_millis() as i64,
+            instance_secret,
         }))
     }
 
@@ -471,13 +1601,16 @@ impl GrpcHub for GrpcHubService {
         &self,
         request: Request<UnregisterServiceRequest>,
     ) -> Result<Response<UnregisterServiceResponse>, Status> {
+        if let Some((namespace, service_name)) = self.instance_scope(&request.get_ref().service_id).await {
+            self.authorize(&request, Action::Manage(&namespace, &service_name))?;
+        }
+        let identity = request.extensions().get::<Identity>().cloned();
         let req = request.into_inner();
+        self.verify_instance_secret(identity.as_ref(), &req.service_id, &req.instance_secret).await?;
         
-        let mut services = self.services.write().await;
-        let removed = services.remove(&req.service_id);
+        let removed = self.remove_service(&req.service_id).await;
         
         if removed.is_some() {
-            println!("Service unregistered: {}", req.service_id);
             Ok(Response::new(UnregisterServiceResponse {
                 success: true,
                 message: "Service unregistered successfully".to_string(),
@@ -494,7 +1627,10 @@ impl GrpcHub for GrpcHubService {
         &self,
         request: Request<ListServicesRequest>,
     ) -> Result<Response<ListServicesResponse>, Status> {
+        self.authorize(&request, Action::Read)?;
         let req = request.into_inner();
+        let selector = LabelSelector::parse_optional(req.label_selector.as_deref())
+            .map_err(|e| Status::invalid_argument(e.message))?;
         let services = self.services.read().await;
         
         let mut service_list: Vec<grpc_hub::ServiceInfo> = services
@@ -507,7 +1643,9 @@ impl GrpcHub for GrpcHubService {
                     true
                 }
             })
-            .map(|service| service.clone().into())
+            .filter(|service| selector.as_ref().is_none_or(|selector| selector.matches(&service.metadata)))
+            .filter(|service| req.namespace.as_deref().is_none_or(|ns| service.namespace == namespace::normalize(ns)))
+            .map(|service| self.service_info(service))
             .collect();
         
         service_list.sort_by(|a, b| a.service_name.cmp(&b.service_name));
@@ -521,12 +1659,13 @@ impl GrpcHub for GrpcHubService {
         &self,
         request: Request<GetServiceRequest>,
     ) -> Result<Response<GetServiceResponse>, Status> {
+        self.authorize(&request, Action::Read)?;
         let req = request.into_inner();
         let services = self.services.read().await;
         
         if let Some(service) = services.get(&req.service_id) {
             Ok(Response::new(GetServiceResponse {
-                service: Some(service.clone().into()),
+                service: Some(self.service_info(service)),
                 found: true,
             }))
         } else {
@@ -541,19 +1680,39 @@ impl GrpcHub for GrpcHubService {
         &self,
         request: Request<HealthCheckRequest>,
     ) -> Result<Response<HealthCheckResponse>, Status> {
+        if let Some((namespace, service_name)) = self.instance_scope(&request.get_ref().service_id).await {
+            self.authorize(&request, Action::Register(&namespace, &service_name))?;
+        }
+        let identity = request.extensions().get::<Identity>().cloned();
         let req = request.into_inner();
+        self.verify_instance_secret(identity.as_ref(), &req.service_id, &req.instance_secret).await?;
+        // An instance taken offline by failed probes only comes back once it passes them again
+        let failing_probes = self.probe_states.read().await.get(&req.service_id).is_some_and(|state| state.down);
         let mut services = self.services.write().await;
         
         if let Some(service) = services.get_mut(&req.service_id) {
-            let was_offline = service.status == "offline";
+            let was_offline = (service.status == "offline" && !failing_probes) || service.status == "recovering";
             let service_name = service.service_name.clone();
             service.last_heartbeat = Utc::now();
-            // Mark service as online when it sends heartbeat
-            service.status = "online".to_string();
+            // Mark an offline or recovering service as online when it sends heartbeat (a busy one stays busy)
+            if was_offline {
+                service.status = "online".to_string();
+            }
+            let last_heartbeat = service.last_heartbeat.to_rfc3339();
+            let lease_ttl_ms = self.health_config.for_service(&service.metadata).heartbeat_ttl.as_millis() as i64;
+            drop(services);
+            
+            self.broadcast_event(SSEEvent {
+                event_type: "heartbeat".to_string(),
+                data: serde_json::json!({
+                    "service_id": req.service_id,
+                    "service_name": service_name,
+                    "last_heartbeat": last_heartbeat
+                }).to_string(),
+            }).await;
             
             // Broadcast status change if service came back online
             if was_offline {
-                drop(services);
                 let event = SSEEvent {
                     event_type: "status_change".to_string(),
                     data: serde_json::json!({
@@ -563,16 +1722,27 @@ impl GrpcHub for GrpcHubService {
                     }).to_string(),
                 };
                 self.broadcast_event(event).await;
+                self.hand_off_to_queued_call(&req.service_id).await;
+            }
+            
+            if failing_probes {
+                return Ok(Response::new(HealthCheckResponse {
+                    healthy: false,
+                    message: "Heartbeat recorded, but the service is failing health checks".to_string(),
+                    lease_ttl_ms,
+                }));
             }
             
             Ok(Response::new(HealthCheckResponse {
                 healthy: true,
                 message: "Service is healthy".to_string(),
+                lease_ttl_ms,
             }))
         } else {
             Ok(Response::new(HealthCheckResponse {
                 healthy: false,
                 message: "Service not found".to_string(),
+                lease_ttl_ms: 0,
             }))
         }
     }
@@ -581,6 +1751,12 @@ impl GrpcHub for GrpcHubService {
         &self,
         request: Request<ServiceCallRequest>,
     ) -> Result<Response<ServiceCallResponse>, Status> {
+        // The deadline counts from now, so time spent in the hub comes off what the target gets
+        let deadline = call_deadline(
+            request.metadata().get("grpc-timeout").and_then(|v| v.to_str().ok()),
+            request.get_ref().timeout_ms,
+        );
+        let identity = request.extensions().get::<Identity>().cloned();
         let req = request.into_inner();
         
         println!("🔍 [DEBUG] gRPC CallService: {} -> {}", req.target_service, req.method);
@@ -589,72 +1765,150 @@ impl GrpcHub for GrpcHubService {
         let request_data: serde_json::Value = match serde_json::from_str(&req.request_data) {
             Ok(data) => data,
             Err(e) => {
-                return Ok(Response::new(ServiceCallResponse {
-                    success: false,
-                    response_data: "".to_string(),
-                    error_message: format!("Invalid JSON in request data: {}", e),
-                    status_code: 400,
-                }));
+                return Ok(Response::new(call_response(Err(GrpcCallError::new(
+                    tonic::Code::InvalidArgument,
+                    format!("Invalid JSON in request data: {}", e),
+                )))));
             }
         };
         
-        // Extract service name from full gRPC service name (e.g., "web_content_extract.WebContentExtract" -> "web-content-extract")
-        let short_service_name = req.target_service.split('.').next().unwrap_or(&req.target_service)
-            .replace("_", "-")
-            .to_lowercase();
+        // "namespace/service@range" names an exported service of another namespace, and limits
+        // the call to instances whose version satisfies the range
+        let target = match self.resolve_target(&req.namespace, &req.target_service) {
+            Ok(target) => target,
+            Err(e) => return Ok(Response::new(call_response(Err(e)))),
+        };
+        let selector = match LabelSelector::parse_optional(Some(&req.label_selector)) {
+            Ok(selector) => selector,
+            Err(e) => return Ok(Response::new(call_response(Err(e)))),
+        };
+        let routing = CallRouting {
+            deadline,
+            ..self.call_routing(&target, req.priority, selector, &req.headers, Some(&request_data))
+        };
+        if let Err(e) = self.rbac.authorize(identity.as_ref(), Action::Call(&target.namespace, &target.service_name, &req.method)) {
+            return Ok(Response::new(call_response(Err(e))));
+        }
+        let ResolvedTarget { grpc_service, service_name: short_service_name, .. } = target;
+        let options = CallOptions { headers: req.headers, deadline, service_name: short_service_name.clone() };
         
+        let mode = match DispatchMode::parse(&req.dispatch_mode, req.quorum as i64, req.hedge_delay_ms) {
+            Ok(mode) => mode,
+            Err(e) => return Ok(Response::new(call_response(Err(e)))),
+        };
+        // The permit holds the call's place in flight until it is done
+        let _permit = match self.limits.admit(&req.caller_service, &short_service_name) {
+            Ok(permit) => permit,
+            Err(e) => return Ok(Response::new(call_response(Err(e)))),
+        };
         println!("🔍 [DEBUG] Hub: Intelligent selection mode for service: {}", short_service_name);
         
-        // Get the best available service
-        let (service_id, host, port) = match self.get_best_service_by_name(&short_service_name).await {
-            Some((id, h, p)) => {
-                println!("🎯 [DEBUG] Hub: Selected service {} at {}:{}", id, h, p);
-                (id, h, p)
-            }
-            None => {
-                return Ok(Response::new(ServiceCallResponse {
-                    success: false,
-                    response_data: "".to_string(),
-                    error_message: format!("No available service found for '{}'", short_service_name),
-                    status_code: 404,
-                }));
-            }
+        // One instance (retried on others as its policy allows), or fanned out to several
+        let call = DispatchCall {
+            service_name: short_service_name,
+            grpc_service,
+            method: req.method,
+            input: request_data,
+            options,
+            routing,
         };
+        let started = tokio::time::Instant::now();
+        let dispatched = dispatch(self, &call, &mode).await;
+        self.record_call_metrics(CallApi::Grpc, &call.service_name, &call.method, &dispatched.result, started.elapsed()).await;
+        let mut response = call_response(dispatched.result);
+        response.results = dispatched.outcomes.iter().map(Into::into).collect();
+        Ok(Response::new(response))
+    }
+
+    type CallServiceStreamStream = std::pin::Pin<Box<dyn tokio_stream::Stream<Item = Result<ServiceCallResponse, Status>> + Send>>;
+
+    async fn call_service_stream(
+        &self,
+        request: Request<tonic::Streaming<ServiceCallRequest>>,
+    ) -> Result<Response<Self::CallServiceStreamStream>, Status> {
+        let identity = request.extensions().get::<Identity>().cloned();
+        let mut inbound = request.into_inner();
+        let first = inbound.message().await?
+            .ok_or_else(|| Status::invalid_argument("Stream closed before the first request"))?;
         
-        // Set service to busy before making the call
-        println!("🔍 [DEBUG] Hub: Setting service {} to busy", service_id);
-        self.set_service_busy(&service_id).await;
+        println!("🔍 [DEBUG] gRPC CallServiceStream: {} -> {}", first.target_service, first.method);
         
-        // Call the gRPC method using grpcurl
-        let result = call_grpc_method(
-            &host,
-            port,
-            &req.target_service,
-            &req.method,
-            request_data,
-        ).await;
-        
-        // Set service back to online after the call
-        self.set_service_online(&service_id).await;
-        
-        match result {
-            Ok(response_data) => {
-                Ok(Response::new(ServiceCallResponse {
-                    success: true,
-                    response_data: serde_json::to_string(&response_data).unwrap_or_else(|_| "{}".to_string()),
-                    error_message: "".to_string(),
-                    status_code: 200,
-                }))
-            }
+        let first_input: serde_json::Value = match serde_json::from_str(&first.request_data) {
+            Ok(data) => data,
             Err(e) => {
-                Ok(Response::new(ServiceCallResponse {
+                return Ok(Response::new(single_response_stream(ServiceCallResponse {
                     success: false,
-                    response_data: "".to_string(),
-                    error_message: e.to_string(),
-                    status_code: 500,
-                }))
+                    error_message: format!("Invalid JSON in request data: {}", e),
+                    status_code: 400,
+                    grpc_code: tonic::Code::InvalidArgument as i32,
+                    ..Default::default()
+                })));
             }
-        }
+        };
+        
+        let target = self.resolve_target(&first.namespace, &first.target_service)
+            .and_then(|target| {
+                self.rbac.authorize(identity.as_ref(), Action::Call(&target.namespace, &target.service_name, &first.method))?;
+                Ok((target, LabelSelector::parse_optional(Some(&first.label_selector))?))
+            });
+        // The permit counts the stream against its limits until it ends
+        let acquired = match target {
+            Ok((target, selector)) => match self.limits.admit(&first.caller_service, &target.service_name) {
+                Ok(permit) => {
+                    let routing = self.call_routing(&target, first.priority, selector, &first.headers, Some(&first_input));
+                    self.acquire_service_by_name(&target.service_name, &routing).await
+                        .map(|instance| (target, instance, permit))
+                }
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        let (target, instance, permit) = match acquired {
+            Ok(acquired) => acquired,
+            Err(e) => return Ok(Response::new(single_response_stream(call_response(Err(e))))),
+        };
+        println!("🎯 [DEBUG] Hub: Selected service {} at {}:{} for stream", instance.guard.service_id(), instance.host, instance.port);
+        
+        // Every further request on the inbound stream carries one more message
+        let inputs = inbound.map(|request| {
+            let request = request.map_err(GrpcCallError::from_status)?;
+            serde_json::from_str(&request.request_data).map_err(|e| {
+                GrpcCallError::new(tonic::Code::InvalidArgument, format!("Invalid JSON in request data: {}", e))
+            })
+        });
+        
+        let target = StreamTarget {
+            guard: Some(instance.guard),
+            permit: Some(permit),
+            service_name: target.service_name,
+            host: instance.host,
+            port: instance.port,
+            service: target.grpc_service,
+            method: first.method,
+            headers: first.headers,
+        };
+        let events = relay_stream(self.clone(), target, first_input, inputs);
+        
+        let responses = ReceiverStream::new(events).filter_map(|event| match event {
+            RelayEvent::Message(message) => Some(Ok(ServiceCallResponse {
+                success: true,
+                response_data: message.to_string(),
+                status_code: 200,
+                grpc_code: tonic::Code::Ok as i32,
+                ..Default::default()
+            })),
+            RelayEvent::Completed(_) => None,
+            RelayEvent::Failed(e) => Some(Ok(ServiceCallResponse {
+                success: false,
+                status_code: e.http_status() as i32,
+                grpc_code: e.code as i32,
+                error_message: e.message,
+                metadata: e.metadata,
+                ..Default::default()
+            })),
+        });
+        
+        Ok(Response::new(Box::pin(responses)))
     }
 
     type SubscribeToServiceStream = ReceiverStream<Result<ServiceEvent, Status>>;
@@ -663,17 +1917,49 @@ impl GrpcHub for GrpcHubService {
         &self,
         request: Request<SubscribeRequest>,
     ) -> Result<Response<Self::SubscribeToServiceStream>, Status> {
+        self.authorize(&request, Action::Read)?;
         let req = request.into_inner();
         let (tx, rx) = tokio::sync::mpsc::channel(128);
         
+        println!("🔌 New event subscription: namespace='{}', service='{}', event_types={:?}", req.namespace, req.service_name, req.event_types);
+        
+        // Join the same broadcast path as the SSE clients
+        let (event_tx, mut event_rx) = tokio::sync::broadcast::channel::<SSEEvent>(100);
+        self.add_event_sender(event_tx).await;
+        
         // Send initial event
         let _ = tx.send(Ok(ServiceEvent {
             event_type: "subscribed".to_string(),
-            service_name: req.service_name,
+            service_name: req.service_name.clone(),
             data: "{}".to_string(),
             timestamp: Utc::now().to_rfc3339(),
+            namespace: req.namespace.clone(),
         })).await;
         
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tx.closed() => {
+                        println!("🔌 Event subscription for '{}' closed by client", req.service_name);
+                        break;
+                    }
+                    result = event_rx.recv() => match result {
+                        Ok(event) => {
+                            if let Some(service_event) = to_service_event(&event, &req) {
+                                if tx.send(Ok(service_event)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                            println!("⚠️  Event subscriber lagged, skipped {} events", skipped);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+        
         Ok(Response::new(ReceiverStream::new(rx)))
     }
 
@@ -681,7 +1967,12 @@ impl GrpcHub for GrpcHubService {
         &self,
         request: Request<UpdateServiceStatusRequest>,
     ) -> Result<Response<UpdateServiceStatusResponse>, Status> {
+        if let Some((namespace, service_name)) = self.instance_scope(&request.get_ref().service_id).await {
+            self.authorize(&request, Action::Manage(&namespace, &service_name))?;
+        }
+        let identity = request.extensions().get::<Identity>().cloned();
         let req = request.into_inner();
+        self.verify_instance_secret(identity.as_ref(), &req.service_id, &req.instance_secret).await?;
         
         println!("🔍 [DEBUG] UpdateServiceStatus: Service {} status -> {}", req.service_id, req.status);
         
@@ -690,6 +1981,7 @@ impl GrpcHub for GrpcHubService {
             if let Some(service) = services.get_mut(&req.service_id) {
                 let old_status = service.status.clone();
                 service.status = req.status.clone();
+                service.busy_with_calls = false;
                 let service_name = service.service_name.clone();
                 
                 println!("🔄 Service {} status changed: {} -> {}", service_name, old_status, req.status);
@@ -715,6 +2007,10 @@ impl GrpcHub for GrpcHubService {
                     "reason": "Service reported status change via gRPC"
                 }).to_string(),
             }).await;
+            
+            if req.status == "online" {
+                self.hand_off_to_queued_call(&req.service_id).await;
+            }
         }
         
         Ok(Response::new(UpdateServiceStatusResponse {
@@ -728,46 +2024,357 @@ async fn start_http_server(
     hub_service: Arc<GrpcHubService>,
     host: String,
     port: u16,
+    tls: Option<Arc<TlsListener>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use hyper::body::Incoming;
-    use hyper::service::service_fn;
-    use hyper::Method;
-    use hyper_util::rt::TokioExecutor;
-    use hyper_util::server::conn::auto::Builder;
-    
     let listener = tokio::net::TcpListener::bind(format!("{}:{}", host, port)).await?;
-    println!("HTTP server listening on http://{}:{}", host, port);
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    println!("HTTP server listening on {}://{}:{}", scheme, host, port);
     
+    serve_http(listener, hub_service, tls).await
+}
+
+async fn serve_http(
+    listener: tokio::net::TcpListener,
+    hub_service: Arc<GrpcHubService>,
+    tls: Option<Arc<TlsListener>>,
+) -> Result<(), Box<dyn std::error::Error>> {
     loop {
-        let (stream, _) = listener.accept().await?;
+        let (stream, peer) = listener.accept().await?;
         let hub_service = hub_service.clone();
+        let acceptor = tls.as_ref().map(|tls| tls.acceptor());
         
         tokio::task::spawn(async move {
-            let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
-                let hub_service = hub_service.clone();
-                async move {
-                    handle_http_request(req, hub_service).await
+            let Some(acceptor) = acceptor else {
+                return serve_http_connection(stream, hub_service, None).await;
+            };
+            match acceptor.accept(stream).await {
+                Ok(stream) => {
+                    let certificate = tls::peer_identity(stream.get_ref().1.peer_certificates().map(|certs| Arc::new(certs.to_vec())));
+                    serve_http_connection(stream, hub_service, certificate).await
                 }
-            });
-            
-            let io = hyper_util::rt::TokioIo::new(stream);
-            
-            if let Err(err) = Builder::new(TokioExecutor::new())
-                .serve_connection(io, service)
-                .await
-            {
-                eprintln!("Error serving connection: {:?}", err);
+                Err(e) => println!("🔐 [TLS] Handshake with {} failed: {}", peer, e),
             }
         });
     }
 }
 
+/// Serve HTTP on one connection, whose client certificate (if any) identified `certificate`
+async fn serve_http_connection<I>(stream: I, hub_service: Arc<GrpcHubService>, certificate: Option<Identity>)
+where
+    I: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioExecutor;
+    use hyper_util::server::conn::auto::Builder;
+    
+    let service = service_fn(move |mut req: hyper::Request<hyper::body::Incoming>| {
+        let hub_service = hub_service.clone();
+        if let Some(certificate) = &certificate {
+            req.extensions_mut().insert(certificate.clone());
+        }
+        async move {
+            handle_http_request(req, hub_service).await
+        }
+    });
+    
+    let io = hyper_util::rt::TokioIo::new(stream);
+    
+    if let Err(err) = Builder::new(TokioExecutor::new())
+        .serve_connection(io, service)
+        .await
+    {
+        eprintln!("Error serving connection: {:?}", err);
+    }
+}
+
 type BoxBody = http_body_util::combinators::BoxBody<hyper::body::Bytes, hyper::Error>;
 
 fn full_response(bytes: hyper::body::Bytes) -> BoxBody {
     http_body_util::Full::new(bytes).map_err(|e| match e {}).boxed()
 }
 
+fn json_response(status: u16, json: serde_json::Value) -> hyper::Response<BoxBody> {
+    hyper::Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(full_response(hyper::body::Bytes::from(json.to_string())))
+        .unwrap()
+}
+
+/// The 401 or 403 response for a request that failed authentication or authorization
+fn access_error_response(error: &GrpcCallError) -> hyper::Response<BoxBody> {
+    let mut response = json_response(error.http_status(), serde_json::json!({ "success": false, "error": error.message }));
+    if error.code == tonic::Code::Unauthenticated {
+        response.headers_mut().insert(hyper::header::WWW_AUTHENTICATE, hyper::header::HeaderValue::from_static("Bearer"));
+    }
+    response
+}
+
+/// Check that the caller may call the service and method an `/api/grpc-call` or `/api/grpc-stream`
+/// body names
+fn authorize_http_call(hub_service: &GrpcHubService, identity: Option<&Identity>, request: &serde_json::Value) -> Result<(), GrpcCallError> {
+    let method = request.get("method").and_then(|v| v.as_str()).unwrap_or_default();
+    let (namespace, service_name) = http_target(hub_service, request);
+    hub_service.rbac.authorize(identity, Action::Call(&namespace, &service_name, method))
+}
+
+/// The namespace and registered service name an `/api/grpc-call` or `/api/grpc-stream` body targets
+fn http_target(hub_service: &GrpcHubService, request: &serde_json::Value) -> (String, String) {
+    let service = request.get("service").and_then(|v| v.as_str()).unwrap_or_default();
+    let namespace = request.get("namespace").and_then(|v| v.as_str()).unwrap_or_default();
+    hub_service.resolve_target(namespace, service)
+        .map(|target| (target.namespace, target.service_name))
+        .unwrap_or_else(|_| (namespace::normalize(namespace).to_string(), short_service_name(service)))
+}
+
+/// A JSON response for a failed call, with a `Retry-After` header when the error says how long to wait
+fn call_error_response(status: u16, error: &GrpcCallError, json: serde_json::Value) -> hyper::Response<BoxBody> {
+    let mut response = json_response(status, json);
+    if let Some(value) = error.metadata.get("retry-after").and_then(|v| hyper::header::HeaderValue::from_str(v).ok()) {
+        response.headers_mut().insert(hyper::header::RETRY_AFTER, value);
+    }
+    response
+}
+
+/// The calling service named by an `/api/grpc-call` body, which its limits are counted against
+fn http_caller(request: &serde_json::Value) -> &str {
+    request.get("caller_service").and_then(|v| v.as_str()).unwrap_or_default()
+}
+
+/// The 429 response for an `/api/grpc-call` call rejected by a limit
+fn limited_call_response(e: &GrpcCallError) -> hyper::Response<BoxBody> {
+    println!("🚦 [LIMIT] Rejected call: {}", e.message);
+    call_error_response(e.http_status(), e, serde_json::json!({
+        "success": false,
+        "error": e.to_string(),
+        "grpc_code": e.code as i32,
+        "grpc_status": format!("{:?}", e.code),
+        "metadata": e.metadata
+    }))
+}
+
+/// A URL-decoded query string parameter
+fn query_param<B>(req: &hyper::Request<B>, name: &str) -> Option<String> {
+    form_urlencoded::parse(req.uri().query()?.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Read and parse a JSON request body, or produce the 400 response to send instead
+async fn read_json_body(req: hyper::Request<hyper::body::Incoming>) -> Result<serde_json::Value, hyper::Response<BoxBody>> {
+    let bytes = match http_body_util::BodyExt::collect(req.into_body()).await {
+        Ok(body) => body.to_bytes(),
+        Err(_) => {
+            return Err(json_response(400, serde_json::json!({
+                "success": false,
+                "error": "Failed to read request body"
+            })));
+        }
+    };
+    
+    serde_json::from_slice(&bytes).map_err(|_| json_response(400, serde_json::json!({
+        "success": false,
+        "error": "Invalid JSON request"
+    })))
+}
+
+/// Where an HTTP call request is routed
+struct CallTarget {
+    /// Registered name of the target service
+    service_name: String,
+    service: String,
+    method: String,
+    host: String,
+    port: u16,
+    /// Keeps the registered instance busy until the call is done
+    guard: Option<BusyGuard>,
+}
+
+/// Resolve the target of an HTTP call request, either from an explicit host and port or by
+/// intelligent selection on the service name (queueing while every instance is busy)
+async fn resolve_call_target(
+    hub_service: &GrpcHubService,
+    identity: Option<&Identity>,
+    request: &serde_json::Value,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<CallTarget, hyper::Response<BoxBody>> {
+    match (
+        request.get("service").and_then(|v| v.as_str()),
+        request.get("method").and_then(|v| v.as_str()),
+        request.get("host").and_then(|v| v.as_str()),
+        request.get("port").and_then(|v| v.as_str().and_then(|s| s.parse::<u16>().ok())),
+    ) {
+        (Some(svc), Some(meth), Some(hst), Some(prt)) => {
+            // Direct addressing mode: host and port provided
+            println!("🔍 [DEBUG] Hub: Looking for service at {}:{}", hst, prt);
+            let service_id = hub_service.get_service_by_address(hst, prt).await;
+            // The instance registered at the address is what gets called, whatever the body names;
+            // only operators may have the hub dial an address no instance is registered at
+            let scope = match &service_id {
+                Some(service_id) => hub_service.instance_scope(service_id).await,
+                None => None,
+            };
+            let authorized = match &scope {
+                Some((namespace, service_name)) => hub_service.rbac.authorize(identity, Action::Call(namespace, service_name, meth)),
+                None => hub_service.rbac.authorize(identity, Action::Operate),
+            };
+            authorized.map_err(|e| access_error_response(&e))?;
+            let guard = match service_id {
+                Some(service_id) => Some(BusyGuard::acquire(hub_service, &service_id).await),
+                None => None,
+            };
+            let service_name = scope.map(|(_, service_name)| service_name);
+            Ok(CallTarget {
+                service_name: service_name.unwrap_or_else(|| short_service_name(svc)),
+                service: svc.to_string(),
+                method: meth.to_string(),
+                host: hst.to_string(),
+                port: prt,
+                guard,
+            })
+        }
+        (Some(svc), Some(meth), None, None) => {
+            // Intelligent selection mode: only service name provided, optionally "namespace/service@range"
+            let namespace = request.get("namespace").and_then(|v| v.as_str()).unwrap_or_default();
+            let target = hub_service.resolve_target(namespace, svc).and_then(|target| {
+                let selector = LabelSelector::parse_optional(request.get("label_selector").and_then(|v| v.as_str()))?;
+                Ok((target, selector))
+            });
+            let (target, selector) = target.map_err(|e| json_response(e.http_status(), serde_json::json!({
+                "success": false,
+                "error": e.message,
+                "grpc_code": e.code as i32,
+                "grpc_status": format!("{:?}", e.code)
+            })))?;
+            
+            println!("🔍 [DEBUG] Hub: Intelligent selection mode for service: {}", target.service_name);
+            
+            let priority = request.get("priority").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+            let headers: HashMap<String, String> = request.get("headers")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default();
+            let input = request.get("input").or_else(|| request.get("inputs").and_then(|v| v.get(0)));
+            let routing = CallRouting { deadline, ..hub_service.call_routing(&target, priority, selector, &headers, input) };
+            match hub_service.acquire_service_by_name(&target.service_name, &routing).await {
+                Ok(instance) => {
+                    println!("🎯 [DEBUG] Hub: Selected service {} at {}:{}", instance.guard.service_id(), instance.host, instance.port);
+                    Ok(CallTarget {
+                        service_name: target.service_name,
+                        service: target.grpc_service,
+                        method: meth.to_string(),
+                        host: instance.host,
+                        port: instance.port,
+                        guard: Some(instance.guard),
+                    })
+                }
+                Err(e) => Err(json_response(e.http_status(), serde_json::json!({
+                    "success": false,
+                    "error": e.message,
+                    "grpc_code": e.code as i32,
+                    "grpc_status": format!("{:?}", e.code)
+                }))),
+            }
+        }
+        _ => {
+            Err(json_response(400, serde_json::json!({
+                "success": false,
+                "error": "Missing required fields: service, method, and either (host, port) or service name for intelligent selection"
+            })))
+        }
+    }
+}
+
+/// Route an `/api/grpc-call` request by service name: to one instance, retried on others as its
+/// policy allows, or fanned out to several
+async fn dispatch_http_call(
+    hub_service: &GrpcHubService,
+    request: &serde_json::Value,
+    mode: &DispatchMode,
+    deadline: Option<tokio::time::Instant>,
+) -> hyper::Response<BoxBody> {
+    let (Some(service), Some(method), None) = (
+        request.get("service").and_then(|v| v.as_str()),
+        request.get("method").and_then(|v| v.as_str()),
+        request.get("host"),
+    ) else {
+        return json_response(400, serde_json::json!({
+            "success": false,
+            "error": "Dispatch modes need a service name and method, without host and port"
+        }));
+    };
+    
+    let namespace = request.get("namespace").and_then(|v| v.as_str()).unwrap_or_default();
+    let target = hub_service.resolve_target(namespace, service).and_then(|target| {
+        let selector = LabelSelector::parse_optional(request.get("label_selector").and_then(|v| v.as_str()))?;
+        Ok((target, selector))
+    });
+    let (target, selector) = match target {
+        Ok(target) => target,
+        Err(e) => return json_response(e.http_status(), serde_json::json!({
+            "success": false,
+            "error": e.message,
+            "grpc_code": e.code as i32
+        })),
+    };
+    let input = request.get("input").cloned().unwrap_or(serde_json::json!({}));
+    let headers: HashMap<String, String> = request.get("headers")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+    let priority = request.get("priority").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+    let _permit = match hub_service.limits.admit(http_caller(request), &target.service_name) {
+        Ok(permit) => permit,
+        Err(e) => return limited_call_response(&e),
+    };
+    let call = DispatchCall {
+        routing: CallRouting { deadline, ..hub_service.call_routing(&target, priority, selector, &headers, Some(&input)) },
+        service_name: target.service_name.clone(),
+        grpc_service: target.grpc_service,
+        method: method.to_string(),
+        input,
+        options: CallOptions { headers, deadline, service_name: target.service_name },
+    };
+    let started = tokio::time::Instant::now();
+    let dispatched = dispatch(hub_service, &call, mode).await;
+    hub_service.record_call_metrics(CallApi::Http, &call.service_name, &call.method, &dispatched.result, started.elapsed()).await;
+    
+    let results: Vec<serde_json::Value> = dispatched.outcomes.iter().map(|outcome| match &outcome.result {
+        Ok(result) => serde_json::json!({
+            "service_id": outcome.service_id,
+            "success": true,
+            "data": result.response,
+            "grpc_code": tonic::Code::Ok as i32
+        }),
+        Err(e) => serde_json::json!({
+            "service_id": outcome.service_id,
+            "success": false,
+            "error": e.to_string(),
+            "grpc_code": e.code as i32
+        }),
+    }).collect();
+    
+    match dispatched.result {
+        Ok(result) => json_response(200, serde_json::json!({
+            "success": true,
+            "data": result.response,
+            "grpc_code": tonic::Code::Ok as i32,
+            "metadata": result.metadata,
+            "results": results
+        })),
+        Err(e) => {
+            // Errors from the instances are reported in-band; failing to reach any instance is not
+            let status = if results.is_empty() { e.http_status() } else { 200 };
+            call_error_response(status, &e, serde_json::json!({
+                "success": false,
+                "error": e.to_string(),
+                "grpc_code": e.code as i32,
+                "grpc_status": format!("{:?}", e.code),
+                "metadata": e.metadata,
+                "results": results
+            }))
+        }
+    }
+}
+
 async fn handle_http_request(
     req: hyper::Request<hyper::body::Incoming>,
     hub_service: Arc<GrpcHubService>,
@@ -778,12 +2385,63 @@ async fn handle_http_request(
     let path = req.uri().path();
     let method = req.method();
     
+    // Everything under /api, and /metrics, needs credentials when authentication is configured
+    let mut identity = None;
+    if path.starts_with("/api/") || path == "/metrics" {
+        let credential = auth::header_credential(req.headers()).map(str::to_string).or_else(|| query_param(&req, "access_token"));
+        // serve_http adds the identity of a verified client certificate
+        let certificate = req.extensions().get::<Identity>().cloned();
+        match hub_service.auth.authenticate_peer(credential.as_deref(), certificate) {
+            Ok(authenticated) => identity = authenticated,
+            Err(e) => {
+                println!("🔒 [AUTH] Rejected {} {}: {}", method, path, e.message);
+                return Ok(access_error_response(&e));
+            }
+        }
+        // Cluster routes read and overwrite any record, so they need an authenticated peer with or without roles
+        if path.starts_with("/api/cluster/") && identity.is_none() {
+            println!("🔒 [AUTH] Rejected {} {} from an unauthenticated caller", method, path);
+            let error = GrpcCallError::new(tonic::Code::Unauthenticated, "Cluster routes need the peer token or a client certificate");
+            return Ok(access_error_response(&error));
+        }
+        // Reads and hub-wide changes are authorized here; calls and instance changes once their target is known
+        let action = match (method, path) {
+            // Snapshots carry instance secrets, so with roles only operators get them too
+            (&Method::GET, "/api/cluster/snapshot") => Some(Action::Operate),
+            (&Method::GET, _) => Some(Action::Read),
+            (&Method::PUT | &Method::DELETE, path) if path.starts_with("/api/traffic-splits/") => Some(Action::Operate),
+            (&Method::POST, "/api/cluster/replicate") => Some(Action::Operate),
+            _ => None,
+        };
+        if let Some(Err(e)) = action.map(|action| hub_service.rbac.authorize(identity.as_ref(), action)) {
+            return Ok(access_error_response(&e));
+        }
+    }
+    
     match (method, path) {
+        // Lists every namespace, service and instance, so it needs the same read access as /api/services
+        (&Method::GET, "/metrics") => {
+            let snapshot = hub_service.metrics_snapshot().await;
+            Ok(hyper::Response::builder()
+                .status(200)
+                .header("content-type", "text/plain; version=0.0.4")
+                .body(full_response(Bytes::from(hub_service.metrics.render(&snapshot))))
+                .unwrap())
+        }
         (&Method::GET, "/api/services") => {
+    // "?selector=environment%3Dproduction" lists only the instances whose metadata matches,
+    // and "?namespace=staging" only those of one namespace
+    let selector = match LabelSelector::parse_optional(query_param(&req, "selector").as_deref()) {
+        Ok(selector) => selector,
+        Err(e) => return Ok(json_response(400, serde_json::json!({ "success": false, "error": e.message }))),
+    };
+    let namespace = query_param(&req, "namespace");
     let services = hub_service.services.read().await;
     let service_list: Vec<grpc_hub::ServiceInfo> = services
         .values()
-        .map(|service| service.clone().into())
+        .filter(|service| selector.as_ref().is_none_or(|selector| selector.matches(&service.metadata)))
+        .filter(|service| namespace.as_deref().is_none_or(|ns| service.namespace == namespace::normalize(ns)))
+        .map(|service| hub_service.service_info(service))
         .collect();
     
     // Convert to a serializable format
@@ -791,6 +2449,7 @@ async fn handle_http_request(
         .into_iter()
                 .map(|service| serde_json::json!({
             "service_id": service.service_id,
+            "namespace": service.namespace,
             "service_name": service.service_name,
             "service_version": service.service_version,
             "service_address": service.service_address,
@@ -800,6 +2459,7 @@ async fn handle_http_request(
             "registered_at": service.registered_at,
             "last_heartbeat": service.last_heartbeat,
                     "status": service.status,
+            "circuit_state": service.circuit_state,
         }))
         .collect();
     
@@ -813,10 +2473,124 @@ async fn handle_http_request(
                 .body(full_response(Bytes::from(json.to_string())))
                 .unwrap())
         }
+        (&Method::GET, "/api/stats") => {
+            let mut instances: HashMap<String, HashMap<String, usize>> = HashMap::new();
+            let mut load_balancing = HashMap::new();
+            for service in hub_service.services.read().await.values() {
+                *instances.entry(qualified_name(&service.namespace, &service.service_name)).or_default()
+                    .entry(service.status.clone()).or_default() += 1;
+                load_balancing.insert(service.service_name.clone(), hub_service.balancers.kind(&service.service_name));
+            }
+            let queue_config = hub_service.call_queue.config();
+            
+            Ok(json_response(200, serde_json::json!({
+                "instances": instances,
+                "load_balancing": load_balancing,
+                "load": hub_service.balancers.loads(),
+                "traffic_splits": hub_service.traffic_splits.stats().await,
+                "circuits": hub_service.breakers.tripped(),
+                "queues": {
+                    "depths": hub_service.call_queue.depths().await,
+                    "max_depth": queue_config.max_depth,
+                    "max_wait_ms": queue_config.max_wait.as_millis() as u64
+                }
+            })))
+        }
+        (&Method::GET, "/api/namespaces") => {
+            let mut instances: HashMap<String, usize> = HashMap::new();
+            for service in hub_service.services.read().await.values() {
+                *instances.entry(service.namespace.clone()).or_default() += 1;
+            }
+            let exports = hub_service.namespaces.exports();
+            let mut names: Vec<&str> = instances.keys().map(String::as_str).chain(exports.keys().copied()).collect();
+            names.sort();
+            names.dedup();
+            let namespaces: Vec<serde_json::Value> = names.into_iter().map(|name| serde_json::json!({
+                "namespace": name,
+                "instances": instances.get(name).copied().unwrap_or(0),
+                "exports": exports.get(name).copied().unwrap_or_default()
+            })).collect();
+            Ok(json_response(200, serde_json::json!({ "namespaces": namespaces })))
+        }
+        (&Method::GET, "/api/traffic-splits") => {
+            Ok(json_response(200, hub_service.traffic_splits.stats().await))
+        }
+        (&Method::PUT, path) if path.starts_with("/api/traffic-splits/") => {
+            let service_name = path.trim_start_matches("/api/traffic-splits/").to_string();
+            let body = match read_json_body(req).await {
+                Ok(body) => body,
+                Err(response) => return Ok(response),
+            };
+            let entries = body.get("versions")
+                .ok_or_else(|| "Missing 'versions'".to_string())
+                .and_then(|v| serde_json::from_value::<Vec<SplitEntry>>(v.clone()).map_err(|e| format!("Invalid 'versions': {}", e)));
+            let result = match entries {
+                Ok(entries) => hub_service.traffic_splits.set(&service_name, entries).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => Ok(json_response(200, serde_json::json!({ "success": true }))),
+                Err(e) => Ok(json_response(400, serde_json::json!({ "success": false, "error": e }))),
+            }
+        }
+        (&Method::DELETE, path) if path.starts_with("/api/traffic-splits/") => {
+            let service_name = path.trim_start_matches("/api/traffic-splits/");
+            let removed = hub_service.traffic_splits.remove(service_name).await;
+            Ok(json_response(200, serde_json::json!({ "success": removed })))
+        }
+        (&Method::GET, "/api/cluster") => {
+            let Some(cluster) = &hub_service.cluster else {
+                return Ok(json_response(200, serde_json::json!({ "clustered": false })));
+            };
+            Ok(json_response(200, serde_json::json!({
+                "clustered": true,
+                "node_id": cluster.node_id(),
+                "peers": cluster.peer_states().await
+            })))
+        }
+        (&Method::GET, "/api/cluster/snapshot") => {
+            if hub_service.cluster.is_none() {
+                return Ok(json_response(409, serde_json::json!({
+                    "success": false,
+                    "error": "This hub is not part of a cluster"
+                })));
+            }
+            Ok(json_response(200, serde_json::json!(hub_service.cluster_snapshot().await)))
+        }
+        (&Method::POST, "/api/cluster/replicate") => {
+            if hub_service.cluster.is_none() {
+                return Ok(json_response(409, serde_json::json!({
+                    "success": false,
+                    "error": "This hub is not part of a cluster"
+                })));
+            }
+            let update = match read_json_body(req).await {
+                Ok(body) => serde_json::from_value::<ReplicationUpdate>(body),
+                Err(response) => return Ok(response),
+            };
+            match update {
+                Ok(update) => {
+                    hub_service.apply_replication(update).await;
+                    Ok(json_response(200, serde_json::json!({ "success": true })))
+                }
+                Err(e) => Ok(json_response(400, serde_json::json!({
+                    "success": false,
+                    "error": format!("Invalid replication update: {}", e)
+                }))),
+            }
+        }
         (&Method::DELETE, path) if path.starts_with("/api/services/") => {
             let service_id = path.trim_start_matches("/api/services/");
-            let mut services = hub_service.services.write().await;
-            let removed = services.remove(service_id);
+            if let Some((namespace, service_name)) = hub_service.instance_scope(service_id).await {
+                if let Err(e) = hub_service.rbac.authorize(identity.as_ref(), Action::Manage(&namespace, &service_name)) {
+                    return Ok(access_error_response(&e));
+                }
+            }
+            let secret = req.headers().get("x-hub-instance-secret").and_then(|v| v.to_str().ok()).unwrap_or_default();
+            if let Err(e) = hub_service.verify_instance_secret(identity.as_ref(), service_id, secret).await {
+                return Ok(access_error_response(&e));
+            }
+            let removed = hub_service.remove_service(service_id).await;
             
             let json = if removed.is_some() {
                 serde_json::json!({"success": true, "message": "Service unregistered successfully"})
@@ -914,12 +2688,23 @@ async fn handle_http_request(
                 }
             };
             
+            if let Some((namespace, service_name)) = hub_service.instance_scope(service_id).await {
+                if let Err(e) = hub_service.rbac.authorize(identity.as_ref(), Action::Manage(&namespace, &service_name)) {
+                    return Ok(access_error_response(&e));
+                }
+            }
+            let secret = request.get("instance_secret").and_then(|v| v.as_str()).unwrap_or_default();
+            if let Err(e) = hub_service.verify_instance_secret(identity.as_ref(), service_id, secret).await {
+                return Ok(access_error_response(&e));
+            }
+            
             // Update service status
             let service_name = {
                 let mut services = hub_service.services.write().await;
                 if let Some(service) = services.get_mut(service_id) {
                     let old_status = service.status.clone();
                     service.status = status.to_string();
+                    service.busy_with_calls = false;
                     let service_name = service.service_name.clone();
                     
                     println!("🔄 Service {} status changed: {} -> {}", service_name, old_status, status);
@@ -946,6 +2731,10 @@ async fn handle_http_request(
                     }).to_string(),
                 }).await;
                 
+                if status == "online" {
+                    hub_service.hand_off_to_queued_call(service_id).await;
+                }
+                
                 let json = serde_json::json!({
                     "success": true,
                     "message": format!("Service {} status updated to {}", service_id, status)
@@ -970,144 +2759,92 @@ async fn handle_http_request(
             }
         }
         (&Method::POST, "/api/grpc-call") => {
-            // Read request body using http-body-util
-            let bytes = match http_body_util::BodyExt::collect(req.into_body()).await {
-                Ok(body) => body.to_bytes(),
-                Err(_) => {
-                    let json = serde_json::json!({
-                        "success": false,
-                        "error": "Failed to read request body"
-                    });
-                    return Ok(hyper::Response::builder()
-                        .status(400)
-                        .header("content-type", "application/json")
-                        .body(full_response(Bytes::from(json.to_string())))
-                        .unwrap());
-                }
+            let request = match read_json_body(req).await {
+                Ok(request) => request,
+                Err(response) => return Ok(response),
             };
-            let body_str = String::from_utf8_lossy(&bytes);
+            if let Err(e) = authorize_http_call(&hub_service, identity.as_ref(), &request) {
+                return Ok(access_error_response(&e));
+            }
+            // "timeout_ms" bounds the whole call, queueing included
+            let deadline = call_deadline(None, request.get("timeout_ms").and_then(|v| v.as_i64()).unwrap_or(0));
             
-            let request: serde_json::Value = match serde_json::from_str(&body_str) {
-                Ok(req) => req,
-                Err(_) => {
-                    let json = serde_json::json!({
-                        "success": false,
-                        "error": "Invalid JSON request"
-                    });
-                    return Ok(hyper::Response::builder()
-                        .status(400)
-                        .header("content-type", "application/json")
-                        .body(full_response(Bytes::from(json.to_string())))
-                        .unwrap());
-                }
+            let mode = match DispatchMode::parse(
+                request.get("dispatch_mode").and_then(|v| v.as_str()).unwrap_or_default(),
+                request.get("quorum").and_then(|v| v.as_i64()).unwrap_or(0),
+                request.get("hedge_delay_ms").and_then(|v| v.as_i64()).unwrap_or(0),
+            ) {
+                Ok(mode) => mode,
+                Err(e) => return Ok(json_response(400, serde_json::json!({ "success": false, "error": e.message }))),
             };
+            // Calls by service name are retried on another instance when one fails
+            let by_name = request.get("host").is_none() && request.get("service").is_some();
+            if mode != DispatchMode::Single || by_name {
+                return Ok(dispatch_http_call(&hub_service, &request, &mode, deadline).await);
+            }
             
-            // Extract request parameters - support both service name only and host+port
-            let (service_name, method_name, host, port, input_data): (String, String, String, u16, serde_json::Value) = match (
-                request.get("service").and_then(|v| v.as_str()),
-                request.get("method").and_then(|v| v.as_str()),
-                request.get("host").and_then(|v| v.as_str()),
-                request.get("port").and_then(|v| v.as_str().and_then(|s| s.parse::<u16>().ok())),
-                request.get("input").cloned(),
-            ) {
-                (Some(svc), Some(meth), Some(hst), Some(prt), inp_data) => {
-                    // Direct addressing mode: host and port provided
-                    (svc.to_string(), meth.to_string(), hst.to_string(), prt, inp_data.unwrap_or(serde_json::json!({})))
-                }
-                (Some(svc), Some(meth), None, None, inp_data) => {
-                    // Intelligent selection mode: only service name provided
-                    // Extract service name from full gRPC service name (e.g., "web_content_extract.WebContentExtract" -> "web-content-extract")
-                    let short_service_name = svc.split('.').next().unwrap_or(svc)
-                        .replace("_", "-")
-                        .to_lowercase();
-                    
-                    println!("🔍 [DEBUG] Hub: Intelligent selection mode for service: {}", short_service_name);
-                    
-                    if let Some((service_id, selected_host, selected_port)) = hub_service.get_best_service_by_name(&short_service_name).await {
-                        println!("🎯 [DEBUG] Hub: Selected service {} at {}:{}", service_id, selected_host, selected_port);
-                        (svc.to_string(), meth.to_string(), selected_host, selected_port, inp_data.unwrap_or(serde_json::json!({})))
-                    } else {
-                        let json = serde_json::json!({
-                            "success": false,
-                            "error": format!("No available service found for '{}'", short_service_name)
-                        });
-                        return Ok(hyper::Response::builder()
-                            .status(404)
-                            .header("content-type", "application/json")
-                            .body(full_response(Bytes::from(json.to_string())))
-                            .unwrap());
-                    }
-                }
-                _ => {
-                    let json = serde_json::json!({
-                        "success": false,
-                        "error": "Missing required fields: service, method, and either (host, port) or service name for intelligent selection"
-                    });
-                    return Ok(hyper::Response::builder()
-                        .status(400)
-                        .header("content-type", "application/json")
-                        .body(full_response(Bytes::from(json.to_string())))
-                        .unwrap());
-                }
+            // Direct addressing mode: the instance at host+port is marked busy until the call is done
+            let service_name = short_service_name(request.get("service").and_then(|v| v.as_str()).unwrap_or_default());
+            let _permit = match hub_service.limits.admit(http_caller(&request), &service_name) {
+                Ok(permit) => permit,
+                Err(e) => return Ok(limited_call_response(&e)),
             };
+            let started = tokio::time::Instant::now();
+            let target = match resolve_call_target(&hub_service, identity.as_ref(), &request, deadline).await {
+                Ok(target) => target,
+                Err(response) => return Ok(response),
+            };
+            let input_data = request.get("input").cloned().unwrap_or(serde_json::json!({}));
             
-            // Set service to busy before making the call
-            println!("🔍 [DEBUG] Hub: Looking for service at {}:{}", host, port);
-            if let Some(service_id) = hub_service.get_service_by_address(&host, port).await {
-                println!("🔍 [DEBUG] Hub: Found service ID: {}, setting to busy", service_id);
-                hub_service.set_service_busy(&service_id).await;
-            } else {
-                println!("❌ [DEBUG] Hub: No service found at {}:{}", host, port);
-            }
+            let headers: HashMap<String, String> = request.get("headers")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default();
             
-            // Call the gRPC method using grpcurl
-            let result = call_grpc_method(
-                &host,
-                port,
-                &service_name,
-                &method_name,
+            let result = hub_service.call_grpc_method(
+                &target.host,
+                target.port,
+                &target.service,
+                &target.method,
                 input_data,
+                &CallOptions { headers, deadline, service_name: target.service_name.clone() },
             ).await;
+            hub_service.record_call_metrics(CallApi::Http, &target.service_name, &target.method, &result, started.elapsed()).await;
+            if let Some(guard) = &target.guard {
+                hub_service.record_call_outcome(guard.service_id(), &result).await;
+            }
             
             let json = match result {
-                Ok(response_data) => {
+                Ok(result) => {
                     // Set service back to online after successful call
-                    if let Some(service_id) = hub_service.get_service_by_address(&host, port).await {
-                        hub_service.set_service_online(&service_id).await;
+                    if let Some(guard) = target.guard {
+                        guard.release().await;
                     }
                     
                     serde_json::json!({
                         "success": true,
-                        "data": response_data
+                        "data": result.response,
+                        "grpc_code": tonic::Code::Ok as i32,
+                        "metadata": result.metadata
                     })
                 }
                 Err(e) => {
-                    let error_msg = e.to_string();
-                    
-                    // Instantly mark service as offline if direct connection to THIS service failed
-                    // Check if the error is about connecting to the target service (not a downstream service)
-                    let is_direct_connection_failure = 
-                        (error_msg.contains("connection refused") || 
-                         error_msg.contains("connection reset") ||
-                         error_msg.contains("connection error")) &&
-                        !error_msg.contains("Web content service") && // Not a downstream service error
-                        !error_msg.contains("unavailable:"); // Not a gRPC service-level error
-                    
-                    if is_direct_connection_failure {
-                        if let Some(service_id) = hub_service.get_service_by_address(&host, port).await {
-                            println!("🔴 [INSTANT] Detected direct service failure at {}:{}", host, port);
-                            hub_service.mark_service_offline(&service_id, "Direct connection failed").await;
-                        }
-                    } else {
-                        // For other errors (including downstream service failures), just set back to online
-                        if let Some(service_id) = hub_service.get_service_by_address(&host, port).await {
-                            hub_service.set_service_online(&service_id).await;
+                    // Instantly mark service as offline if the hub could not reach THIS service;
+                    // errors returned by the service itself (including downstream failures) leave it online
+                    if let Some(guard) = target.guard {
+                        if e.connection_failure {
+                            println!("🔴 [INSTANT] Detected direct service failure at {}:{}", target.host, target.port);
+                            guard.release_offline("Direct connection failed").await;
+                        } else {
+                            guard.release().await;
                         }
                     }
                     
                     serde_json::json!({
                         "success": false,
-                        "error": error_msg
+                        "error": e.to_string(),
+                        "grpc_code": e.code as i32,
+                        "grpc_status": format!("{:?}", e.code),
+                        "metadata": e.metadata
                     })
                 }
             };
@@ -1118,8 +2855,93 @@ async fn handle_http_request(
                 .body(full_response(Bytes::from(json.to_string())))
                 .unwrap())
         }
+        (&Method::POST, "/api/grpc-stream") => {
+            let request = match read_json_body(req).await {
+                Ok(request) => request,
+                Err(response) => return Ok(response),
+            };
+            
+            if let Err(e) = authorize_http_call(&hub_service, identity.as_ref(), &request) {
+                return Ok(access_error_response(&e));
+            }
+            let (_, service_name) = http_target(&hub_service, &request);
+            let permit = match hub_service.limits.admit(http_caller(&request), &service_name) {
+                Ok(permit) => permit,
+                Err(e) => return Ok(limited_call_response(&e)),
+            };
+            let call_target = match resolve_call_target(&hub_service, identity.as_ref(), &request, None).await {
+                Ok(target) => target,
+                Err(response) => return Ok(response),
+            };
+            
+            // "inputs" carries every request message of a client-streaming call; "input" a single one
+            let mut inputs: Vec<serde_json::Value> = match request.get("inputs").and_then(|v| v.as_array()) {
+                Some(inputs) => inputs.clone(),
+                None => vec![request.get("input").cloned().unwrap_or(serde_json::json!({}))],
+            };
+            if inputs.is_empty() {
+                inputs.push(serde_json::json!({}));
+            }
+            let first_input = inputs.remove(0);
+            let headers: HashMap<String, String> = request.get("headers")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default();
+            
+            println!("🌊 [STREAM] HTTP stream {}/{} at {}:{}", call_target.service, call_target.method, call_target.host, call_target.port);
+            
+            let target = StreamTarget {
+                guard: call_target.guard,
+                permit: Some(permit),
+                service_name: call_target.service_name,
+                host: call_target.host,
+                port: call_target.port,
+                service: call_target.service,
+                method: call_target.method,
+                headers,
+            };
+            let mut events = relay_stream(
+                (*hub_service).clone(),
+                target,
+                first_input,
+                tokio_stream::iter(inputs.into_iter().map(Ok)),
+            );
+            
+            // Relay each message as an SSE event; the body ends after "end" or "error"
+            let stream = async_stream::stream! {
+                while let Some(event) = events.recv().await {
+                    let (event_type, data) = match event {
+                        RelayEvent::Message(message) => ("message", message),
+                        RelayEvent::Completed(metadata) => ("end", serde_json::json!({
+                            "success": true,
+                            "grpc_code": tonic::Code::Ok as i32,
+                            "metadata": metadata
+                        })),
+                        RelayEvent::Failed(e) => ("error", serde_json::json!({
+                            "success": false,
+                            "error": e.to_string(),
+                            "grpc_code": e.code as i32,
+                            "grpc_status": format!("{:?}", e.code),
+                            "metadata": e.metadata
+                        })),
+                    };
+                    let message = format!("event: {}\ndata: {}\n\n", event_type, data);
+                    yield Ok::<hyper::body::Frame<Bytes>, hyper::Error>(hyper::body::Frame::data(Bytes::from(message)));
+                }
+            };
+            
+            Ok(hyper::Response::builder()
+                .status(200)
+                .header("content-type", "text/event-stream")
+                .header("cache-control", "no-cache")
+                .header("x-accel-buffering", "no")
+                .header("access-control-allow-origin", "*")
+                .body(BoxBody::new(http_body_util::StreamBody::new(stream)))
+                .unwrap())
+        }
         (&Method::GET, "/api/events") => {
-            println!("🔌 New SSE connection established");
+            // "?namespace=staging" only streams the events of one namespace
+            let namespace = query_param(&req, "namespace").unwrap_or_default();
+            println!("🔌 New SSE connection established (namespace: '{}')", namespace);
             
             // Create a broadcast channel for SSE events
             let (tx, mut rx) = tokio::sync::broadcast::channel::<SSEEvent>(100);
@@ -1151,6 +2973,10 @@ async fn handle_http_request(
                         result = rx.recv() => {
                             match result {
                                 Ok(event) => {
+                                    let data: serde_json::Value = serde_json::from_str(&event.data).unwrap_or_default();
+                                    if !event_in_namespace(&data, &namespace) {
+                                        continue;
+                                    }
                                     let message = format!("event: {}\ndata: {}\n\n", event.event_type, event.data);
                                     println!("📤 Sending SSE message: event={}", event.event_type);
                                     yield Ok::<Frame<hyper::body::Bytes>, hyper::Error>(Frame::data(Bytes::from(message)));
@@ -1200,52 +3026,72 @@ async fn handle_http_request(
 }
 
 async fn cleanup_stale_services(hub_service: Arc<GrpcHubService>) {
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+    let mut interval = tokio::time::interval(hub_service.health_config.sweep_interval);
     
     loop {
         interval.tick().await;
+        expire_leases(&hub_service).await;
+    }
+}
+
+/// Mark services whose lease lapsed offline, and evict those that stayed offline past the grace period
+async fn expire_leases(hub_service: &GrpcHubService) {
+    let now = Utc::now();
+    let mut services = hub_service.services.write().await;
+    
+    let mut events_to_send = Vec::new();
+    let mut evicted_ids = Vec::new();
+    
+    for (service_id, service_info) in services.iter_mut() {
+        let time_since_heartbeat = now - service_info.last_heartbeat;
+        let config = hub_service.health_config.for_service(&service_info.metadata);
+        let heartbeat_ttl = chrono::Duration::from_std(config.heartbeat_ttl).unwrap_or(chrono::Duration::MAX);
+        let eviction_grace = chrono::Duration::from_std(config.eviction_grace).unwrap_or(chrono::Duration::MAX);
         
-        let now = Utc::now();
-        let mut services = hub_service.services.write().await;
-        
-        let mut events_to_send = Vec::new();
-        
-        for (service_id, service_info) in services.iter_mut() {
-            let time_since_heartbeat = now - service_info.last_heartbeat;
+        // Mark services as offline if they haven't sent a heartbeat within the TTL (10 seconds by default)
+        // Services send heartbeats every 7 seconds, so the default gives buffer for network delays
+        if time_since_heartbeat > heartbeat_ttl
+            && (service_info.status == "online" || service_info.status == "recovering")
+        {
+            println!("⚠️  Marking service '{}' as offline (last heartbeat: {}s ago)", 
+                service_info.service_name, 
+                time_since_heartbeat.num_seconds()
+            );
+            let service_name_clone = service_info.service_name.clone();
+            let service_id_clone = service_id.clone();
+            service_info.status = "offline".to_string();
             
-            // Mark services as offline if they haven't sent heartbeat in 10 seconds
-            // Services send heartbeats every 7 seconds, so 10 seconds gives buffer for network delays
-            if time_since_heartbeat > chrono::Duration::seconds(10) {
-                if service_info.status == "online" {
-                    println!("⚠️  Marking service '{}' as offline (last heartbeat: {}s ago)", 
-                        service_info.service_name, 
-                        time_since_heartbeat.num_seconds()
-                    );
-                    let service_name_clone = service_info.service_name.clone();
-                    let service_id_clone = service_id.clone();
-                    service_info.status = "offline".to_string();
-                    
-                    // Collect event to send after releasing lock
-                    events_to_send.push((service_id_clone, service_name_clone));
-                }
-            }
-        }
-        
-        drop(services); // Release lock before async call
-        
-        // Broadcast all status change events
-        for (service_id, service_name) in events_to_send {
-            let event = SSEEvent {
-                event_type: "status_change".to_string(),
-                data: serde_json::json!({
-                    "service_id": service_id,
-                    "service_name": service_name,
-                    "status": "offline"
-                }).to_string(),
-            };
-            hub_service.broadcast_event(event).await;
+            // Collect event to send after releasing lock
+            events_to_send.push((service_id_clone, service_name_clone));
+        } else if service_info.status == "offline" && time_since_heartbeat > heartbeat_ttl + eviction_grace {
+            evicted_ids.push(service_id.clone());
         }
     }
+    
+    let evicted: Vec<ServiceInfo> = evicted_ids.iter().filter_map(|id| services.remove(id)).collect();
+    drop(services); // Release lock before async call
+    
+    // Broadcast all status change events
+    for (service_id, service_name) in events_to_send {
+        let event = SSEEvent {
+            event_type: "status_change".to_string(),
+            data: serde_json::json!({
+                "service_id": service_id,
+                "service_name": service_name,
+                "status": "offline"
+            }).to_string(),
+        };
+        hub_service.broadcast_event(event).await;
+    }
+    
+    for service in evicted {
+        println!("🗑️  Evicting service '{}' (ID: {}): lease lapsed {}s ago",
+            service.service_name,
+            service.service_id,
+            (now - service.last_heartbeat).num_seconds()
+        );
+        hub_service.forget_service(&service, "service_evicted", "evicted").await;
+    }
 }
 
 #[tokio::main]
@@ -1259,7 +3105,78 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("gRPC server: {}", grpc_addr);
     println!("HTTP server: http://{}", http_addr);
     
-    let hub_service = Arc::new(GrpcHubService::default());
+    let mut descriptor_sets = Vec::new();
+    for path in &args.descriptor_sets {
+        descriptor_sets.push(std::fs::read(path)?);
+        println!("📄 Loaded descriptor set {}", path.display());
+    }
+    let grpc_client = DynamicGrpcClient::new(&descriptor_sets)
+        .map_err(|e| format!("Invalid descriptor set: {}", e))?;
+    
+    let health_config = HealthConfig {
+        heartbeat_ttl: std::time::Duration::from_millis(args.heartbeat_ttl_ms),
+        eviction_grace: std::time::Duration::from_millis(args.eviction_grace_ms),
+        sweep_interval: std::time::Duration::from_millis(args.sweep_interval_ms.max(1)),
+        probe_interval: std::time::Duration::from_millis(args.probe_interval_ms),
+        probe_timeout: std::time::Duration::from_millis(args.probe_timeout_ms),
+        failure_threshold: args.probe_failure_threshold,
+        success_threshold: args.probe_success_threshold,
+    };
+    let hub_config = match &args.config {
+        Some(path) => {
+            let config = HubConfig::load(path).map_err(|e| format!("{:#}", e))?;
+            println!("⚙️  Loaded hub config {}", path.display());
+            config
+        }
+        None => HubConfig::default(),
+    };
+    // Peers authenticate to each other, or anyone could rewrite the registry through replication
+    if !args.peers.is_empty() && (!hub_config.auth.is_enabled() || args.peer_token.is_none()) {
+        return Err("--peer needs an auth section in --config and a --peer-token the other hubs accept".into());
+    }
+    let grpc_tls = TlsListener::load(&hub_config.tls, &[b"h2"]).map_err(|e| format!("{:#}", e))?;
+    let http_tls = TlsListener::load(&hub_config.tls, &[b"h2", b"http/1.1"]).map_err(|e| format!("{:#}", e))?;
+    if grpc_tls.is_some() {
+        let clients = if hub_config.tls.require_client_cert { "required" } else if hub_config.tls.client_ca_path.is_some() { "optional" } else { "off" };
+        println!("🔐 TLS on both listeners (client certificates {})", clients);
+    }
+    let backend_tls = BackendTls::load(&hub_config.tls).map_err(|e| format!("{:#}", e))?;
+    
+    let mut hub = GrpcHubService::new(grpc_client.with_tls(backend_tls))
+        .with_balancers(hub_config.load_balancing)
+        .with_traffic_splits(hub_config.traffic_splits)
+        .with_namespaces(hub_config.namespaces)
+        .with_retries(hub_config.retries)
+        .with_circuit_breakers(hub_config.circuit_breaker)
+        .with_limits(hub_config.limits)
+        .with_auth(hub_config.auth)
+        .with_rbac(hub_config.rbac)
+        .with_queue_config(QueueConfig {
+            max_depth: args.queue_max_depth,
+            max_wait: std::time::Duration::from_millis(args.queue_max_wait_ms),
+        })
+        .with_health_config(health_config.clone());
+    if let Some(path) = &args.registry_file {
+        let store = FileRegistryStore::open(path).map_err(|e| format!("{:#}", e))?;
+        hub = hub.with_store(Arc::new(store));
+    }
+    if !args.peers.is_empty() {
+        let node_id = args.node_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+        println!("🔗 Cluster node '{}' with peers {:?}", node_id, args.peers);
+        hub = hub.with_cluster(Cluster::start(ClusterConfig {
+            node_id,
+            peers: args.peers.clone(),
+            sync_interval: std::time::Duration::from_secs(args.cluster_sync_interval_secs),
+            peer_token: args.peer_token.clone(),
+        }));
+    }
+    let hub_service = Arc::new(hub);
+    hub_service.start_cluster_sync();
+    
+    if let Some(path) = &args.registry_file {
+        let restored = hub_service.restore_registry().await.map_err(|e| format!("{:#}", e))?;
+        println!("💾 Registry persisted to {} ({} services restored, recovering until their next heartbeat)", path.display(), restored);
+    }
     
     // Start cleanup task for stale services
     let cleanup_hub = hub_service.clone();
@@ -1268,24 +3185,1058 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
     
     // Start active health monitoring
-    println!("🏥 Starting health monitoring (checks every 5 seconds)");
+    println!("🏥 Starting health monitoring (probes every {:?}, offline after {} failures or {:?} without a heartbeat)",
+        health_config.probe_interval, health_config.failure_threshold, health_config.heartbeat_ttl);
     hub_service.start_health_monitoring().await;
     
     // Start HTTP server in background
     let http_hub = hub_service.clone();
     let http_task = tokio::spawn(async move {
-        if let Err(e) = start_http_server(http_hub, args.http_host, args.http_port).await {
+        if let Err(e) = start_http_server(http_hub, args.http_host, args.http_port, http_tls).await {
             eprintln!("HTTP server error: {}", e);
         }
     });
     
-    // Start gRPC server - clone the service
+    // The hub answers grpc.health.v1 probes for itself
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    health_reporter.set_serving::<GrpcHubServer<GrpcHubService>>().await;
+    
+    // Start gRPC server - the hub's own API, with every other path proxied to registered services
     let grpc_service_clone = (*hub_service).clone();
-    Server::builder()
-        .add_service(GrpcHubServer::new(grpc_service_clone))
-        .serve(grpc_addr.parse()?)
-        .await?;
+    // Every GrpcHub RPC needs credentials when authentication is configured
+    let grpc_hub = GrpcHubServer::with_interceptor(grpc_service_clone, AuthInterceptor::new(hub_service.auth.clone()));
+    let grpc_router = tonic::service::Routes::new(grpc_hub)
+        .add_service(health_service)
+        .into_axum_router()
+        .fallback_service(GrpcProxy::new((*hub_service).clone()));
+    let grpc_server = Server::builder().add_routes(grpc_router.into());
+    match grpc_tls {
+        Some(tls) => {
+            let listener = tokio::net::TcpListener::bind(&grpc_addr).await?;
+            grpc_server.serve_with_incoming(tls.incoming(listener)).await?
+        }
+        None => grpc_server.serve(grpc_addr.parse()?).await?,
+    }
     
     http_task.abort();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_filters_by_service_and_event_type() {
+        let event = SSEEvent {
+            event_type: "status_change".to_string(),
+            data: serde_json::json!({"service_id": "a", "service_name": "dividend", "status": "busy"}).to_string(),
+        };
+        let subscription = |service_name: &str, event_types: &[&str]| SubscribeRequest {
+            service_name: service_name.to_string(),
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+
+        let service_event = to_service_event(&event, &subscription("", &[])).unwrap();
+        assert_eq!(service_event.service_name, "dividend");
+        assert!(to_service_event(&event, &subscription("*", &["status_change"])).is_some());
+        assert!(to_service_event(&event, &subscription("dividend", &["heartbeat"])).is_none());
+        assert!(to_service_event(&event, &subscription("other", &[])).is_none());
+    }
+
+    fn test_hub() -> GrpcHubService {
+        GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap())
+    }
+
+    /// A "dividend" instance at 127.0.0.1:`port`
+    fn registration(port: &str) -> RegisterServiceRequest {
+        RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: port.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Register an instance and return its ID
+    async fn register(hub: &GrpcHubService, registration: RegisterServiceRequest) -> String {
+        hub.register_service(Request::new(registration)).await.unwrap().into_inner().service_id
+    }
+
+    async fn hub_with_instance(queue: QueueConfig) -> GrpcHubService {
+        let hub = test_hub().with_queue_config(queue);
+        register(&hub, registration("1")).await;
+        hub
+    }
+
+    /// The instance `get_best_service_by_name` picks for a "dividend" call
+    async fn picked(hub: &GrpcHubService) -> String {
+        match hub.get_best_service_by_name("dividend", &CallRouting::default()).await {
+            Selection::Instance(service_id, _, _) => service_id,
+            other => panic!("no instance picked: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_busy_service_hands_instance_to_queued_call() {
+        let hub = hub_with_instance(QueueConfig::default()).await;
+        let first = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let hub = hub.clone();
+            async move { hub.acquire_service_by_name("dividend", &CallRouting::default()).await }
+        });
+        while !hub.call_queue.has_waiters("dividend", |_| true).await {
+            tokio::task::yield_now().await;
+        }
+
+        let service_id = first.guard.service_id().to_string();
+        first.guard.release().await;
+        let second = waiting.await.unwrap().unwrap();
+        assert_eq!(second.guard.service_id(), service_id);
+        // Handed over directly, so the instance never went back online in between
+        assert_eq!(hub.services.read().await[&service_id].status, "busy");
+    }
+
+    #[tokio::test]
+    async fn test_instance_no_queued_call_accepts_stays_online() {
+        let hub = test_hub();
+        let version = |version: &str, port: &str| RegisterServiceRequest { service_version: version.to_string(), ..registration(port) };
+        register(&hub, version("2.0.0", "2")).await;
+        let _held = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+        let (_, range) = split_target("dividend@^2").unwrap();
+        let waiting = tokio::spawn({
+            let hub = hub.clone();
+            async move { hub.acquire_service_by_name("dividend", &CallRouting { version: range, ..Default::default() }).await }
+        });
+        while !hub.call_queue.has_waiters("dividend", |_| true).await {
+            tokio::task::yield_now().await;
+        }
+
+        // The queued call only takes version 2, so the new 1.0.0 instance must stay free for others
+        let id = register(&hub, version("1.0.0", "1")).await;
+        assert_eq!(hub.services.read().await[&id].status, "online");
+        assert!(!waiting.is_finished());
+        let other = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+        assert_eq!(other.guard.service_id(), id);
+        waiting.abort();
+    }
+
+    #[tokio::test]
+    async fn test_calls_beyond_the_queue_depth_are_resource_exhausted() {
+        let hub = hub_with_instance(QueueConfig {
+            max_depth: 0,
+            max_wait: std::time::Duration::from_millis(10),
+        })
+        .await;
+        let _held = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+
+        let full = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap_err();
+
+        assert_eq!(full.code, tonic::Code::ResourceExhausted);
+        assert_eq!(full.http_status(), 429);
+    }
+
+    #[tokio::test]
+    async fn test_queued_calls_past_max_wait_are_resource_exhausted() {
+        let hub = hub_with_instance(QueueConfig {
+            max_depth: 1,
+            max_wait: std::time::Duration::from_millis(10),
+        })
+        .await;
+        let _held = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+
+        let timed_out = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap_err();
+
+        assert_eq!(timed_out.code, tonic::Code::ResourceExhausted);
+        assert!(!hub.call_queue.has_waiters("dividend", |_| true).await);
+    }
+
+    #[tokio::test]
+    async fn test_call_deadline_bounds_queueing_and_the_downstream_call() {
+        let hub = hub_with_instance(QueueConfig::default()).await;
+        let held = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+        let routing = CallRouting { deadline: call_deadline(None, 20), ..CallRouting::default() };
+        let queued = hub.acquire_service_by_name("dividend", &routing).await.unwrap_err();
+        assert_eq!(queued.code, tonic::Code::DeadlineExceeded);
+        drop(held);
+
+        // A backend that accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+        hub.register_service(Request::new(RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: port.to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+
+        let mut request = Request::new(ServiceCallRequest {
+            target_service: "dividend".to_string(),
+            method: "GetDividendHistory".to_string(),
+            request_data: "{}".to_string(),
+            timeout_ms: 5_000,
+            ..Default::default()
+        });
+        request.metadata_mut().insert("grpc-timeout", "200m".parse().unwrap());
+        let started = std::time::Instant::now();
+        let response = hub.call_service(request).await.unwrap().into_inner();
+
+        // The shorter grpc-timeout wins, and the instance is free again afterwards
+        assert_eq!(response.grpc_code, tonic::Code::DeadlineExceeded as i32);
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert!(hub.services.read().await.values().all(|s| s.status == "online"));
+    }
+
+    /// A hub with "dividend" 1.0.0 and 2.1.0 instances, and their IDs by version
+    async fn versioned_instances() -> (GrpcHubService, HashMap<&'static str, String>) {
+        let hub = test_hub();
+        let mut ids = HashMap::new();
+        for (version, port) in [("1.0.0", "1"), ("2.1.0", "2")] {
+            let registration = RegisterServiceRequest { service_version: version.to_string(), ..registration(port) };
+            ids.insert(version, register(&hub, registration).await);
+        }
+        (hub, ids)
+    }
+
+    #[tokio::test]
+    async fn test_version_range_picks_a_matching_instance() {
+        let (hub, ids) = versioned_instances().await;
+        let (_, range) = split_target("dividend@^2").unwrap();
+        let routing = CallRouting { version: range, ..Default::default() };
+
+        let instance = hub.acquire_service_by_name("dividend", &routing).await.unwrap();
+
+        assert_eq!(instance.guard.service_id(), ids["2.1.0"]);
+    }
+
+    #[tokio::test]
+    async fn test_version_range_no_instance_satisfies_is_not_found() {
+        let (hub, _) = versioned_instances().await;
+        let (_, range) = split_target("dividend@>=3").unwrap();
+        let routing = CallRouting { version: range, ..Default::default() };
+
+        let missing = hub.acquire_service_by_name("dividend", &routing).await.unwrap_err();
+
+        assert_eq!(missing.code, tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_traffic_split_sends_calls_to_its_versions() {
+        let (hub, ids) = versioned_instances().await;
+        hub.traffic_splits.set("dividend", vec![SplitEntry { version: "1.0.0".to_string(), weight: 1 }]).await.unwrap();
+
+        for _ in 0..4 {
+            let instance = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+            assert_eq!(instance.guard.service_id(), ids["1.0.0"]);
+            instance.guard.release().await;
+        }
+    }
+
+    /// A hub with "dividend" instances labelled production and staging, and their IDs by label
+    async fn labelled_instances() -> (GrpcHubService, HashMap<&'static str, String>) {
+        let hub = test_hub();
+        let mut ids = HashMap::new();
+        for (environment, port) in [("production", "1"), ("staging", "2")] {
+            let registration = RegisterServiceRequest {
+                metadata: HashMap::from([("environment".to_string(), environment.to_string())]),
+                ..registration(port)
+            };
+            ids.insert(environment, register(&hub, registration).await);
+        }
+        (hub, ids)
+    }
+
+    fn selected(selector: &str) -> ListServicesRequest {
+        ListServicesRequest { label_selector: Some(selector.to_string()), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_label_selector_filters_listed_services() {
+        let (hub, ids) = labelled_instances().await;
+
+        let listed = hub.list_services(Request::new(selected("environment in (staging, qa)"))).await.unwrap().into_inner().services;
+
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].service_id, ids["staging"]);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_label_selector_is_invalid_argument() {
+        let (hub, _) = labelled_instances().await;
+
+        let rejected = hub.list_services(Request::new(selected("environment in (staging"))).await.unwrap_err();
+
+        assert_eq!(rejected.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_label_selector_routes_to_matching_instances() {
+        let (hub, ids) = labelled_instances().await;
+        let routing = CallRouting { selector: LabelSelector::parse_optional(Some("environment=production")).unwrap(), ..Default::default() };
+
+        for _ in 0..2 {
+            let instance = hub.acquire_service_by_name("dividend", &routing).await.unwrap();
+            assert_eq!(instance.guard.service_id(), ids["production"]);
+            instance.guard.release().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_label_selector_matching_no_instance_is_not_found() {
+        let (hub, _) = labelled_instances().await;
+        let routing = CallRouting { selector: LabelSelector::parse_optional(Some("!environment")).unwrap(), ..Default::default() };
+
+        let missing = hub.acquire_service_by_name("dividend", &routing).await.unwrap_err();
+
+        assert_eq!(missing.code, tonic::Code::NotFound);
+    }
+
+    /// A hub with "dividend" in the default and staging namespaces, and "auth" exported from
+    /// shared; instance IDs by port
+    async fn namespaced_instances() -> (GrpcHubService, HashMap<&'static str, String>) {
+        let hub = test_hub().with_namespaces(HashMap::from([(
+            "shared".to_string(),
+            namespace::NamespaceConfig { exports: vec!["auth".to_string()] },
+        )]));
+        let mut ids = HashMap::new();
+        for (namespace, service_name, port) in [("", "dividend", "1"), ("staging", "dividend", "2"), ("shared", "auth", "3")] {
+            let registration = RegisterServiceRequest {
+                namespace: namespace.to_string(),
+                service_name: service_name.to_string(),
+                ..registration(port)
+            };
+            ids.insert(port, register(&hub, registration).await);
+        }
+        (hub, ids)
+    }
+
+    /// The instance a call from `caller`'s namespace to `target` gets
+    async fn routed(hub: &GrpcHubService, caller: &str, target: &str) -> Result<String, GrpcCallError> {
+        let target = hub.resolve_target(caller, target)?;
+        let routing = hub.call_routing(&target, 0, None, &HashMap::new(), None);
+        let instance = hub.acquire_service_by_name(&target.service_name, &routing).await?;
+        let service_id = instance.guard.service_id().to_string();
+        instance.guard.release().await;
+        Ok(service_id)
+    }
+
+    #[tokio::test]
+    async fn test_calls_go_to_the_callers_own_namespace() {
+        let (hub, ids) = namespaced_instances().await;
+
+        assert_eq!(routed(&hub, "", "dividend").await.unwrap(), ids["1"]);
+        assert_eq!(routed(&hub, "staging", "dividend").await.unwrap(), ids["2"]);
+    }
+
+    #[tokio::test]
+    async fn test_exported_services_are_reachable_from_other_namespaces() {
+        let (hub, ids) = namespaced_instances().await;
+
+        assert_eq!(routed(&hub, "staging", "shared/auth").await.unwrap(), ids["3"]);
+    }
+
+    #[tokio::test]
+    async fn test_bare_names_do_not_reach_other_namespaces() {
+        let (hub, _) = namespaced_instances().await;
+
+        assert_eq!(routed(&hub, "", "auth").await.unwrap_err().code, tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_unexported_services_in_other_namespaces_are_denied() {
+        let (hub, _) = namespaced_instances().await;
+
+        assert_eq!(routed(&hub, "", "staging/dividend").await.unwrap_err().code, tonic::Code::PermissionDenied);
+    }
+
+    #[test]
+    fn test_subscribers_only_see_events_from_their_namespace() {
+        let event = SSEEvent {
+            event_type: "status_change".to_string(),
+            data: serde_json::json!({"service_id": "staging-dividend", "namespace": "staging", "service_name": "dividend"}).to_string(),
+        };
+        let subscription = |namespace: &str| SubscribeRequest { namespace: namespace.to_string(), ..Default::default() };
+
+        assert_eq!(to_service_event(&event, &subscription("staging")).unwrap().namespace, "staging");
+        assert!(to_service_event(&event, &subscription("*")).is_some());
+        assert!(to_service_event(&event, &subscription("default")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_gather_calls_every_instance_and_releases_them() {
+        let hub = hub_with_instance(QueueConfig::default()).await;
+        hub.register_service(Request::new(RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "2".to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+
+        let response = hub.call_service(Request::new(ServiceCallRequest {
+            target_service: "dividend".to_string(),
+            method: "GetDividendHistory".to_string(),
+            request_data: "{}".to_string(),
+            dispatch_mode: "gather".to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+        // Nothing listens on either port, so both attempts fail and both instances go offline
+        assert!(!response.success);
+        assert_eq!(response.results.len(), 2);
+        assert!(hub.services.read().await.values().all(|s| s.status == "offline"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_call_is_retried_on_another_instance() {
+        // A healthy replica: another hub, whose ListServices any instance of "grpc-hub" can serve
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_port = listener.local_addr().unwrap().port();
+        tokio::spawn(
+            Server::builder()
+                .add_service(GrpcHubServer::new(GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap())))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+        for port in ["1".to_string(), backend_port.to_string()] {
+            hub.register_service(Request::new(RegisterServiceRequest {
+                service_name: "grpc-hub".to_string(),
+                service_address: "127.0.0.1".to_string(),
+                service_port: port,
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        }
+
+        // Round-robin sends one of the two calls to the dead instance first
+        let mut attempts = Vec::new();
+        for _ in 0..2 {
+            let response = hub.call_service(Request::new(ServiceCallRequest {
+                target_service: "grpc_hub.GrpcHub".to_string(),
+                method: "ListServices".to_string(),
+                request_data: "{}".to_string(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+            assert!(response.success, "{}", response.error_message);
+            attempts.push(response.results.len());
+        }
+        assert!(attempts.contains(&2));
+        let services = hub.services.read().await;
+        assert!(services.values().any(|s| s.service_port == "1" && s.status == "offline"));
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_takes_instance_out_of_rotation() {
+        let hub = hub_with_instance(QueueConfig::default()).await
+            .with_circuit_breakers(BreakerConfig { failure_threshold: 2, open_duration_ms: 0 });
+        let (events_tx, mut events) = tokio::sync::broadcast::channel(16);
+        hub.add_event_sender(events_tx).await;
+        let service_id = hub.services.read().await.keys().next().unwrap().clone();
+
+        let failure = Err(GrpcCallError::new(tonic::Code::Unavailable, "overloaded"));
+        hub.record_call_outcome(&service_id, &failure).await;
+        hub.record_call_outcome(&service_id, &failure).await;
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.event_type, "circuit_breaker");
+        assert!(event.data.contains(r#""circuit_state":"open""#));
+
+        let listed = hub.list_services(Request::new(ListServicesRequest::default())).await.unwrap().into_inner();
+        assert_eq!(listed.services[0].circuit_state, "open");
+        assert_eq!(hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap_err().code, tonic::Code::NotFound);
+
+        // Half-open lets a trial call through, and its success closes the circuit
+        hub.half_open_expired_circuits().await;
+        let trial = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+        hub.record_call_outcome(&service_id, &Ok(GrpcCallResult { response: serde_json::json!({}), metadata: HashMap::new() })).await;
+        drop(trial);
+        assert_eq!(hub.breakers.state(&service_id), breaker::CircuitState::Closed);
+    }
+
+    /// One call a second for the "batch-job" caller and for every instance
+    fn rate_limits() -> LimitsConfig {
+        serde_json::from_str(r#"{"callers": {"batch-job": {"rate_per_sec": 1}}, "instances": {"rate_per_sec": 1}}"#).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_callers_over_their_rate_are_rejected_with_retry_after() {
+        // The caller's limit applies before any instance is looked for
+        let hub = test_hub().with_limits(rate_limits());
+        let call = || ServiceCallRequest {
+            caller_service: "batch-job".to_string(),
+            target_service: "dividend".to_string(),
+            method: "GetDividend".to_string(),
+            request_data: "{}".to_string(),
+            ..Default::default()
+        };
+        let first = hub.call_service(Request::new(call())).await.unwrap().into_inner();
+        assert_eq!(first.grpc_code, tonic::Code::NotFound as i32);
+
+        let limited = hub.call_service(Request::new(call())).await.unwrap().into_inner();
+
+        assert_eq!(limited.grpc_code, tonic::Code::ResourceExhausted as i32);
+        assert_eq!(limited.status_code, 429);
+        assert_eq!(limited.metadata["retry-after"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_instances_over_their_rate_get_no_calls_until_it_refills() {
+        let hub = hub_with_instance(QueueConfig::default()).await.with_limits(rate_limits());
+        let instance = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+        instance.guard.release().await;
+
+        let rejected = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap_err();
+
+        assert_eq!(rejected.code, tonic::Code::ResourceExhausted);
+        assert!(rejected.metadata.contains_key("retry-after-ms"));
+    }
+
+    #[tokio::test]
+    async fn test_instances_take_up_to_their_max_in_flight_calls() {
+        let hub = test_hub();
+        let id = register(&hub, RegisterServiceRequest {
+            metadata: HashMap::from([("hub.max_in_flight".to_string(), "2".to_string())]),
+            ..registration("1")
+        })
+        .await;
+
+        let first = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+        assert_eq!(hub.services.read().await[&id].status, "online");
+        let second = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+        assert_eq!(second.guard.service_id(), id);
+        assert_eq!(hub.services.read().await[&id].status, "busy");
+        assert!(matches!(hub.get_best_service_by_name("dividend", &CallRouting::default()).await, Selection::AllBusy));
+
+        first.guard.release().await;
+        assert_eq!(hub.services.read().await[&id].status, "online");
+        assert_eq!(hub.balancers.load(&id).outstanding, 1);
+    }
+
+    /// Two "dividend" instances that take four calls at once each, balanced by `kind`
+    async fn concurrent_instances(kind: balancer::BalancerKind) -> (GrpcHubService, String, String) {
+        let hub = test_hub().with_balancers(balancer::BalancerConfig { default: kind, ..Default::default() });
+        let concurrent = |port: &str| RegisterServiceRequest {
+            metadata: HashMap::from([("hub.max_in_flight".to_string(), "4".to_string())]),
+            ..registration(port)
+        };
+        let first = register(&hub, concurrent("1")).await;
+        let second = register(&hub, concurrent("2")).await;
+        (hub, first, second)
+    }
+
+    #[tokio::test]
+    async fn test_least_outstanding_avoids_instances_with_calls_in_flight() {
+        let (hub, loaded, idle) = concurrent_instances(balancer::BalancerKind::LeastOutstanding).await;
+        let _in_flight = BusyGuard::try_acquire(&hub, &loaded).await.unwrap();
+        for _ in 0..4 {
+            assert_eq!(picked(&hub).await, idle);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_power_of_two_avoids_instances_with_calls_in_flight() {
+        let (hub, loaded, idle) = concurrent_instances(balancer::BalancerKind::PowerOfTwo).await;
+        let _in_flight = BusyGuard::try_acquire(&hub, &loaded).await.unwrap();
+        for _ in 0..4 {
+            assert_eq!(picked(&hub).await, idle);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ewma_latency_avoids_slow_instances() {
+        let (hub, slow, fast) = concurrent_instances(balancer::BalancerKind::EwmaLatency).await;
+        let call = BusyGuard::try_acquire(&hub, &slow).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        call.release().await;
+        BusyGuard::try_acquire(&hub, &fast).await.unwrap().release().await;
+
+        // Still the faster one with a call in flight
+        let _in_flight = BusyGuard::try_acquire(&hub, &fast).await.unwrap();
+        for _ in 0..4 {
+            assert_eq!(picked(&hub).await, fast);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_calls_count_against_limits() {
+        let limits: LimitsConfig = serde_json::from_str(r#"{"callers": {"batch-job": {"max_in_flight": 1}}}"#).unwrap();
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_limits(limits);
+        let _in_flight = hub.limits.admit("batch-job", "dividend").unwrap();
+        let url = serve_test_http(hub).await;
+
+        let body = serde_json::json!({ "service": "dividend", "method": "Watch", "caller_service": "batch-job" });
+        let response = reqwest::Client::new().post(format!("{}/api/grpc-stream", url)).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), 429);
+        assert_eq!(response.headers()["retry-after"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_roles_gate_registration_and_calls() {
+        let rbac: RbacConfig = serde_json::from_str(
+            r#"{"subjects": {"dividend": {"roles": ["registrar"]}, "ops": {"roles": ["operator"]}}}"#,
+        )
+        .unwrap();
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_rbac(rbac);
+        fn as_caller<T>(subject: &str, message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request.extensions_mut().insert(Identity { subject: subject.to_string() });
+            request
+        }
+        let registration = |service_name: &str| RegisterServiceRequest {
+            service_name: service_name.to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "1".to_string(),
+            ..Default::default()
+        };
+
+        let denied = hub.register_service(as_caller("dividend", registration("extract"))).await.unwrap_err();
+        assert_eq!(denied.code(), tonic::Code::PermissionDenied);
+        let registered = hub.register_service(as_caller("dividend", registration("dividend"))).await.unwrap().into_inner();
+        assert!(hub.list_services(as_caller("dividend", ListServicesRequest::default())).await.is_err());
+
+        // Calls are rejected in-band, like other call failures
+        let call = ServiceCallRequest { target_service: "dividend".to_string(), method: "Get".to_string(), request_data: "{}".to_string(), ..Default::default() };
+        let response = hub.call_service(as_caller("ops", call)).await.unwrap().into_inner();
+        assert_eq!(response.grpc_code, tonic::Code::PermissionDenied as i32);
+        assert_eq!(response.status_code, 403);
+
+        let unregister = UnregisterServiceRequest { service_id: registered.service_id, instance_secret: registered.instance_secret };
+        assert!(hub.unregister_service(as_caller("ops", unregister)).await.unwrap().into_inner().success);
+    }
+
+    #[tokio::test]
+    async fn test_direct_calls_to_unregistered_addresses_need_an_operator() {
+        let rbac: RbacConfig = serde_json::from_str(r#"{"subjects": {"*": {"roles": ["caller"], "calls": ["*"]}}}"#).unwrap();
+        let url = serve_test_http(GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_rbac(rbac)).await;
+
+        // The caller may call "dividend", but nothing is registered at the address it names
+        let body = serde_json::json!({ "service": "dividend", "method": "Get", "host": "127.0.0.1", "port": "1" });
+        let response = reqwest::Client::new().post(format!("{}/api/grpc-call", url)).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), 403);
+    }
+
+    #[tokio::test]
+    async fn test_instance_changes_need_the_instance_secret() {
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+        let RegisterServiceResponse { service_id, instance_secret, .. } = hub.register_service(Request::new(RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "1".to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+        assert!(!instance_secret.is_empty());
+
+        let status = |instance_secret: &str| UpdateServiceStatusRequest {
+            service_id: service_id.clone(),
+            status: "offline".to_string(),
+            instance_secret: instance_secret.to_string(),
+        };
+        let impersonated = hub.update_service_status(Request::new(status("guess"))).await.unwrap_err();
+        assert_eq!(impersonated.code(), tonic::Code::PermissionDenied);
+        let heartbeat = HealthCheckRequest { service_id: service_id.clone(), instance_secret: String::new() };
+        assert!(hub.health_check(Request::new(heartbeat)).await.is_err());
+        let unregister = UnregisterServiceRequest { service_id: service_id.clone(), instance_secret: "guess".to_string() };
+        assert!(hub.unregister_service(Request::new(unregister)).await.is_err());
+        assert_eq!(hub.services.read().await[&service_id].status, "online");
+
+        hub.update_service_status(Request::new(status(&instance_secret))).await.unwrap();
+        assert_eq!(hub.services.read().await[&service_id].status, "offline");
+    }
+
+    #[tokio::test]
+    async fn test_registering_a_live_instance_again_needs_its_secret() {
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+        let registration = |instance_secret: &str| RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "1".to_string(),
+            instance_secret: instance_secret.to_string(),
+            ..Default::default()
+        };
+        let first = hub.register_service(Request::new(registration(""))).await.unwrap().into_inner();
+
+        let takeover = hub.register_service(Request::new(registration(""))).await.unwrap_err();
+        assert_eq!(takeover.code(), tonic::Code::PermissionDenied);
+        let again = hub.register_service(Request::new(registration(&first.instance_secret))).await.unwrap().into_inner();
+        assert_eq!(again.service_id, first.service_id);
+        assert_ne!(again.instance_secret, first.instance_secret);
+
+        // A restarted service that lost its secret gets its instance back once it went offline
+        hub.mark_service_offline(&first.service_id, "Heartbeat timeout").await;
+        assert!(hub.register_service(Request::new(registration(""))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_operators_change_instances_without_their_secret() {
+        let rbac: RbacConfig = serde_json::from_str(
+            r#"{"subjects": {"dividend": {"roles": ["registrar"]}, "ops": {"roles": ["operator"]}}}"#,
+        )
+        .unwrap();
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_rbac(rbac);
+        let mut registration = Request::new(RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "1".to_string(),
+            ..Default::default()
+        });
+        registration.extensions_mut().insert(Identity { subject: "dividend".to_string() });
+        let service_id = hub.register_service(registration)
+        .await
+        .unwrap()
+        .into_inner()
+        .service_id;
+
+        let mut status = Request::new(UpdateServiceStatusRequest {
+            service_id: service_id.clone(),
+            status: "offline".to_string(),
+            instance_secret: String::new(),
+        });
+        status.extensions_mut().insert(Identity { subject: "ops".to_string() });
+        hub.update_service_status(status).await.unwrap();
+        assert_eq!(hub.services.read().await[&service_id].status, "offline");
+    }
+
+    #[tokio::test]
+    async fn test_http_unregistration_needs_the_instance_secret() {
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+        let RegisterServiceResponse { service_id, instance_secret, .. } = hub.register_service(Request::new(RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "1".to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+        let url = serve_test_http(hub.clone()).await;
+        let client = reqwest::Client::new();
+
+        let impersonated = client.delete(format!("{}/api/services/{}", url, service_id)).send().await.unwrap();
+        assert_eq!(impersonated.status(), 403);
+        assert!(hub.services.read().await.contains_key(&service_id));
+
+        let unregistered = client.delete(format!("{}/api/services/{}", url, service_id))
+            .header("x-hub-instance-secret", instance_secret)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unregistered.status(), 200);
+        assert!(!hub.services.read().await.contains_key(&service_id));
+    }
+
+    #[tokio::test]
+    async fn test_cluster_snapshot_is_only_served_to_authenticated_peers() {
+        let auth = || AuthConfig { api_keys: HashMap::from([("peer".to_string(), "s3cret".to_string())]), jwt: None };
+        let cluster = Cluster::start(ClusterConfig {
+            node_id: "node-0".to_string(),
+            peers: Vec::new(),
+            sync_interval: std::time::Duration::from_secs(60),
+            peer_token: Some("s3cret".to_string()),
+        });
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_auth(auth()).with_cluster(cluster);
+        let mut registration = Request::new(RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "1".to_string(),
+            ..Default::default()
+        });
+        registration.extensions_mut().insert(Identity { subject: "peer".to_string() });
+        let instance_secret = hub.register_service(registration).await.unwrap().into_inner().instance_secret;
+        let url = serve_test_http(hub).await;
+        let client = reqwest::Client::new();
+
+        let anonymous = client.get(format!("{}/api/cluster/snapshot", url)).send().await.unwrap();
+        assert_eq!(anonymous.status(), 401);
+        assert!(!anonymous.text().await.unwrap().contains(&instance_secret));
+        let peer = client.get(format!("{}/api/cluster/snapshot", url)).bearer_auth("s3cret").send().await.unwrap();
+        assert_eq!(peer.status(), 200);
+
+        // A hub outside a cluster has no snapshot to hand out at all
+        let standalone = serve_test_http(GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_auth(auth())).await;
+        let response = client.get(format!("{}/api/cluster/snapshot", standalone)).bearer_auth("s3cret").send().await.unwrap();
+        assert_eq!(response.status(), 409);
+    }
+
+    #[tokio::test]
+    async fn test_restored_services_recover_on_heartbeat() {
+        let path = std::env::temp_dir().join(format!("grpc-hub-registry-{}.json", Uuid::new_v4()));
+        let store = || Arc::new(FileRegistryStore::open(&path).unwrap());
+
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_store(store());
+        let RegisterServiceResponse { service_id, instance_secret, .. } = hub.register_service(Request::new(RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "1".to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+        // A new hub on the same file gets the instance back, recovering until it heartbeats
+        let restarted = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_store(store());
+        assert_eq!(restarted.restore_registry().await.unwrap(), 1);
+        assert_eq!(restarted.services.read().await[&service_id].status, "recovering");
+
+        // ... and still knows the secret it was issued
+        restarted.health_check(Request::new(HealthCheckRequest { service_id: service_id.clone(), instance_secret }))
+            .await
+            .unwrap();
+        assert_eq!(restarted.services.read().await[&service_id].status, "online");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_probe_failures_hold_service_offline_until_it_recovers() {
+        let hub = hub_with_instance(QueueConfig::default()).await
+            .with_health_config(HealthConfig { failure_threshold: 2, ..HealthConfig::default() });
+        let (service_id, instance_secret) = hub.services.read().await.values()
+            .map(|service| (service.service_id.clone(), service.instance_secret.clone()))
+            .next()
+            .unwrap();
+        let config = hub.health_config.clone();
+        let status = || async { hub.services.read().await[&service_id].status.clone() };
+
+        hub.record_probe(&service_id, false, &config).await;
+        assert_eq!(status().await, "online");
+        hub.record_probe(&service_id, false, &config).await;
+        assert_eq!(status().await, "offline");
+
+        // Heartbeats alone don't bring it back while it is failing its probes
+        let heartbeat = hub.health_check(Request::new(HealthCheckRequest { service_id: service_id.clone(), instance_secret }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!heartbeat.healthy);
+        assert_eq!(status().await, "offline");
+
+        hub.record_probe(&service_id, true, &config).await;
+        assert_eq!(status().await, "online");
+    }
+
+    #[tokio::test]
+    async fn test_lapsed_leases_go_offline_then_get_evicted() {
+        let hub = hub_with_instance(QueueConfig::default()).await.with_health_config(HealthConfig {
+            heartbeat_ttl: std::time::Duration::from_secs(10),
+            eviction_grace: std::time::Duration::from_secs(60),
+            ..HealthConfig::default()
+        });
+        let service_id = hub.services.read().await.keys().next().unwrap().clone();
+        let (sender, mut events) = tokio::sync::broadcast::channel(16);
+        hub.event_senders.write().await.push(sender);
+        let last_heard = |seconds_ago: i64| {
+            let services = hub.services.clone();
+            let service_id = service_id.clone();
+            async move {
+                services.write().await.get_mut(&service_id).unwrap().last_heartbeat = Utc::now() - chrono::Duration::seconds(seconds_ago);
+            }
+        };
+
+        last_heard(20).await;
+        expire_leases(&hub).await;
+        assert_eq!(hub.services.read().await.values().next().unwrap().status, "offline");
+
+        last_heard(80).await;
+        expire_leases(&hub).await;
+        assert!(hub.services.read().await.is_empty());
+
+        assert_eq!(events.recv().await.unwrap().event_type, "status_change");
+        assert_eq!(events.recv().await.unwrap().event_type, "service_evicted");
+    }
+
+    #[tokio::test]
+    async fn test_cluster_replicates_registrations_and_removals() {
+        let listeners = [
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap(),
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap(),
+        ];
+        let urls: Vec<String> = listeners.iter()
+            .map(|l| format!("http://{}", l.local_addr().unwrap()))
+            .collect();
+
+        let mut hubs = Vec::new();
+        for (index, listener) in listeners.into_iter().enumerate() {
+            let hub = Arc::new(GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap())
+                .with_auth(AuthConfig { api_keys: HashMap::from([("peer".to_string(), "s3cret".to_string())]), jwt: None })
+                .with_cluster(Cluster::start(ClusterConfig {
+                    node_id: format!("node-{}", index),
+                    peers: vec![urls[1 - index].clone()],
+                    sync_interval: std::time::Duration::from_secs(60),
+                    peer_token: Some("s3cret".to_string()),
+                })));
+            let server = hub.clone();
+            tokio::spawn(async move {
+                let _ = serve_http(listener, server, None).await;
+            });
+            hubs.push(hub);
+        }
+
+        let service_id = hubs[0].register_service(Request::new(RegisterServiceRequest {
+            service_name: "dividend".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "1".to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+        .service_id;
+
+        let replica = hubs[1].clone();
+        let has_service = |present: bool| {
+            let replica = replica.clone();
+            let service_id = service_id.clone();
+            async move {
+                for _ in 0..100 {
+                    if replica.services.read().await.contains_key(&service_id) == present {
+                        return true;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                }
+                false
+            }
+        };
+        assert!(has_service(true).await);
+
+        hubs[0].remove_service(&service_id).await;
+        assert!(has_service(false).await);
+    }
+
+    /// A hub in a cluster of its own, to feed replicated updates to
+    fn clustered_hub() -> GrpcHubService {
+        test_hub().with_cluster(Cluster::start(ClusterConfig {
+            node_id: "node-0".to_string(),
+            peers: Vec::new(),
+            sync_interval: std::time::Duration::from_secs(60),
+            peer_token: None,
+        }))
+    }
+
+    /// A peer's update setting an instance's `status`, changed at `updated_at`
+    async fn peer_update(hub: &GrpcHubService, service_id: &str, status: &str, updated_at: DateTime<Utc>) -> ReplicationUpdate {
+        let mut record = ServiceRecord::from(&hub.services.read().await[service_id]);
+        record.status = status.to_string();
+        record.updated_at = updated_at;
+        ReplicationUpdate {
+            origin: "node-1".to_string(),
+            service_id: service_id.to_string(),
+            record: Some(record),
+            event_type: "status_change".to_string(),
+            event_data: "{}".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replicated_updates_older_than_the_local_copy_are_ignored() {
+        let hub = clustered_hub();
+        let id = register(&hub, registration("1")).await;
+        let updated_at = hub.services.read().await[&id].updated_at;
+
+        hub.apply_replication(peer_update(&hub, &id, "offline", updated_at - chrono::Duration::seconds(1)).await).await;
+        assert_eq!(hub.services.read().await[&id].status, "online");
+        hub.apply_replication(peer_update(&hub, &id, "offline", updated_at + chrono::Duration::seconds(1)).await).await;
+        assert_eq!(hub.services.read().await[&id].status, "offline");
+    }
+
+    #[tokio::test]
+    async fn test_replicated_online_keeps_an_instance_busy_with_local_calls() {
+        let hub = clustered_hub();
+        let id = register(&hub, registration("1")).await;
+        let _call = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+
+        hub.apply_replication(peer_update(&hub, &id, "online", Utc::now() + chrono::Duration::seconds(1)).await).await;
+        assert_eq!(hub.services.read().await[&id].status, "busy");
+    }
+
+    #[tokio::test]
+    async fn test_instances_busy_with_local_calls_replicate_as_online() {
+        let hub = clustered_hub();
+        let id = register(&hub, registration("1")).await;
+        let updated_at = hub.services.read().await[&id].updated_at;
+        let _call = hub.acquire_service_by_name("dividend", &CallRouting::default()).await.unwrap();
+
+        let record = hub.cluster_snapshot().await.services.remove(0);
+        assert_eq!(record.status, "online");
+        assert_eq!(record.updated_at, updated_at);
+    }
+
+    /// Serve the HTTP API of `hub` on a free port and return its base URL
+    async fn serve_test_http(hub: GrpcHubService) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let _ = serve_http(listener, Arc::new(hub), None).await;
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_cluster_routes_need_an_authenticated_peer() {
+        let url = serve_test_http(GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_cluster(Cluster::start(ClusterConfig {
+            node_id: "node-0".to_string(),
+            peers: Vec::new(),
+            sync_interval: std::time::Duration::from_secs(60),
+            peer_token: None,
+        }))).await;
+
+        // No auth section and no roles, but replication still needs a credential
+        let update = serde_json::json!({
+            "origin": "intruder",
+            "service_id": "forged",
+            "record": null,
+            "event_type": "service_unregistered",
+            "event_data": "{}"
+        });
+        let response = reqwest::Client::new().post(format!("{}/api/cluster/replicate", url)).json(&update).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_need_credentials_when_auth_is_configured() {
+        let auth: AuthConfig = serde_json::from_str(r#"{"api_keys": {"prometheus": "k1"}}"#).unwrap();
+        let url = serve_test_http(test_hub().with_auth(auth)).await;
+
+        let anonymous = reqwest::get(format!("{}/metrics", url)).await.unwrap();
+        assert_eq!(anonymous.status(), 401);
+        let scraper = reqwest::Client::new().get(format!("{}/metrics", url)).bearer_auth("k1").send().await.unwrap();
+        assert_eq!(scraper.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_calls_to_unregistered_services_as_unknown() {
+        let hub = test_hub();
+        let call = ServiceCallRequest {
+            target_service: "made-up".to_string(),
+            method: "Anything".to_string(),
+            request_data: "{}".to_string(),
+            ..Default::default()
+        };
+        hub.call_service(Request::new(call)).await.unwrap();
+
+        let text = hub.metrics.render(&hub.metrics_snapshot().await);
+        assert!(text.contains(r#"grpc_hub_calls_total{api="grpc",service="unknown",method="unknown",code="NotFound"} 1"#));
+        assert!(!text.contains("made-up"));
+    }
+}
@@ -0,0 +1,252 @@
+//! Prometheus metrics about the hub itself, served as text at `GET /metrics`.
+//!
+//! Calls and probes are counted as they happen; instance counts, queue depths, heartbeat
+//! lag and SSE subscribers are read from the registry when scraped:
+//!
+//! - `grpc_hub_instances{namespace, service, status}`
+//! - `grpc_hub_calls_total{api, service, method, code}` and
+//!   `grpc_hub_call_duration_seconds{api, service, method}` for `CallService` (`api="grpc"`)
+//!   and `/api/grpc-call` (`api="http"`). Callers name the method, so one only gets its own
+//!   series once a call to it has succeeded; until then its calls count as `method="unknown"`.
+//! - `grpc_hub_queue_depth{service}`
+//! - `grpc_hub_probes_total{service, outcome}`
+//! - `grpc_hub_heartbeat_lag_seconds{service, service_id}`
+//! - `grpc_hub_sse_subscribers`
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tonic::Code;
+
+use crate::grpc_client::GrpcCallError;
+
+/// Label of the calls to services and methods not known to exist
+pub const UNKNOWN: &str = "unknown";
+
+/// Upper bounds of the call latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Which API a call came in through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CallApi {
+    Grpc,
+    Http,
+}
+
+impl CallApi {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Grpc => "grpc",
+            Self::Http => "http",
+        }
+    }
+}
+
+/// Outcomes and latencies of the calls to one method
+#[derive(Debug, Default)]
+struct CallStats {
+    codes: BTreeMap<String, u64>,
+    /// Calls per latency bucket, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum_secs: f64,
+    count: u64,
+}
+
+/// The hub's state when scraped
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    /// Instances per namespace, service name and status
+    pub instances: BTreeMap<(String, String, String), usize>,
+    /// Calls waiting for a free instance, per service name
+    pub queue_depths: BTreeMap<String, usize>,
+    /// Time since the last heartbeat, per service name and instance
+    pub heartbeat_lag: BTreeMap<(String, String), Duration>,
+    pub sse_subscribers: usize,
+}
+
+/// Counts calls and probes as they happen
+#[derive(Debug, Default)]
+pub struct Metrics {
+    calls: Mutex<BTreeMap<(CallApi, String, String), CallStats>>,
+    probes: Mutex<BTreeMap<(String, bool), u64>>,
+}
+
+impl Metrics {
+    /// Count a call to `service`/`method` that ended with `result` after `elapsed`
+    pub fn record_call<T>(&self, api: CallApi, service: &str, method: &str, result: &Result<T, GrpcCallError>, elapsed: Duration) {
+        let code = match result {
+            Ok(_) => Code::Ok,
+            Err(e) => e.code,
+        };
+        let mut calls = self.calls.lock().unwrap();
+        let mut key = (api, service.to_string(), method.to_string());
+        if code != Code::Ok && !calls.contains_key(&key) {
+            key.2 = UNKNOWN.to_string();
+        }
+        let stats = calls.entry(key).or_default();
+        *stats.codes.entry(format!("{:?}", code)).or_default() += 1;
+        let secs = elapsed.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| secs <= *bound) {
+            stats.buckets[bucket] += 1;
+        }
+        stats.sum_secs += secs;
+        stats.count += 1;
+    }
+
+    /// Count a health probe of an instance of `service`
+    pub fn record_probe(&self, service: &str, healthy: bool) {
+        *self.probes.lock().unwrap().entry((service.to_string(), healthy)).or_default() += 1;
+    }
+
+    /// Everything in the Prometheus text exposition format
+    pub fn render(&self, snapshot: &Snapshot) -> String {
+        let mut out = String::new();
+
+        family(&mut out, "grpc_hub_instances", "gauge", "Registered instances by service and status");
+        for ((namespace, service, status), count) in &snapshot.instances {
+            sample(&mut out, "grpc_hub_instances", &[("namespace", namespace), ("service", service), ("status", status)], *count as f64);
+        }
+
+        let calls = self.calls.lock().unwrap();
+        family(&mut out, "grpc_hub_calls_total", "counter", "Calls routed through CallService and /api/grpc-call, by status code");
+        for ((api, service, method), stats) in calls.iter() {
+            for (code, count) in &stats.codes {
+                let labels = [("api", api.label()), ("service", service), ("method", method), ("code", code)];
+                sample(&mut out, "grpc_hub_calls_total", &labels, *count as f64);
+            }
+        }
+        family(&mut out, "grpc_hub_call_duration_seconds", "histogram", "Latency of calls routed through the hub, queueing and retries included");
+        for ((api, service, method), stats) in calls.iter() {
+            let labels = [("api", api.label()), ("service", service.as_str()), ("method", method.as_str())];
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+                cumulative += count;
+                let le = bound.to_string();
+                sample(&mut out, "grpc_hub_call_duration_seconds_bucket", &[&labels[..], &[("le", le.as_str())]].concat(), cumulative as f64);
+            }
+            sample(&mut out, "grpc_hub_call_duration_seconds_bucket", &[&labels[..], &[("le", "+Inf")]].concat(), stats.count as f64);
+            sample(&mut out, "grpc_hub_call_duration_seconds_sum", &labels, stats.sum_secs);
+            sample(&mut out, "grpc_hub_call_duration_seconds_count", &labels, stats.count as f64);
+        }
+        drop(calls);
+
+        family(&mut out, "grpc_hub_queue_depth", "gauge", "Calls waiting for a free instance");
+        for (service, depth) in &snapshot.queue_depths {
+            sample(&mut out, "grpc_hub_queue_depth", &[("service", service)], *depth as f64);
+        }
+
+        family(&mut out, "grpc_hub_probes_total", "counter", "Active health probes of instances, by outcome");
+        for ((service, healthy), count) in self.probes.lock().unwrap().iter() {
+            let outcome = if *healthy { "healthy" } else { "unhealthy" };
+            sample(&mut out, "grpc_hub_probes_total", &[("service", service), ("outcome", outcome)], *count as f64);
+        }
+
+        family(&mut out, "grpc_hub_heartbeat_lag_seconds", "gauge", "Time since each instance's last heartbeat");
+        for ((service, service_id), lag) in &snapshot.heartbeat_lag {
+            sample(&mut out, "grpc_hub_heartbeat_lag_seconds", &[("service", service), ("service_id", service_id)], lag.as_secs_f64());
+        }
+
+        family(&mut out, "grpc_hub_sse_subscribers", "gauge", "Connected SSE and SubscribeToService subscribers");
+        sample(&mut out, "grpc_hub_sse_subscribers", &[], snapshot.sse_subscribers as f64);
+
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    let labels: Vec<String> = labels.iter().map(|(key, value)| format!("{}=\"{}\"", key, escape(value))).collect();
+    if labels.is_empty() {
+        let _ = writeln!(out, "{} {}", name, value);
+    } else {
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels.join(","), value);
+    }
+}
+
+/// Escape a label value as the exposition format requires
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(metrics: &Metrics, snapshot: &Snapshot) -> Vec<String> {
+        metrics.render(snapshot).lines().map(str::to_string).collect()
+    }
+
+    fn assert_has(lines: &[String], line: &str) {
+        assert!(lines.iter().any(|l| l == line), "missing {}", line);
+    }
+
+    #[test]
+    fn test_calls_are_counted_by_code() {
+        let metrics = Metrics::default();
+        metrics.record_call(CallApi::Grpc, "dividend", "Get", &Ok::<_, GrpcCallError>(()), Duration::from_millis(20));
+        let failed: Result<(), _> = Err(GrpcCallError::new(Code::Unavailable, "down"));
+        metrics.record_call(CallApi::Grpc, "dividend", "Get", &failed, Duration::from_millis(20));
+
+        let lines = rendered(&metrics, &Snapshot::default());
+        assert_has(&lines, r#"grpc_hub_calls_total{api="grpc",service="dividend",method="Get",code="Ok"} 1"#);
+        assert_has(&lines, r#"grpc_hub_calls_total{api="grpc",service="dividend",method="Get",code="Unavailable"} 1"#);
+    }
+
+    #[test]
+    fn test_failed_calls_to_unproven_methods_are_counted_as_unknown() {
+        let metrics = Metrics::default();
+        let failed: Result<(), _> = Err(GrpcCallError::new(Code::Unimplemented, "no such method"));
+        metrics.record_call(CallApi::Http, "dividend", "Made-Up-1", &failed, Duration::from_millis(1));
+        metrics.record_call(CallApi::Http, "dividend", "Made-Up-2", &failed, Duration::from_millis(1));
+
+        let lines = rendered(&metrics, &Snapshot::default());
+        assert_has(&lines, r#"grpc_hub_calls_total{api="http",service="dividend",method="unknown",code="Unimplemented"} 2"#);
+        assert!(!lines.iter().any(|l| l.contains("Made-Up")));
+    }
+
+    #[test]
+    fn test_call_latency_buckets_are_cumulative() {
+        let metrics = Metrics::default();
+        metrics.record_call(CallApi::Grpc, "dividend", "Get", &Ok::<_, GrpcCallError>(()), Duration::from_millis(20));
+        metrics.record_call(CallApi::Grpc, "dividend", "Get", &Ok::<_, GrpcCallError>(()), Duration::from_secs(30));
+
+        let lines = rendered(&metrics, &Snapshot::default());
+        assert_has(&lines, "# TYPE grpc_hub_call_duration_seconds histogram");
+        assert_has(&lines, r#"grpc_hub_call_duration_seconds_bucket{api="grpc",service="dividend",method="Get",le="0.025"} 1"#);
+        assert_has(&lines, r#"grpc_hub_call_duration_seconds_bucket{api="grpc",service="dividend",method="Get",le="10"} 1"#);
+        assert_has(&lines, r#"grpc_hub_call_duration_seconds_bucket{api="grpc",service="dividend",method="Get",le="+Inf"} 2"#);
+    }
+
+    #[test]
+    fn test_probes_are_counted_by_outcome() {
+        let metrics = Metrics::default();
+        metrics.record_probe("dividend", false);
+        assert_has(&rendered(&metrics, &Snapshot::default()), r#"grpc_hub_probes_total{service="dividend",outcome="unhealthy"} 1"#);
+    }
+
+    #[test]
+    fn test_gauges_come_from_the_snapshot() {
+        let snapshot = Snapshot {
+            instances: BTreeMap::from([(("default".to_string(), "dividend".to_string(), "online".to_string()), 2)]),
+            queue_depths: BTreeMap::from([("dividend".to_string(), 3)]),
+            heartbeat_lag: BTreeMap::from([(("dividend".to_string(), "id-1".to_string()), Duration::from_millis(1500))]),
+            sse_subscribers: 1,
+        };
+        let lines = rendered(&Metrics::default(), &snapshot);
+        assert_has(&lines, r#"grpc_hub_instances{namespace="default",service="dividend",status="online"} 2"#);
+        assert_has(&lines, r#"grpc_hub_queue_depth{service="dividend"} 3"#);
+        assert_has(&lines, r#"grpc_hub_heartbeat_lag_seconds{service="dividend",service_id="id-1"} 1.5"#);
+        assert_has(&lines, "grpc_hub_sse_subscribers 1");
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        assert_eq!(escape("a\"b\\"), "a\\\"b\\\\");
+    }
+}
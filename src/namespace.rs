@@ -0,0 +1,120 @@
+//! Namespaces (tenants) in the registry.
+//!
+//! Every instance registers into a namespace, `default` unless it names one. Calls are
+//! only routed to instances in the caller's namespace, and event streams can be scoped
+//! to one namespace. A namespace can export services in the hub config file; callers
+//! in any other namespace reach those as `namespace/service`:
+//!
+//! ```json
+//! { "namespaces": { "shared": { "exports": ["auth-service"] } } }
+//! ```
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tonic::Code;
+
+use crate::grpc_client::GrpcCallError;
+
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NamespaceConfig {
+    /// Service names callers in other namespaces may use
+    pub exports: Vec<String>,
+}
+
+/// The namespace an empty one stands for
+pub fn normalize(namespace: &str) -> &str {
+    match namespace.trim() {
+        "" => DEFAULT_NAMESPACE,
+        namespace => namespace,
+    }
+}
+
+/// Check a namespace name: letters, digits, '-', '_' and '.'
+pub fn validate(namespace: &str) -> Result<(), String> {
+    if namespace.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+        Ok(())
+    } else {
+        Err(format!("Invalid namespace '{}': use letters, digits, '-', '_' and '.'", namespace))
+    }
+}
+
+/// Split `namespace/service` into the namespace (if given) and the service
+pub fn split_namespace(target: &str) -> (Option<&str>, &str) {
+    match target.split_once('/') {
+        Some((namespace, service)) => (Some(namespace), service),
+        None => (None, target),
+    }
+}
+
+/// `service_name` in `namespace` as shown in queues and stats: bare in the default namespace,
+/// `namespace/service_name` elsewhere
+pub fn qualified_name(namespace: &str, service_name: &str) -> String {
+    if namespace == DEFAULT_NAMESPACE {
+        service_name.to_string()
+    } else {
+        format!("{}/{}", namespace, service_name)
+    }
+}
+
+/// The export lists of every configured namespace
+#[derive(Debug, Default)]
+pub struct Namespaces {
+    config: HashMap<String, NamespaceConfig>,
+}
+
+impl Namespaces {
+    pub fn new(config: HashMap<String, NamespaceConfig>) -> Self {
+        Self { config }
+    }
+
+    pub fn is_exported(&self, namespace: &str, service_name: &str) -> bool {
+        self.config.get(namespace).is_some_and(|c| c.exports.iter().any(|e| e == service_name))
+    }
+
+    /// The namespace a call from `caller` to `service_name` (in `target`, if it names one) is routed in
+    pub fn resolve(&self, caller: &str, target: Option<&str>, service_name: &str) -> Result<String, GrpcCallError> {
+        let caller = normalize(caller);
+        match target.map(normalize) {
+            None => Ok(caller.to_string()),
+            Some(target) if target == caller || self.is_exported(target, service_name) => Ok(target.to_string()),
+            Some(target) => Err(GrpcCallError::new(
+                Code::PermissionDenied,
+                format!("Service '{}' is not exported from namespace '{}'", service_name, target),
+            )),
+        }
+    }
+
+    pub fn exports(&self) -> HashMap<&str, &[String]> {
+        self.config.iter().map(|(namespace, c)| (namespace.as_str(), c.exports.as_slice())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_own_and_exported_services() {
+        let namespaces = Namespaces::new(HashMap::from([(
+            "shared".to_string(),
+            NamespaceConfig { exports: vec!["auth-service".to_string()] },
+        )]));
+
+        assert_eq!(namespaces.resolve("", None, "dividend").unwrap(), "default");
+        assert_eq!(namespaces.resolve("staging", None, "dividend").unwrap(), "staging");
+        assert_eq!(namespaces.resolve("staging", Some("staging"), "dividend").unwrap(), "staging");
+        assert_eq!(namespaces.resolve("staging", Some("shared"), "auth-service").unwrap(), "shared");
+        assert_eq!(
+            namespaces.resolve("staging", Some("shared"), "dividend").unwrap_err().code,
+            Code::PermissionDenied
+        );
+        assert_eq!(split_namespace("shared/auth.Auth@^1"), (Some("shared"), "auth.Auth@^1"));
+        assert_eq!(qualified_name("default", "dividend"), "dividend");
+        assert!(validate("team-a.dev").is_ok());
+        assert!(validate("a/b").is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Transparent gRPC reverse proxy on the hub port.
+//!
+//! Any request path the hub does not serve itself (`/package.Service/Method`) is
+//! routed to the best instance of the matching service and forwarded as-is: the
+//! HTTP/2 request and response bodies, headers and trailers are never decoded, so
+//! stock generated clients can simply point at the hub.
+//!
+//! A caller's `grpc-timeout` also bounds the wait for a free instance; the backend gets
+//! whatever is left of it. Calls count against the limits of the caller named in
+//! `x-hub-caller`, and need the same credentials as the hub's own RPCs when authentication
+//! is configured; those credentials are not passed on to the backend. When the caller goes
+//! away the forwarded call is dropped, which resets the stream to the backend and frees the
+//! instance.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::body::Body;
+use http_body_util::BodyExt;
+use hyper::body::{Frame, SizeHint};
+use tonic::transport::server::{TcpConnectInfo, TlsConnectInfo};
+use tonic::Status;
+
+use crate::auth::header_credential;
+use crate::grpc_client::{apply_headers, call_deadline, grpc_timeout_value, metadata_to_map, remaining, GrpcCallError};
+use crate::limits::Permit;
+use crate::rbac::Action;
+use crate::selector::LabelSelector;
+use crate::tls::peer_identity;
+use crate::versions::parse_range;
+use crate::{AcquiredInstance, BusyGuard, CallRouting, GrpcHubService};
+
+/// Fallback service for the hub's gRPC router that forwards unknown paths to backends
+#[derive(Debug, Clone)]
+pub struct GrpcProxy {
+    hub: GrpcHubService,
+}
+
+impl GrpcProxy {
+    pub fn new(hub: GrpcHubService) -> Self {
+        Self { hub }
+    }
+
+    async fn forward(hub: GrpcHubService, mut req: http::Request<Body>) -> http::Response<Body> {
+        let path = req.uri().path().to_string();
+        let deadline = call_deadline(req.headers().get("grpc-timeout").and_then(|v| v.to_str().ok()), 0);
+
+        // "/package.Service/Method" -> "package.Service"
+        let grpc_service = match path.trim_start_matches('/').split_once('/') {
+            Some((service, method)) if !service.is_empty() && !method.is_empty() => service.to_string(),
+            _ => return status_response(Status::unimplemented(format!("Unknown path {}", path))),
+        };
+        let certificate = peer_identity(req.extensions().get::<TlsConnectInfo<TcpConnectInfo>>().and_then(|info| info.peer_certs()));
+        let identity = match hub.auth.authenticate_peer(header_credential(req.headers()), certificate) {
+            Ok(identity) => identity,
+            Err(e) => {
+                println!("🔒 [AUTH] Rejected proxied call to {}: {}", path, e.message);
+                return status_response(Status::new(e.code, e.message));
+            }
+        };
+        // The caller's hub credential is not the backend's business
+        req.headers_mut().remove(http::header::AUTHORIZATION);
+        req.headers_mut().remove("x-api-key");
+        // Calls are routed within the caller's "x-hub-namespace" (default if unset)
+        let namespace = req.headers().get("x-hub-namespace").and_then(|v| v.to_str().ok()).unwrap_or_default();
+        let mut target = match hub.resolve_target(namespace, &grpc_service) {
+            Ok(target) => target,
+            Err(e) => return status_response(Status::new(e.code, e.message)),
+        };
+        let service_name = target.service_name.clone();
+        let method = path.rsplit('/').next().unwrap_or_default();
+        if let Err(e) = hub.rbac.authorize(identity.as_ref(), Action::Call(&target.namespace, &service_name, method)) {
+            return status_response(Status::new(e.code, e.message));
+        }
+
+        println!("🔀 [PROXY] {} -> service '{}' in namespace '{}'", path, service_name, target.namespace);
+
+        // Callers can jump the queue of a busy service with a higher "x-hub-priority"
+        let priority = req
+            .headers()
+            .get("x-hub-priority")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        // ... and limit the call to a semver range with "x-hub-version"
+        if let Some(range) = req.headers().get("x-hub-version").map(|v| v.to_str().unwrap_or_default()) {
+            match parse_range(range) {
+                Ok(range) => target.version = Some(range),
+                Err(e) => return status_response(Status::new(e.code, e.message)),
+            }
+        }
+        // ... or to instances whose metadata matches "x-hub-selector"
+        let selector = req.headers().get("x-hub-selector").map(|v| v.to_str().unwrap_or_default());
+        let selector = match LabelSelector::parse_optional(selector) {
+            Ok(selector) => selector,
+            Err(e) => return status_response(Status::new(e.code, e.message)),
+        };
+        // Protobuf bodies are opaque here, so only header hash keys apply to proxied calls
+        let headers = metadata_to_map(&tonic::metadata::MetadataMap::from_headers(req.headers().clone()));
+        let routing = CallRouting { deadline, ..hub.call_routing(&target, priority, selector, &headers, None) };
+        let caller = headers.get("x-hub-caller").map(String::as_str).unwrap_or_default();
+        let permit = match hub.limits.admit(caller, &service_name) {
+            Ok(permit) => permit,
+            Err(e) => return status_response(error_status(e)),
+        };
+        let AcquiredInstance { guard, host, port } = match hub.acquire_service_by_name(&service_name, &routing).await {
+            Ok(instance) => instance,
+            Err(e) if e.code == tonic::Code::NotFound => {
+                return status_response(Status::unavailable(e.message));
+            }
+            Err(e) => return status_response(error_status(e)),
+        };
+        // Pass on the deadline minus the time spent queueing here
+        if let Some(deadline) = deadline {
+            match grpc_timeout_value(deadline) {
+                Ok(timeout) => {
+                    req.headers_mut().insert("grpc-timeout", http::HeaderValue::from_str(&timeout).unwrap());
+                }
+                Err(e) => {
+                    guard.release().await;
+                    return status_response(Status::new(e.code, e.message));
+                }
+            }
+        }
+
+        let mut channel = match hub.grpc_client.channel(&service_name, &host, port).await {
+            Ok(channel) => channel,
+            Err(e) => return status_response(Status::new(e.code, e.message)),
+        };
+
+        let req = req.map(|body| body.map_err(|e| Status::internal(e.to_string())).boxed_unsync());
+        match tower::ServiceExt::ready(&mut channel).await {
+            Ok(channel) => match tower::Service::call(channel, req).await {
+                // Keep the instance busy until the response body (and its trailers) has been relayed
+                Ok(response) => response.map(|body| Body::new(GuardedBody { inner: body, _guard: guard, _permit: permit })),
+                // The channel gave up at the forwarded grpc-timeout; the backend itself is fine
+                Err(_) if deadline.is_some_and(|deadline| remaining(deadline).is_err()) => {
+                    guard.release().await;
+                    status_response(Status::deadline_exceeded(format!("Deadline exceeded calling '{}'", service_name)))
+                }
+                Err(e) => {
+                    println!("🔴 [PROXY] Forwarding to {}:{} failed: {}", host, port, e);
+                    guard.release_offline("Direct connection failed").await;
+                    status_response(Status::unavailable(format!("Failed to reach '{}': {}", service_name, e)))
+                }
+            },
+            Err(e) => {
+                guard.release_offline("Direct connection failed").await;
+                status_response(Status::unavailable(format!("Failed to reach '{}': {}", service_name, e)))
+            }
+        }
+    }
+}
+
+impl tower::Service<http::Request<Body>> for GrpcProxy {
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let hub = self.hub.clone();
+        Box::pin(async move { Ok(Self::forward(hub, req).await) })
+    }
+}
+
+/// Trailers-only gRPC response carrying `status`
+fn status_response(status: Status) -> http::Response<Body> {
+    status.into_http().map(Body::new)
+}
+
+/// `error` as a status, with its metadata (such as `retry-after`) as trailers
+fn error_status(error: GrpcCallError) -> Status {
+    let mut metadata = tonic::metadata::MetadataMap::new();
+    apply_headers(&mut metadata, &error.metadata);
+    Status::with_metadata(error.code, error.message, metadata)
+}
+
+/// Response body that holds the instance's busy guard and the call's limit permit until the
+/// body is finished or dropped
+struct GuardedBody<B> {
+    inner: B,
+    _guard: BusyGuard,
+    _permit: Permit,
+}
+
+impl<B> hyper::body::Body for GuardedBody<B>
+where
+    B: hyper::body::Body + Unpin,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc_hub::grpc_hub_server::{GrpcHub, GrpcHubServer};
+    use crate::grpc_hub::RegisterServiceRequest;
+    use crate::DynamicGrpcClient;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+    use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+    use tonic_reflection::pb::v1::ServerReflectionRequest;
+
+    async fn serve(router: tonic::service::Routes) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_routes(router)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        port
+    }
+
+    #[tokio::test]
+    async fn test_stock_client_is_proxied_to_registered_service() {
+        // Backend: a reflection server, which registers as service "grpc" (from "grpc.reflection.v1")
+        let reflection = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(tonic_reflection::pb::v1::FILE_DESCRIPTOR_SET)
+            .build_v1()
+            .unwrap();
+        let backend_port = serve(tonic::service::Routes::new(reflection)).await;
+
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+        hub.register_service(tonic::Request::new(RegisterServiceRequest {
+            service_name: "grpc".to_string(),
+            service_version: "1.0.0".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: backend_port.to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+
+        let router = tonic::service::Routes::new(GrpcHubServer::new(hub.clone()))
+            .into_axum_router()
+            .fallback_service(GrpcProxy::new(hub));
+        let hub_port = serve(router.into()).await;
+
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://127.0.0.1:{}", hub_port))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = ServerReflectionClient::new(channel);
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(MessageRequest::ListServices(String::new())),
+        };
+        let mut responses = client
+            .server_reflection_info(tokio_stream::iter(vec![request]))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(responses.message().await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_hub_credentials_are_not_forwarded() {
+        // Backend that remembers the headers it was called with
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(http::HeaderMap::new()));
+        let recorder = seen.clone();
+        let backend = axum::Router::new().fallback(move |headers: http::HeaderMap| async move {
+            *recorder.lock().unwrap() = headers;
+            status_response(Status::ok(""))
+        });
+        let backend_port = serve(backend.into()).await;
+
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap()).with_auth(crate::AuthConfig {
+            api_keys: std::collections::HashMap::from([("client".to_string(), "k3y".to_string())]),
+            jwt: None,
+        });
+        hub.register_service(tonic::Request::new(RegisterServiceRequest {
+            service_name: "echo".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: backend_port.to_string(),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+
+        let response = GrpcProxy::forward(hub, http::Request::builder()
+            .uri("/echo.Echo/Say")
+            .header("authorization", "Bearer k3y")
+            .header("x-hub-caller", "client")
+            .body(Body::empty())
+            .unwrap())
+            .await;
+        assert_eq!(response.headers().get("grpc-status").unwrap(), "0");
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.get("x-hub-caller").unwrap(), "client");
+        assert!(seen.get("authorization").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_service_returns_unavailable() {
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+        let response = GrpcProxy::forward(hub, http::Request::builder()
+            .uri("/missing.Service/Method")
+            .body(Body::empty())
+            .unwrap())
+            .await;
+        assert_eq!(response.headers().get("grpc-status").unwrap(), "14");
+    }
+}
@@ -0,0 +1,233 @@
+//! Parking calls while every instance of a service is busy.
+//!
+//! Each service name has its own queue, ordered by priority (highest first) and
+//! then by arrival. When an instance of the service becomes free it is handed
+//! straight to the call at the head of the queue instead of going back `online`,
+//! so newly arriving calls cannot overtake the ones already waiting. A call can
+//! restrict which instances it accepts (e.g. a version range); an instance is then
+//! handed to the first waiting call that accepts it.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::sync::{oneshot, Mutex};
+
+/// Limits applied to every service's queue
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    /// Most calls that may wait for one service name; further calls are rejected
+    pub max_depth: usize,
+    /// How long a call may wait for an instance before giving up
+    pub max_wait: Duration,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 100,
+            max_wait: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Why a call could not be queued
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueFull {
+    pub depth: usize,
+}
+
+struct Waiter<T, F> {
+    ticket: u64,
+    priority: i32,
+    filter: F,
+    sender: oneshot::Sender<T>,
+}
+
+/// A call's place in a queue; the instance is delivered through `receiver`
+pub struct QueueTicket<T> {
+    pub id: u64,
+    pub receiver: oneshot::Receiver<T>,
+}
+
+/// Per-service-name queues of calls waiting for a free instance; `F` describes the instances a call accepts
+#[derive(Debug)]
+pub struct CallQueue<T, F = ()> {
+    config: QueueConfig,
+    waiters: Mutex<HashMap<String, VecDeque<Waiter<T, F>>>>,
+    next_ticket: AtomicU64,
+}
+
+impl<T, F> std::fmt::Debug for Waiter<T, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Waiter")
+            .field("ticket", &self.ticket)
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
+
+impl<T, F> CallQueue<T, F> {
+    pub fn new(config: QueueConfig) -> Self {
+        Self {
+            config,
+            waiters: Mutex::new(HashMap::new()),
+            next_ticket: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &QueueConfig {
+        &self.config
+    }
+
+    /// Join the queue for `service_name`, behind every call of equal or higher priority
+    pub async fn enqueue(&self, service_name: &str, priority: i32, filter: F) -> Result<QueueTicket<T>, QueueFull> {
+        let mut waiters = self.waiters.lock().await;
+        let queue = waiters.entry(service_name.to_string()).or_default();
+        // Callers that went away while waiting don't count towards the depth
+        queue.retain(|w| !w.sender.is_closed());
+        if queue.len() >= self.config.max_depth {
+            return Err(QueueFull { depth: queue.len() });
+        }
+
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        let position = queue.iter().position(|w| w.priority < priority).unwrap_or(queue.len());
+        queue.insert(position, Waiter { ticket, priority, filter, sender });
+
+        Ok(QueueTicket { id: ticket, receiver })
+    }
+
+    /// Leave the queue; returns false if the call was already handed an instance
+    pub async fn cancel(&self, service_name: &str, ticket: u64) -> bool {
+        let mut waiters = self.waiters.lock().await;
+        let Some(queue) = waiters.get_mut(service_name) else {
+            return false;
+        };
+        let removed = match queue.iter().position(|w| w.ticket == ticket) {
+            Some(position) => queue.remove(position).is_some(),
+            None => false,
+        };
+        if queue.is_empty() {
+            waiters.remove(service_name);
+        }
+        removed
+    }
+
+    /// Hand `item` to the first waiting call that is still listening and whose filter `accepts` it.
+    /// Gives the item back if no such call is waiting.
+    pub async fn hand_off(&self, service_name: &str, mut item: T, accepts: impl Fn(&F) -> bool) -> Result<(), T> {
+        let mut waiters = self.waiters.lock().await;
+        let Some(queue) = waiters.get_mut(service_name) else {
+            return Err(item);
+        };
+        // Callers that went away while waiting are dropped on the way
+        queue.retain(|w| !w.sender.is_closed());
+        while let Some(position) = queue.iter().position(|w| accepts(&w.filter)) {
+            let waiter = queue.remove(position).expect("position is in range");
+            match waiter.sender.send(item) {
+                Ok(()) => {
+                    if queue.is_empty() {
+                        waiters.remove(service_name);
+                    }
+                    return Ok(());
+                }
+                // That caller gave up; try the next one
+                Err(returned) => item = returned,
+            }
+        }
+        if queue.is_empty() {
+            waiters.remove(service_name);
+        }
+        Err(item)
+    }
+
+    /// Whether a call that is still listening and whose filter `accepts` an item is waiting for `service_name`
+    pub async fn has_waiters(&self, service_name: &str, accepts: impl Fn(&F) -> bool) -> bool {
+        self.waiters
+            .lock()
+            .await
+            .get(service_name)
+            .is_some_and(|queue| queue.iter().any(|w| !w.sender.is_closed() && accepts(&w.filter)))
+    }
+
+    /// Number of calls waiting for each service name
+    pub async fn depths(&self) -> HashMap<String, usize> {
+        self.waiters
+            .lock()
+            .await
+            .iter()
+            .map(|(name, queue)| (name.clone(), queue.len()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(max_depth: usize) -> CallQueue<&'static str, u32> {
+        CallQueue::new(QueueConfig {
+            max_depth,
+            max_wait: Duration::from_secs(1),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_hand_off_follows_priority_then_arrival() {
+        let queue = queue(10);
+        let first = queue.enqueue("svc", 0, 0).await.unwrap();
+        let second = queue.enqueue("svc", 0, 0).await.unwrap();
+        let urgent = queue.enqueue("svc", 5, 0).await.unwrap();
+
+        queue.hand_off("svc", "a", |_| true).await.unwrap();
+        queue.hand_off("svc", "b", |_| true).await.unwrap();
+        queue.hand_off("svc", "c", |_| true).await.unwrap();
+
+        assert_eq!(urgent.receiver.await.unwrap(), "a");
+        assert_eq!(first.receiver.await.unwrap(), "b");
+        assert_eq!(second.receiver.await.unwrap(), "c");
+        assert_eq!(queue.hand_off("svc", "d", |_| true).await, Err("d"));
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_and_abandoned_waiters() {
+        let queue = queue(2);
+        let gone = queue.enqueue("svc", 0, 0).await.unwrap();
+        let waiting = queue.enqueue("svc", 0, 0).await.unwrap();
+        assert_eq!(queue.enqueue("svc", 0, 0).await.err(), Some(QueueFull { depth: 2 }));
+
+        // A caller that dropped its ticket is skipped
+        drop(gone);
+        queue.hand_off("svc", "a", |_| true).await.unwrap();
+        assert_eq!(waiting.receiver.await.unwrap(), "a");
+
+        let cancelled = queue.enqueue("svc", 0, 0).await.unwrap();
+        assert!(queue.cancel("svc", cancelled.id).await);
+        assert!(queue.depths().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hand_off_skips_calls_that_reject_the_item() {
+        let queue = queue(10);
+        let picky = queue.enqueue("svc", 5, 2).await.unwrap();
+        let any = queue.enqueue("svc", 0, 0).await.unwrap();
+
+        assert!(!queue.has_waiters("svc", |&wanted| wanted == 1).await);
+        queue.hand_off("svc", "v1", |&wanted| wanted == 0 || wanted == 1).await.unwrap();
+        assert_eq!(any.receiver.await.unwrap(), "v1");
+        assert_eq!(queue.hand_off("svc", "v1", |&wanted| wanted == 1).await, Err("v1"));
+        queue.hand_off("svc", "v2", |&wanted| wanted == 2).await.unwrap();
+        assert_eq!(picky.receiver.await.unwrap(), "v2");
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_waiters_are_not_counted() {
+        let queue = queue(10);
+        let gone = queue.enqueue("svc", 0, 0).await.unwrap();
+        assert!(queue.has_waiters("svc", |_| true).await);
+
+        drop(gone);
+        assert!(!queue.has_waiters("svc", |_| true).await);
+    }
+}
@@ -0,0 +1,284 @@
+//! Role-based authorization of the hub's gRPC and HTTP APIs.
+//!
+//! With an `rbac` section in the hub config file, every `GrpcHub` RPC, `/api/*` route and
+//! proxied call is checked against the roles granted to its caller (the API key name or
+//! JWT subject it authenticated as):
+//!
+//! - `reader` lists and gets services, watches events, reads stats and scrapes `/metrics`
+//! - `registrar` registers, heartbeats, unregisters and sets the status of instances of the
+//!   services in `services` (the caller's own name if empty)
+//! - `operator` unregisters or sets the status of any instance, changes traffic splits,
+//!   replicates between clustered hubs and calls instances by explicit host and port
+//! - `caller` calls the `service/method` pairs in `calls`
+//!
+//! Services are named `service` in the default namespace or `namespace/service` in another,
+//! so calls are `service/method` or `namespace/service/method`. `*` matches any namespace,
+//! service or method, and on its own anything at all.
+//!
+//! ```json
+//! {
+//!   "rbac": {
+//!     "subjects": {
+//!       "dividend-service": { "roles": ["registrar", "caller"], "calls": ["web-content-extract/*"] },
+//!       "staging-deployer": { "roles": ["registrar"], "services": ["staging/*"] },
+//!       "ops-dashboard": { "roles": ["reader", "operator"] },
+//!       "*": { "roles": ["reader"] }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! The `*` entry's grants apply to every caller, authenticated or not.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Deserialize;
+use tonic::Code;
+
+use crate::auth::Identity;
+use crate::grpc_client::GrpcCallError;
+use crate::namespace::{qualified_name, DEFAULT_NAMESPACE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Reader,
+    Registrar,
+    Operator,
+    Caller,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Grant {
+    pub roles: Vec<Role>,
+    /// Services a registrar may register, as `[namespace/]service`; its own name if empty
+    pub services: Vec<String>,
+    /// `[namespace/]service/method` a caller may call
+    pub calls: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RbacConfig {
+    /// Grants per caller, or "*" for every caller
+    pub subjects: HashMap<String, Grant>,
+}
+
+impl RbacConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (subject, grant) in &self.subjects {
+            if let Some(call) = grant.calls.iter().find(|call| *call != "*" && !(1..=2).contains(&call.matches('/').count())) {
+                return Err(format!("Invalid call '{}' for '{}'; expected \"[namespace/]service/method\"", call, subject));
+            }
+            if let Some(service) = grant.services.iter().find(|service| service.matches('/').count() > 1) {
+                return Err(format!("Invalid service '{}' for '{}'; expected \"[namespace/]service\"", service, subject));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a request does, as far as authorization goes
+#[derive(Debug, Clone, Copy)]
+pub enum Action<'a> {
+    /// List, get or watch services, or read stats
+    Read,
+    /// Register or heartbeat an instance of a service name, in a namespace
+    Register(&'a str, &'a str),
+    /// Unregister or set the status of an instance of a service name, in a namespace
+    Manage(&'a str, &'a str),
+    /// Change hub-wide settings such as traffic splits, replicate from a peer, or call an
+    /// address no instance is registered at
+    Operate,
+    /// Call a method of a service name, in a namespace
+    Call(&'a str, &'a str, &'a str),
+}
+
+impl fmt::Display for Action<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read => write!(f, "read"),
+            Self::Register(namespace, service) => write!(f, "register '{}'", qualified_name(namespace, service)),
+            Self::Manage(namespace, service) => write!(f, "manage '{}'", qualified_name(namespace, service)),
+            Self::Operate => write!(f, "operate the hub"),
+            Self::Call(namespace, service, method) => write!(f, "call '{}/{}'", qualified_name(namespace, service), method),
+        }
+    }
+}
+
+impl Grant {
+    fn allows(&self, subject: &str, action: &Action) -> bool {
+        let has = |role| self.roles.contains(&role);
+        let registers = |namespace: &str, service: &str| {
+            has(Role::Registrar)
+                && if self.services.is_empty() {
+                    namespace == DEFAULT_NAMESPACE && service == subject
+                } else {
+                    self.services.iter().any(|s| names_service(s, namespace, service))
+                }
+        };
+        match action {
+            Action::Read => has(Role::Reader),
+            Action::Register(namespace, service) => registers(namespace, service),
+            Action::Manage(namespace, service) => has(Role::Operator) || registers(namespace, service),
+            Action::Operate => has(Role::Operator),
+            Action::Call(namespace, service, method) => has(Role::Caller) && self.calls.iter().any(|call| {
+                let (s, m) = call.rsplit_once('/').unwrap_or(("*", "*"));
+                names_service(s, namespace, service) && (m == "*" || m == *method)
+            }),
+        }
+    }
+}
+
+/// Whether `pattern`, `[namespace/]service` with either part possibly `*`, names `service` in
+/// `namespace`; a bare service is in the default namespace, and a bare `*` is any service anywhere
+fn names_service(pattern: &str, namespace: &str, service: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    let (ns, name) = pattern.split_once('/').unwrap_or((DEFAULT_NAMESPACE, pattern));
+    (ns == "*" || ns == namespace) && (name == "*" || name == service)
+}
+
+/// Checks actions against the roles in the config
+#[derive(Debug, Default)]
+pub struct Authorizer {
+    config: RbacConfig,
+}
+
+impl Authorizer {
+    pub fn new(config: RbacConfig) -> Self {
+        Self { config }
+    }
+
+    /// Whether the caller `identity` may do `action`; PERMISSION_DENIED if not. Everything is
+    /// allowed when no roles are configured.
+    pub fn authorize(&self, identity: Option<&Identity>, action: Action) -> Result<(), GrpcCallError> {
+        if self.config.subjects.is_empty() {
+            return Ok(());
+        }
+        let subject = identity.map_or("", |identity| identity.subject.as_str());
+        let allowed = [self.config.subjects.get(subject), self.config.subjects.get("*")]
+            .into_iter()
+            .flatten()
+            .any(|grant| grant.allows(subject, &action));
+        if allowed {
+            return Ok(());
+        }
+        let caller = if subject.is_empty() { "anonymous caller" } else { subject };
+        println!("⛔ [RBAC] Denied {} to {}", action, caller);
+        Err(GrpcCallError::new(Code::PermissionDenied, format!("'{}' may not {}", caller, action)))
+    }
+
+    /// Whether the caller `identity` was granted `role`, itself or through `*`. Unlike
+    /// `authorize`, nobody holds any role when no roles are configured.
+    pub fn holds(&self, identity: Option<&Identity>, role: Role) -> bool {
+        let subject = identity.map_or("", |identity| identity.subject.as_str());
+        [self.config.subjects.get(subject), self.config.subjects.get("*")]
+            .into_iter()
+            .flatten()
+            .any(|grant| grant.roles.contains(&role))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "dividend" registers and calls, "ops" operates, and everyone reads
+    fn authorizer() -> Authorizer {
+        let config: RbacConfig = serde_json::from_str(
+            r#"{"subjects": {
+                "dividend": { "roles": ["registrar", "caller"], "calls": ["extract/*", "quotes/Get"] },
+                "ops": { "roles": ["operator"] },
+                "*": { "roles": ["reader"] }
+            }}"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        Authorizer::new(config)
+    }
+
+    fn subject(name: &str) -> Identity {
+        Identity { subject: name.to_string() }
+    }
+
+    #[test]
+    fn test_wildcard_roles_apply_to_anonymous_callers() {
+        let rbac = authorizer();
+        assert!(rbac.authorize(None, Action::Read).is_ok());
+        assert!(rbac.authorize(None, Action::Call("default", "extract", "Extract")).is_err());
+    }
+
+    #[test]
+    fn test_registrars_register_and_manage_only_their_own_name() {
+        let rbac = authorizer();
+        let dividend = subject("dividend");
+        assert!(rbac.authorize(Some(&dividend), Action::Register("default", "dividend")).is_ok());
+        assert!(rbac.authorize(Some(&dividend), Action::Manage("default", "dividend")).is_ok());
+        assert_eq!(rbac.authorize(Some(&dividend), Action::Register("default", "extract")).unwrap_err().code, Code::PermissionDenied);
+    }
+
+    #[test]
+    fn test_callers_call_only_the_methods_granted() {
+        let rbac = authorizer();
+        let dividend = subject("dividend");
+        assert!(rbac.authorize(Some(&dividend), Action::Call("default", "extract", "Extract")).is_ok());
+        assert!(rbac.authorize(Some(&dividend), Action::Call("default", "quotes", "Get")).is_ok());
+        assert!(rbac.authorize(Some(&dividend), Action::Call("default", "quotes", "Delete")).is_err());
+    }
+
+    #[test]
+    fn test_operators_manage_any_service_but_register_none() {
+        let rbac = authorizer();
+        let ops = subject("ops");
+        assert!(rbac.authorize(Some(&ops), Action::Manage("default", "dividend")).is_ok());
+        assert!(rbac.authorize(Some(&ops), Action::Register("default", "dividend")).is_err());
+    }
+
+    #[test]
+    fn test_only_operators_operate() {
+        let rbac = authorizer();
+        assert!(rbac.authorize(Some(&subject("ops")), Action::Operate).is_ok());
+        assert!(rbac.authorize(Some(&subject("dividend")), Action::Operate).is_err());
+    }
+
+    #[test]
+    fn test_everything_is_allowed_without_roles_configured() {
+        assert!(Authorizer::default().authorize(None, Action::Operate).is_ok());
+    }
+
+    #[test]
+    fn test_call_grants_must_name_a_method() {
+        let invalid: RbacConfig = serde_json::from_str(r#"{"subjects": {"x": {"calls": ["extract"]}}}"#).unwrap();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_grants_are_scoped_to_namespaces() {
+        let config: RbacConfig = serde_json::from_str(
+            r#"{"subjects": {
+                "dividend": { "roles": ["registrar"] },
+                "staging": { "roles": ["registrar", "caller"], "services": ["staging/*"], "calls": ["staging/*/*", "*/auth/Check"] }
+            }}"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let rbac = Authorizer::new(config);
+        let dividend = Identity { subject: "dividend".to_string() };
+        let staging = Identity { subject: "staging".to_string() };
+
+        // A registrar's own name is only its own in the default namespace
+        assert!(rbac.authorize(Some(&dividend), Action::Register("default", "dividend")).is_ok());
+        assert!(rbac.authorize(Some(&dividend), Action::Register("payments", "dividend")).is_err());
+        assert!(rbac.authorize(Some(&dividend), Action::Manage("payments", "dividend")).is_err());
+
+        assert!(rbac.authorize(Some(&staging), Action::Register("staging", "dividend")).is_ok());
+        assert!(rbac.authorize(Some(&staging), Action::Register("default", "dividend")).is_err());
+        assert!(rbac.authorize(Some(&staging), Action::Call("staging", "quotes", "Get")).is_ok());
+        assert!(rbac.authorize(Some(&staging), Action::Call("shared", "auth", "Check")).is_ok());
+        assert!(rbac.authorize(Some(&staging), Action::Call("default", "quotes", "Get")).is_err());
+    }
+}
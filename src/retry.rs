@@ -0,0 +1,189 @@
+//! Retrying failed calls on another instance.
+//!
+//! Each service name has a retry policy from the hub config file. A single-instance
+//! call that fails is retried on a different instance, after an exponential backoff
+//! with jitter, until it succeeds or runs out of attempts:
+//!
+//! ```json
+//! {
+//!   "retries": {
+//!     "default": { "max_attempts": 3 },
+//!     "services": {
+//!       "dividend-service": { "max_attempts": 4, "retryable_codes": ["UNAVAILABLE", "DEADLINE_EXCEEDED"], "idempotent": true }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! A call that never reached its target is always safe to retry. One the target answered
+//! with an error is only retried for an `idempotent` service, as it may already have
+//! done the work.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rand::Rng;
+use serde::Deserialize;
+use tonic::Code;
+
+use crate::grpc_client::GrpcCallError;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included; 1 disables retries
+    pub max_attempts: u32,
+    /// gRPC status names, e.g. "UNAVAILABLE", retried for idempotent services
+    pub retryable_codes: Vec<String>,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub backoff_multiplier: f64,
+    /// Whether calls may be repeated after the target answered with an error
+    pub idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            retryable_codes: vec!["UNAVAILABLE".to_string()],
+            initial_backoff_ms: 50,
+            max_backoff_ms: 1000,
+            backoff_multiplier: 2.0,
+            idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("max_attempts must be at least 1".to_string());
+        }
+        if self.backoff_multiplier < 1.0 {
+            return Err("backoff_multiplier must be at least 1".to_string());
+        }
+        match self.retryable_codes.iter().find(|name| code_from_name(name).is_none()) {
+            Some(name) => Err(format!("Unknown gRPC status code '{}'", name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether a call that failed with `error` may be tried again
+    pub fn should_retry(&self, error: &GrpcCallError) -> bool {
+        error.connection_failure
+            || (self.idempotent && self.retryable_codes.iter().any(|name| code_from_name(name) == Some(error.code)))
+    }
+
+    /// How long to wait after failed attempt number `attempt` (from 1): exponential, capped
+    /// at `max_backoff_ms`, with up to half of it taken off at random
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self.initial_backoff_ms as f64 * self.backoff_multiplier.powi(attempt.saturating_sub(1) as i32);
+        let capped = exponential.min(self.max_backoff_ms as f64);
+        Duration::from_millis((capped * rand::thread_rng().gen_range(0.5..=1.0)) as u64)
+    }
+}
+
+/// Which retry policy each service name uses
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    pub default: RetryPolicy,
+    pub services: HashMap<String, RetryPolicy>,
+}
+
+impl RetryConfig {
+    pub fn policy(&self, service_name: &str) -> &RetryPolicy {
+        self.services.get(service_name).unwrap_or(&self.default)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.default.validate().map_err(|e| format!("Invalid default retry policy: {}", e))?;
+        for (service, policy) in &self.services {
+            policy.validate().map_err(|e| format!("Invalid retry policy for '{}': {}", service, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// The gRPC status code named like "DEADLINE_EXCEEDED"
+pub fn code_from_name(name: &str) -> Option<Code> {
+    (0..=16).map(Code::from_i32).find(|code| {
+        // Debug names are CamelCase ("DeadlineExceeded")
+        let debug = format!("{:?}", code);
+        let mut upper = String::new();
+        for (i, c) in debug.chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                upper.push('_');
+            }
+            upper.push(c.to_ascii_uppercase());
+        }
+        upper == name.trim().to_ascii_uppercase()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A call that never reached the instance
+    fn unreachable() -> GrpcCallError {
+        let mut error = GrpcCallError::new(Code::Unavailable, "connection refused");
+        error.connection_failure = true;
+        error
+    }
+
+    /// A call the instance answered with an error
+    fn answered() -> GrpcCallError {
+        GrpcCallError::new(Code::Unavailable, "overloaded")
+    }
+
+    #[test]
+    fn test_code_names_parse_in_any_case() {
+        assert_eq!(code_from_name("DEADLINE_EXCEEDED"), Some(Code::DeadlineExceeded));
+        assert_eq!(code_from_name("unavailable"), Some(Code::Unavailable));
+        assert_eq!(code_from_name("NOPE"), None);
+    }
+
+    #[test]
+    fn test_connection_failures_are_retried() {
+        assert!(RetryPolicy::default().should_retry(&unreachable()));
+    }
+
+    #[test]
+    fn test_answered_calls_are_retried_only_when_idempotent() {
+        assert!(!RetryPolicy::default().should_retry(&answered()));
+        let idempotent = RetryPolicy { idempotent: true, ..RetryPolicy::default() };
+        assert!(idempotent.should_retry(&answered()));
+    }
+
+    #[test]
+    fn test_codes_outside_the_retryable_list_are_not_retried() {
+        let idempotent = RetryPolicy { idempotent: true, ..RetryPolicy::default() };
+        assert!(!idempotent.should_retry(&GrpcCallError::new(Code::InvalidArgument, "bad")));
+    }
+
+    #[test]
+    fn test_backoff_grows_up_to_its_cap() {
+        let policy = RetryPolicy::default();
+        assert!(policy.backoff(1) <= Duration::from_millis(50));
+        let backoff = policy.backoff(10);
+        assert!(backoff >= Duration::from_millis(500) && backoff <= Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_services_override_the_default_policy() {
+        let config: RetryConfig = serde_json::from_str(
+            r#"{"services": {"dividend": {"max_attempts": 1, "retryable_codes": ["ABORTED"]}}}"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.policy("dividend").max_attempts, 1);
+        assert_eq!(config.policy("other"), &RetryPolicy::default());
+    }
+
+    #[test]
+    fn test_unknown_retryable_codes_are_invalid() {
+        assert!(RetryPolicy { retryable_codes: vec!["NOPE".to_string()], ..RetryPolicy::default() }.validate().is_err());
+    }
+}
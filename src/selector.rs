@@ -0,0 +1,165 @@
+//! Kubernetes-style label selectors over registration metadata.
+//!
+//! A selector is a comma-separated list of requirements that must all hold:
+//! `environment=production` (also `==`), `tier!=canary`, `team in (finance,ops)`,
+//! `team notin (legacy)`, `purpose` (the key is set) and `!deprecated` (it isn't).
+//! As in Kubernetes, `!=` and `notin` also match instances without the key.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use tonic::Code;
+
+use crate::grpc_client::GrpcCallError;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    In(String, Vec<String>),
+    NotIn(String, Vec<String>),
+    Exists(String),
+    NotExists(String),
+}
+
+impl Requirement {
+    fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        match self {
+            Self::Equals(key, value) => labels.get(key) == Some(value),
+            Self::NotEquals(key, value) => labels.get(key) != Some(value),
+            Self::In(key, values) => labels.get(key).is_some_and(|v| values.contains(v)),
+            Self::NotIn(key, values) => labels.get(key).is_none_or(|v| !values.contains(v)),
+            Self::Exists(key) => labels.contains_key(key),
+            Self::NotExists(key) => !labels.contains_key(key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+    source: String,
+}
+
+impl LabelSelector {
+    /// Parse a selector; an empty one matches everything
+    pub fn parse(selector: &str) -> Result<Self, GrpcCallError> {
+        let invalid = |reason: &str| {
+            GrpcCallError::new(Code::InvalidArgument, format!("Invalid label selector '{}': {}", selector, reason))
+        };
+
+        let mut requirements = Vec::new();
+        for part in split_requirements(selector).map_err(&invalid)? {
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+            let requirement = if let Some(key) = part.strip_prefix('!') {
+                Requirement::NotExists(label(key).ok_or_else(|| invalid("bad key after '!'"))?)
+            } else if let Some((key, value)) = part.split_once("!=") {
+                Requirement::NotEquals(label(key).ok_or_else(|| invalid("bad key"))?, value.trim().to_string())
+            } else if let Some((key, value)) = part.split_once("==").or_else(|| part.split_once('=')) {
+                Requirement::Equals(label(key).ok_or_else(|| invalid("bad key"))?, value.trim().to_string())
+            } else if let Some((key, operator, values)) = set_requirement(part) {
+                let key = label(key).ok_or_else(|| invalid("bad key"))?;
+                match operator {
+                    "in" => Requirement::In(key, values),
+                    _ => Requirement::NotIn(key, values),
+                }
+            } else {
+                Requirement::Exists(label(part).ok_or_else(|| invalid(&format!("can't parse '{}'", part)))?)
+            };
+            requirements.push(requirement);
+        }
+
+        Ok(Self { requirements, source: selector.trim().to_string() })
+    }
+
+    /// `None` for a missing or empty selector, so callers can skip filtering
+    pub fn parse_optional(selector: Option<&str>) -> Result<Option<Self>, GrpcCallError> {
+        match selector.map(str::trim) {
+            Some(selector) if !selector.is_empty() => Self::parse(selector).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.requirements.iter().all(|r| r.matches(labels))
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Split on the commas between requirements, leaving those inside `( )` sets alone
+fn split_requirements(selector: &str) -> Result<Vec<&str>, &'static str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in selector.char_indices() {
+        match c {
+            '(' if depth == 0 => depth = 1,
+            '(' => return Err("nested '('"),
+            ')' if depth == 1 => depth = 0,
+            ')' => return Err("unbalanced ')'"),
+            ',' if depth == 0 => {
+                parts.push(&selector[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err("unbalanced '('");
+    }
+    parts.push(&selector[start..]);
+    Ok(parts)
+}
+
+/// `key in (a, b)` or `key notin (a, b)`
+fn set_requirement(part: &str) -> Option<(&str, &str, Vec<String>)> {
+    let (head, values) = part.strip_suffix(')')?.split_once('(')?;
+    let mut words = head.split_whitespace();
+    let (key, operator) = (words.next()?, words.next()?);
+    if words.next().is_some() || !matches!(operator, "in" | "notin") {
+        return None;
+    }
+    let values = values.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect();
+    Some((key, operator, values))
+}
+
+/// A trimmed metadata key, if it is one
+fn label(key: &str) -> Option<String> {
+    let key = key.trim();
+    let valid = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || "-_./".contains(c));
+    valid.then(|| key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector_requirements() {
+        let labels = HashMap::from([
+            ("environment".to_string(), "production".to_string()),
+            ("team".to_string(), "finance".to_string()),
+        ]);
+        let matches = |selector: &str| LabelSelector::parse(selector).unwrap().matches(&labels);
+
+        assert!(matches("environment=production,team in (finance, ops),!deprecated"));
+        assert!(matches("environment==production, team, tier!=canary, team notin (legacy)"));
+        assert!(matches(""));
+        assert!(!matches("environment=staging"));
+        assert!(!matches("team in (ops)"));
+        assert!(!matches("purpose"));
+        assert!(!matches("!team"));
+        assert!(!matches("team notin (finance)"));
+
+        for bad in ["team in (a", "environment=production)", "team in (a,(b))", "bad key"] {
+            assert_eq!(LabelSelector::parse(bad).unwrap_err().code, Code::InvalidArgument, "{}", bad);
+        }
+    }
+}
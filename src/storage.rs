@@ -0,0 +1,142 @@
+//! Durable storage for the service registry.
+//!
+//! The hub writes every registration to a [`RegistryStore`] and removes it again on
+//! unregistration, so a restarted hub can reload its instances instead of waiting
+//! for each service to re-register. Statuses and heartbeats are not stored: reloaded
+//! instances start out `recovering` until their first heartbeat arrives.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The persisted part of a registered instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredService {
+    pub service_id: String,
+    /// Registries written before namespaces existed hold only default-namespace instances
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    pub service_name: String,
+    pub service_version: String,
+    pub service_address: String,
+    pub service_port: String,
+    pub methods: Vec<String>,
+    pub metadata: HashMap<String, String>,
+    pub registered_at: DateTime<Utc>,
+    /// Issued at registration; registries written before secrets existed hold none, and their
+    /// instances are not asked for one until they register again
+    #[serde(default)]
+    pub instance_secret: String,
+}
+
+fn default_namespace() -> String {
+    crate::namespace::DEFAULT_NAMESPACE.to_string()
+}
+
+/// A storage backend for registered instances
+pub trait RegistryStore: std::fmt::Debug + Send + Sync {
+    /// Every stored instance
+    fn load(&self) -> anyhow::Result<Vec<StoredService>>;
+
+    /// Insert or replace an instance
+    fn save(&self, service: &StoredService) -> anyhow::Result<()>;
+
+    /// Forget an instance
+    fn remove(&self, service_id: &str) -> anyhow::Result<()>;
+}
+
+/// Keeps the registry in a JSON file, rewritten atomically on every change
+#[derive(Debug)]
+pub struct FileRegistryStore {
+    path: PathBuf,
+    services: Mutex<HashMap<String, StoredService>>,
+}
+
+impl FileRegistryStore {
+    /// Open the store at `path`, reading it if it already exists
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let services = if path.exists() {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read registry file {}", path.display()))?;
+            let services: Vec<StoredService> = serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid registry file {}", path.display()))?;
+            services.into_iter().map(|s| (s.service_id.clone(), s)).collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            path,
+            services: Mutex::new(services),
+        })
+    }
+
+    fn write(&self, services: &HashMap<String, StoredService>) -> anyhow::Result<()> {
+        let mut entries: Vec<&StoredService> = services.values().collect();
+        entries.sort_by_key(|s| s.registered_at);
+
+        // Write to a temporary file first so a crash never leaves a truncated registry behind
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_vec_pretty(&entries)?)
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl RegistryStore for FileRegistryStore {
+    fn load(&self) -> anyhow::Result<Vec<StoredService>> {
+        Ok(self.services.lock().unwrap().values().cloned().collect())
+    }
+
+    fn save(&self, service: &StoredService) -> anyhow::Result<()> {
+        let mut services = self.services.lock().unwrap();
+        services.insert(service.service_id.clone(), service.clone());
+        self.write(&services)
+    }
+
+    fn remove(&self, service_id: &str) -> anyhow::Result<()> {
+        let mut services = self.services.lock().unwrap();
+        if services.remove(service_id).is_some() {
+            self.write(&services)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_store_survives_reopening() {
+        let path = std::env::temp_dir().join(format!("grpc-hub-registry-{}.json", uuid::Uuid::new_v4()));
+        let service = StoredService {
+            service_id: "id-1".to_string(),
+            namespace: "finance".to_string(),
+            service_name: "dividend-service".to_string(),
+            service_version: "1.0.0".to_string(),
+            service_address: "127.0.0.1".to_string(),
+            service_port: "8083".to_string(),
+            methods: vec!["GetDividendHistory".to_string()],
+            metadata: HashMap::from([("team".to_string(), "finance".to_string())]),
+            registered_at: Utc::now(),
+            instance_secret: "s3cret".to_string(),
+        };
+
+        let store = FileRegistryStore::open(&path).unwrap();
+        store.save(&service).unwrap();
+        store.save(&StoredService { service_id: "id-2".to_string(), ..service.clone() }).unwrap();
+        store.remove("id-2").unwrap();
+
+        let reopened = FileRegistryStore::open(&path).unwrap();
+        assert_eq!(reopened.load().unwrap(), vec![service]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
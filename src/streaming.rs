@@ -0,0 +1,229 @@
+//! Relaying streaming calls (server, client and bidirectional) through the hub.
+//!
+//! Both `CallServiceStream` and `POST /api/grpc-stream` use [`relay_stream`]:
+//! JSON inputs are transcoded and forwarded as they arrive, and every response
+//! message is handed back as soon as the target produces it. The target stays
+//! `busy`, and the call counts against its limits, for the whole lifetime of the stream.
+
+use std::collections::HashMap;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::grpc_client::{json_to_message, message_to_json, metadata_to_map, GrpcCallError};
+use crate::limits::Permit;
+use crate::{BusyGuard, GrpcHubService};
+
+/// The instance and method a streaming call is routed to
+#[derive(Debug)]
+pub struct StreamTarget {
+    /// Keeps the registered instance (if any) busy for the lifetime of the stream
+    pub guard: Option<BusyGuard>,
+    /// Holds the call's place under its caller and service limits for the lifetime of the stream
+    pub permit: Option<Permit>,
+    /// Registered name of the target service, whose backend TLS settings apply
+    pub service_name: String,
+    pub host: String,
+    pub port: u16,
+    pub service: String,
+    pub method: String,
+    pub headers: HashMap<String, String>,
+}
+
+/// Something that happened on a relayed stream
+#[derive(Debug)]
+pub enum RelayEvent {
+    /// A response message from the target
+    Message(serde_json::Value),
+    /// The target finished the stream successfully; carries response headers and trailers
+    Completed(HashMap<String, String>),
+    /// The call failed; no further events follow
+    Failed(GrpcCallError),
+}
+
+/// Start relaying a streaming call and return the receiver for its events.
+///
+/// `first_input` is sent immediately, and `inputs` supplies any further request
+/// messages (for client-streaming and bidirectional methods); an error from
+/// `inputs` aborts the call. Dropping the receiver cancels the downstream call.
+pub fn relay_stream<S>(
+    hub_service: GrpcHubService,
+    mut target: StreamTarget,
+    first_input: serde_json::Value,
+    inputs: S,
+) -> mpsc::Receiver<RelayEvent>
+where
+    S: Stream<Item = Result<serde_json::Value, GrpcCallError>> + Send + Unpin + 'static,
+{
+    let (tx, rx) = mpsc::channel(32);
+
+    tokio::spawn(async move {
+        let guard = target.guard.take();
+        // Dropped when the relay ends, which frees the call's place under its limits
+        let _permit = target.permit.take();
+
+        match run_relay(&hub_service, &target, first_input, inputs, &tx).await {
+            Ok(()) => {}
+            Err(e) => {
+                println!("❌ [STREAM] {}/{} at {}:{} failed: {}", target.service, target.method, target.host, target.port, e);
+                if e.connection_failure {
+                    if let Some(guard) = guard {
+                        guard.release_offline("Direct connection failed").await;
+                    }
+                }
+                let _ = tx.send(RelayEvent::Failed(e)).await;
+            }
+        }
+    });
+
+    rx
+}
+
+async fn run_relay<S>(
+    hub_service: &GrpcHubService,
+    target: &StreamTarget,
+    first_input: serde_json::Value,
+    mut inputs: S,
+    tx: &mpsc::Sender<RelayEvent>,
+) -> Result<(), GrpcCallError>
+where
+    S: Stream<Item = Result<serde_json::Value, GrpcCallError>> + Send + Unpin + 'static,
+{
+    let client = &hub_service.grpc_client;
+    let descriptor = client
+        .resolve_method(&target.service_name, &target.host, target.port, &target.service, &target.method)
+        .await?;
+
+    let (request_tx, request_rx) = mpsc::channel(32);
+    request_tx
+        .send(json_to_message(descriptor.input(), first_input)?)
+        .await
+        .map_err(|_| GrpcCallError::new(tonic::Code::Internal, "Request stream closed"))?;
+
+    // Transcode further inputs as they arrive; a bad message aborts the whole call
+    let (input_error_tx, mut input_error_rx) = tokio::sync::oneshot::channel();
+    let input_descriptor = descriptor.input();
+    tokio::spawn(async move {
+        while let Some(input) = inputs.next().await {
+            match input.and_then(|input| json_to_message(input_descriptor.clone(), input)) {
+                Ok(message) => {
+                    if request_tx.send(message).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = input_error_tx.send(e);
+                    break;
+                }
+            }
+        }
+    });
+
+    let (mut metadata, mut responses) = client
+        .call_streaming(&target.service_name, &target.host, target.port, &descriptor, ReceiverStream::new(request_rx), &target.headers)
+        .await?;
+
+    let mut inputs_open = true;
+    loop {
+        tokio::select! {
+            result = &mut input_error_rx, if inputs_open => match result {
+                Ok(e) => return Err(e),
+                Err(_) => inputs_open = false,
+            },
+            _ = tx.closed() => {
+                // Caller went away: dropping `responses` cancels the downstream call
+                println!("🔌 [STREAM] Caller disconnected from {}/{}", target.service, target.method);
+                return Ok(());
+            }
+            message = responses.message() => match message.map_err(GrpcCallError::from_status)? {
+                Some(message) => {
+                    if tx.send(RelayEvent::Message(message_to_json(&message)?)).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+
+    if let Some(trailers) = responses.trailers().await.map_err(GrpcCallError::from_status)? {
+        metadata.extend(metadata_to_map(&trailers));
+    }
+    let _ = tx.send(RelayEvent::Completed(metadata)).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DynamicGrpcClient;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    async fn reflection_backend() -> u16 {
+        let reflection = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(tonic_reflection::pb::v1::FILE_DESCRIPTOR_SET)
+            .build_v1()
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(reflection)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        port
+    }
+
+    fn target(port: u16) -> StreamTarget {
+        StreamTarget {
+            guard: None,
+            permit: None,
+            service_name: "reflection".to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            service: "grpc.reflection.v1.ServerReflection".to_string(),
+            method: "ServerReflectionInfo".to_string(),
+            headers: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bidi_stream_relays_every_message() {
+        let port = reflection_backend().await;
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+
+        let list = serde_json::json!({"listServices": ""});
+        let mut events = relay_stream(hub, target(port), list.clone(), tokio_stream::iter(vec![Ok(list)]));
+
+        let mut messages = 0;
+        while let Some(event) = events.recv().await {
+            match event {
+                RelayEvent::Message(message) => {
+                    assert!(message.get("listServicesResponse").is_some());
+                    messages += 1;
+                }
+                RelayEvent::Completed(_) => break,
+                RelayEvent::Failed(e) => panic!("stream failed: {}", e),
+            }
+        }
+        assert_eq!(messages, 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_input_fails_the_stream() {
+        let port = reflection_backend().await;
+        let hub = GrpcHubService::new(DynamicGrpcClient::new(&[]).unwrap());
+
+        let inputs = tokio_stream::iter(vec![Ok(serde_json::json!({"noSuchField": 1}))]);
+        let mut events = relay_stream(hub, target(port), serde_json::json!({"listServices": ""}), inputs);
+
+        let mut failed = None;
+        while let Some(event) = events.recv().await {
+            if let RelayEvent::Failed(e) = event {
+                failed = Some(e);
+            }
+        }
+        assert_eq!(failed.unwrap().code, tonic::Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,388 @@
+//! TLS for the hub's listeners and for its calls to backends.
+//!
+//! With `cert_path` and `key_path` (PEM) in the `tls` section of the hub config file, the
+//! gRPC and HTTP listeners only speak TLS. `client_ca_path` adds mutual TLS: clients may
+//! present a certificate issued by that CA (or must, with `require_client_cert`), and a
+//! caller that did and sent no other credential is identified by the certificate's first
+//! URI or DNS subject alternative name. `backends` sets, per service name, that the hub
+//! calls its instances over TLS, trusting `ca_path` (or the webpki roots), presenting
+//! `cert_path`/`key_path` if set and expecting `domain` (or the instance's address):
+//!
+//! ```json
+//! {
+//!   "tls": {
+//!     "cert_path": "/etc/grpc-hub/hub.pem",
+//!     "key_path": "/etc/grpc-hub/hub-key.pem",
+//!     "client_ca_path": "/etc/grpc-hub/clients-ca.pem",
+//!     "backends": {
+//!       "dividend-service": { "ca_path": "/etc/grpc-hub/backends-ca.pem", "domain": "dividend.internal" }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Every file is checked for changes every `reload_interval_secs` and reloaded in place:
+//! new connections use the new certificates, and backend channels reconnect with them.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use serde::Deserialize;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::crypto::ring::default_provider;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{Certificate, ClientTlsConfig};
+use x509_parser::extensions::GeneralName;
+
+use crate::auth::Identity;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Certificate chain the listeners present; plaintext if unset
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    /// CA that issues client certificates, for mutual TLS
+    pub client_ca_path: Option<PathBuf>,
+    /// Reject clients without a certificate from `client_ca_path`
+    pub require_client_cert: bool,
+    /// How often certificate files are checked for changes; 0 disables reloading
+    pub reload_interval_secs: u64,
+    /// How the hub connects to the instances of each service name
+    pub backends: HashMap<String, BackendTlsConfig>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            cert_path: None,
+            key_path: None,
+            client_ca_path: None,
+            require_client_cert: false,
+            reload_interval_secs: 10,
+            backends: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackendTlsConfig {
+    /// CA the instances' certificates are checked against; the webpki roots if unset
+    pub ca_path: Option<PathBuf>,
+    /// Client certificate presented to instances that require one
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    /// Name the instances' certificates must carry, instead of their address
+    pub domain: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cert_path.is_some() != self.key_path.is_some() {
+            return Err("tls.cert_path and tls.key_path must be set together".to_string());
+        }
+        if self.client_ca_path.is_some() && self.cert_path.is_none() {
+            return Err("tls.client_ca_path needs tls.cert_path and tls.key_path".to_string());
+        }
+        if self.require_client_cert && self.client_ca_path.is_none() {
+            return Err("tls.require_client_cert needs tls.client_ca_path".to_string());
+        }
+        match self.backends.iter().find(|(_, backend)| backend.cert_path.is_some() != backend.key_path.is_some()) {
+            Some((service, _)) => Err(format!("cert_path and key_path of backend '{}' must be set together", service)),
+            None => Ok(()),
+        }
+    }
+
+    fn reload_interval(&self) -> Option<Duration> {
+        (self.reload_interval_secs > 0).then(|| Duration::from_secs(self.reload_interval_secs))
+    }
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn load_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut read_file(path)?.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid certificate in {}", path.display()))?;
+    anyhow::ensure!(!certs.is_empty(), "No certificate in {}", path.display());
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> anyhow::Result<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut read_file(path)?.as_slice())
+        .with_context(|| format!("Invalid private key in {}", path.display()))?
+        .with_context(|| format!("No private key in {}", path.display()))
+}
+
+/// A listener's TLS settings, swapped for new ones when the certificate files change
+#[derive(Debug)]
+pub struct TlsListener {
+    config: TlsConfig,
+    alpn: Vec<Vec<u8>>,
+    server_config: RwLock<Arc<ServerConfig>>,
+}
+
+impl TlsListener {
+    /// TLS for a listener speaking the `alpn` protocols, or `None` when no certificate is configured
+    pub fn load(config: &TlsConfig, alpn: &[&[u8]]) -> anyhow::Result<Option<Arc<Self>>> {
+        if config.cert_path.is_none() {
+            return Ok(None);
+        }
+        let alpn: Vec<Vec<u8>> = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+        let server_config = server_config(config, &alpn)?;
+        let listener = Arc::new(Self {
+            config: config.clone(),
+            alpn,
+            server_config: RwLock::new(Arc::new(server_config)),
+        });
+        if let Some(interval) = config.reload_interval() {
+            let files = [&config.cert_path, &config.key_path, &config.client_ca_path].into_iter().flatten().cloned().collect();
+            let reloaded = listener.clone();
+            watch_files(files, interval, move || reloaded.reload());
+        }
+        Ok(Some(listener))
+    }
+
+    /// An acceptor with the current certificates
+    pub fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.server_config.read().unwrap().clone())
+    }
+
+    /// The connections `listener` accepts, once their handshake succeeds
+    pub fn incoming(self: Arc<Self>, listener: TcpListener) -> ReceiverStream<std::io::Result<TlsStream<TcpStream>>> {
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+                let acceptor = self.acceptor();
+                let tx = tx.clone();
+                // Handshakes run on their own, so a slow client holds up nobody else
+                tokio::spawn(async move {
+                    match acceptor.accept(stream).await {
+                        Ok(stream) => {
+                            let _ = tx.send(Ok(stream)).await;
+                        }
+                        Err(e) => println!("🔐 [TLS] Handshake with {} failed: {}", peer, e),
+                    }
+                });
+            }
+        });
+        ReceiverStream::new(rx)
+    }
+
+    fn reload(&self) -> anyhow::Result<()> {
+        *self.server_config.write().unwrap() = Arc::new(server_config(&self.config, &self.alpn)?);
+        Ok(())
+    }
+}
+
+fn server_config(config: &TlsConfig, alpn: &[Vec<u8>]) -> anyhow::Result<ServerConfig> {
+    let (Some(cert_path), Some(key_path)) = (&config.cert_path, &config.key_path) else {
+        anyhow::bail!("tls.cert_path and tls.key_path must be set together");
+    };
+    let provider = Arc::new(default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let builder = match &config.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots.add(cert).with_context(|| format!("Invalid CA certificate in {}", ca_path.display()))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if config.require_client_cert { verifier } else { verifier.allow_unauthenticated() };
+            builder.with_client_cert_verifier(verifier.build()?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder.with_single_cert(load_certs(cert_path)?, load_key(key_path)?)?;
+    server_config.alpn_protocols = alpn.to_vec();
+    Ok(server_config)
+}
+
+/// TLS settings for calls to backends, per service name
+#[derive(Debug, Default)]
+pub struct BackendTls {
+    configs: HashMap<String, BackendTlsConfig>,
+    loaded: RwLock<HashMap<String, ClientTlsConfig>>,
+    /// Bumped on every reload, so pooled channels with the old settings get replaced
+    generation: AtomicU64,
+}
+
+impl BackendTls {
+    pub fn load(config: &TlsConfig) -> anyhow::Result<Arc<Self>> {
+        let backends = Arc::new(Self {
+            configs: config.backends.clone(),
+            loaded: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        });
+        backends.reload()?;
+        if let Some(interval) = config.reload_interval().filter(|_| !config.backends.is_empty()) {
+            let files = config.backends.values()
+                .flat_map(|backend| [&backend.ca_path, &backend.cert_path, &backend.key_path])
+                .flatten()
+                .cloned()
+                .collect();
+            let reloaded = backends.clone();
+            watch_files(files, interval, move || reloaded.reload());
+        }
+        Ok(backends)
+    }
+
+    fn reload(&self) -> anyhow::Result<()> {
+        let mut loaded = HashMap::new();
+        for (service_name, backend) in &self.configs {
+            let mut tls = ClientTlsConfig::new();
+            tls = match &backend.ca_path {
+                Some(ca_path) => tls.ca_certificate(Certificate::from_pem(read_file(ca_path)?)),
+                None => tls.with_webpki_roots(),
+            };
+            if let (Some(cert_path), Some(key_path)) = (&backend.cert_path, &backend.key_path) {
+                tls = tls.identity(tonic::transport::Identity::from_pem(read_file(cert_path)?, read_file(key_path)?));
+            }
+            loaded.insert(service_name.clone(), tls);
+        }
+        *self.loaded.write().unwrap() = loaded;
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// TLS settings for calling the instance of `service_name` at `host`, or `None` for plaintext
+    pub fn client_config(&self, service_name: &str, host: &str) -> Option<ClientTlsConfig> {
+        let tls = self.loaded.read().unwrap().get(service_name)?.clone();
+        let domain = self.configs.get(service_name).and_then(|backend| backend.domain.clone());
+        Some(tls.domain_name(domain.unwrap_or_else(|| host.to_string())))
+    }
+}
+
+/// Call `reload` whenever one of `files` changes, keeping the previous settings if it fails
+fn watch_files(files: Vec<PathBuf>, interval: Duration, reload: impl Fn() -> anyhow::Result<()> + Send + 'static) {
+    let modified = move || -> Vec<Option<SystemTime>> {
+        files.iter().map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok()).collect()
+    };
+    tokio::spawn(async move {
+        let mut last = modified();
+        loop {
+            tokio::time::sleep(interval).await;
+            let current = modified();
+            if current == last {
+                continue;
+            }
+            last = current;
+            match reload() {
+                Ok(()) => println!("🔐 [TLS] Reloaded certificates"),
+                Err(e) => println!("⚠️  [TLS] Failed to reload certificates, keeping the previous ones: {:#}", e),
+            }
+        }
+    });
+}
+
+/// The caller a verified client certificate identifies: its first URI subject alternative
+/// name, or else its first DNS name
+pub fn certificate_identity(certificate: &[u8]) -> Option<Identity> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(certificate).ok()?;
+    let names = certificate.subject_alternative_name().ok()??.value.general_names.clone();
+    let uri = names.iter().find_map(|name| match name {
+        GeneralName::URI(uri) => Some(uri.to_string()),
+        _ => None,
+    });
+    let dns = || names.iter().find_map(|name| match name {
+        GeneralName::DNSName(dns) => Some(dns.to_string()),
+        _ => None,
+    });
+    uri.or_else(dns).map(|subject| Identity { subject })
+}
+
+/// The identity in the first of `certificates`, the peer's own
+pub fn peer_identity(certificates: Option<Arc<Vec<CertificateDer<'static>>>>) -> Option<Identity> {
+    certificates?.first().and_then(|certificate| certificate_identity(certificate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair, SanType};
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::TlsConnector;
+
+    #[tokio::test]
+    async fn test_mutual_tls_identifies_clients_by_san() {
+        let mut ca = CertificateParams::new(Vec::new()).unwrap();
+        ca.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca_key = KeyPair::generate().unwrap();
+        let ca = ca.self_signed(&ca_key).unwrap();
+        let server_key = KeyPair::generate().unwrap();
+        let server = CertificateParams::new(vec!["localhost".to_string()]).unwrap().signed_by(&server_key, &ca, &ca_key).unwrap();
+        let mut client = CertificateParams::new(vec!["dividend.internal".to_string()]).unwrap();
+        client.subject_alt_names.insert(0, SanType::URI("spiffe://hub/dividend".try_into().unwrap()));
+        let client_key = KeyPair::generate().unwrap();
+        let client = client.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        assert_eq!(certificate_identity(client.der()).unwrap().subject, "spiffe://hub/dividend");
+        assert_eq!(certificate_identity(server.der()).unwrap().subject, "localhost");
+        assert!(certificate_identity(b"not a certificate").is_none());
+
+        let dir = std::env::temp_dir().join(format!("grpc-hub-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, pem: String| {
+            std::fs::write(dir.join(name), pem).unwrap();
+            Some(dir.join(name))
+        };
+        let config = TlsConfig {
+            cert_path: write("hub.pem", server.pem()),
+            key_path: write("hub-key.pem", server_key.serialize_pem()),
+            client_ca_path: write("ca.pem", ca.pem()),
+            require_client_cert: true,
+            reload_interval_secs: 0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert!(TlsConfig { key_path: None, ..config.clone() }.validate().is_err());
+        let listener = TlsListener::load(&config, &[b"h2"]).unwrap().unwrap();
+
+        // A client presenting its certificate gets through, and the hub sees who it is
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = tcp.local_addr().unwrap().port();
+        let mut incoming = listener.incoming(tcp);
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone()).unwrap();
+        let client_config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(vec![client.der().clone()], PrivateKeyDer::try_from(client_key.serialize_der()).unwrap())
+            .unwrap();
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let _client = TlsConnector::from(Arc::new(client_config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap();
+        let accepted = tokio_stream::StreamExt::next(&mut incoming).await.unwrap().unwrap();
+        let certificates = accepted.get_ref().1.peer_certificates().map(|certs| Arc::new(certs.to_vec()));
+        assert_eq!(peer_identity(certificates).unwrap().subject, "spiffe://hub/dividend");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
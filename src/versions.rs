@@ -0,0 +1,205 @@
+//! Version-aware routing.
+//!
+//! A caller can ask for a semver range by suffixing the target service with it, e.g.
+//! `dividend-service@^1.2` (or with the `x-hub-version` header on proxied calls);
+//! only instances whose `service_version` satisfies the range are used. Operators can
+//! also split a service's traffic between versions, e.g. 95% to `1.0.0` and 5% to
+//! `1.1.0`, from the hub config or at runtime through `/api/traffic-splits`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rand::Rng;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tonic::Code;
+
+use crate::grpc_client::GrpcCallError;
+
+/// Split `service@range` into the service and its version range
+pub fn split_target(target: &str) -> Result<(&str, Option<VersionReq>), GrpcCallError> {
+    match target.split_once('@') {
+        Some((service, range)) => Ok((service, Some(parse_range(range)?))),
+        None => Ok((target, None)),
+    }
+}
+
+pub fn parse_range(range: &str) -> Result<VersionReq, GrpcCallError> {
+    VersionReq::parse(range.trim()).map_err(|e| {
+        GrpcCallError::new(Code::InvalidArgument, format!("Invalid version range '{}': {}", range, e))
+    })
+}
+
+/// Parse a registered version, accepting a missing minor or patch ("1", "1.2") and a leading "v"
+pub fn parse_version(version: &str) -> Option<Version> {
+    let version = version.trim().trim_start_matches('v');
+    Version::parse(version).ok().or_else(|| {
+        let parts: Vec<&str> = version.split('.').collect();
+        match parts.len() {
+            1 => Version::parse(&format!("{}.0.0", version)).ok(),
+            2 => Version::parse(&format!("{}.0", version)).ok(),
+            _ => None,
+        }
+    })
+}
+
+/// Whether a registered version satisfies `range`
+pub fn version_matches(version: &str, range: &VersionReq) -> bool {
+    parse_version(version).is_some_and(|v| range.matches(&v))
+}
+
+/// One version's share of a service's traffic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitEntry {
+    /// An exact version ("1.1.0") or a range ("^1.1")
+    pub version: String,
+    pub weight: u32,
+}
+
+impl SplitEntry {
+    /// Whether an instance with `version` belongs to this entry
+    pub fn matches(&self, version: &str) -> bool {
+        match (parse_version(&self.version), VersionReq::parse(&self.version)) {
+            // A bare version means exactly that version, not semver's default caret range
+            (Some(exact), _) if !self.version.contains(['^', '~', '=', '<', '>', '*', ',']) => {
+                parse_version(version).is_some_and(|v| v == exact)
+            }
+            (_, Ok(range)) => version_matches(version, &range),
+            _ => false,
+        }
+    }
+}
+
+/// Check a split before it is installed
+pub fn validate_split(entries: &[SplitEntry]) -> Result<(), String> {
+    if entries.is_empty() {
+        return Err("A traffic split needs at least one version".to_string());
+    }
+    if entries.iter().all(|e| e.weight == 0) {
+        return Err("A traffic split needs a non-zero weight".to_string());
+    }
+    for entry in entries {
+        if parse_version(&entry.version).is_none() && VersionReq::parse(&entry.version).is_err() {
+            return Err(format!("Invalid version '{}' in traffic split", entry.version));
+        }
+    }
+    Ok(())
+}
+
+/// The traffic splits per service name, and how many calls each version has been sent
+#[derive(Debug, Default)]
+pub struct TrafficSplits {
+    splits: RwLock<HashMap<String, Vec<SplitEntry>>>,
+    /// Calls routed per (service name, split version)
+    routed: Mutex<HashMap<(String, String), u64>>,
+}
+
+impl TrafficSplits {
+    pub fn new(splits: HashMap<String, Vec<SplitEntry>>) -> Self {
+        Self {
+            splits: RwLock::new(splits),
+            ..Self::default()
+        }
+    }
+
+    pub async fn set(&self, service_name: &str, entries: Vec<SplitEntry>) -> Result<(), String> {
+        validate_split(&entries)?;
+        println!("🔀 [SPLIT] Traffic split for '{}': {:?}", service_name, entries);
+        self.splits.write().await.insert(service_name.to_string(), entries);
+        self.routed.lock().unwrap().retain(|(service, _), _| service != service_name);
+        Ok(())
+    }
+
+    pub async fn remove(&self, service_name: &str) -> bool {
+        self.routed.lock().unwrap().retain(|(service, _), _| service != service_name);
+        self.splits.write().await.remove(service_name).is_some()
+    }
+
+    /// Pick the split entry for one call to `service_name`, among the entries that have an
+    /// instance in `versions` (the versions currently free). `None` if the service has no split.
+    pub async fn choose(&self, service_name: &str, versions: &[&str]) -> Option<SplitEntry> {
+        let splits = self.splits.read().await;
+        let eligible: Vec<&SplitEntry> = splits.get(service_name)?.iter()
+            .filter(|e| e.weight > 0 && versions.iter().any(|v| e.matches(v)))
+            .collect();
+        let total: u32 = eligible.iter().map(|e| e.weight).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = rand::thread_rng().gen_range(0..total);
+        let chosen = eligible.into_iter().find(|e| {
+            if roll < e.weight {
+                return true;
+            }
+            roll -= e.weight;
+            false
+        })?;
+        *self.routed.lock().unwrap().entry((service_name.to_string(), chosen.version.clone())).or_default() += 1;
+        Some(chosen.clone())
+    }
+
+    /// Every split with each version's configured share and the calls actually routed to it
+    pub async fn stats(&self) -> serde_json::Value {
+        let splits = self.splits.read().await;
+        let routed = self.routed.lock().unwrap();
+        let stats: serde_json::Map<String, serde_json::Value> = splits.iter().map(|(service, entries)| {
+            let total_weight: u32 = entries.iter().map(|e| e.weight).sum();
+            let total_routed: u64 = entries.iter()
+                .map(|e| routed.get(&(service.clone(), e.version.clone())).copied().unwrap_or(0))
+                .sum();
+            let versions: Vec<serde_json::Value> = entries.iter().map(|e| {
+                let count = routed.get(&(service.clone(), e.version.clone())).copied().unwrap_or(0);
+                serde_json::json!({
+                    "version": e.version,
+                    "weight": e.weight,
+                    "share_percent": e.weight as f64 * 100.0 / total_weight.max(1) as f64,
+                    "routed": count,
+                    "routed_percent": if total_routed == 0 { 0.0 } else { count as f64 * 100.0 / total_routed as f64 }
+                })
+            }).collect();
+            (service.clone(), serde_json::json!(versions))
+        }).collect();
+        serde_json::Value::Object(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges_and_split_entries() {
+        let (service, range) = split_target("dividend-service@^1.2").unwrap();
+        assert_eq!(service, "dividend-service");
+        let range = range.unwrap();
+        assert!(version_matches("1.3.0", &range));
+        assert!(version_matches("v1.2", &range));
+        assert!(!version_matches("2.0.0", &range));
+        assert!(!version_matches("latest", &range));
+        assert_eq!(split_target("dividend-service@nope").unwrap_err().code, Code::InvalidArgument);
+
+        let exact = SplitEntry { version: "1.0.0".to_string(), weight: 95 };
+        assert!(exact.matches("1.0.0"));
+        assert!(!exact.matches("1.1.0"));
+        assert!(SplitEntry { version: "~1.1".to_string(), weight: 5 }.matches("1.1.3"));
+    }
+
+    #[tokio::test]
+    async fn test_split_only_chooses_versions_with_free_instances() {
+        let splits = TrafficSplits::default();
+        assert!(splits.set("svc", vec![]).await.is_err());
+        splits.set("svc", vec![
+            SplitEntry { version: "1.0.0".to_string(), weight: 95 },
+            SplitEntry { version: "1.1.0".to_string(), weight: 5 },
+        ]).await.unwrap();
+
+        for _ in 0..20 {
+            assert_eq!(splits.choose("svc", &["1.1.0"]).await.unwrap().version, "1.1.0");
+        }
+        assert!(splits.choose("svc", &["2.0.0"]).await.is_none());
+        assert!(splits.choose("other", &["1.0.0"]).await.is_none());
+        assert_eq!(splits.stats().await["svc"][1]["routed"], 20);
+    }
+}